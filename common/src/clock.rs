@@ -0,0 +1,122 @@
+// Gain applied to the offset error from each GPS fix. Fix timestamps arrive over the
+// UART with tens of ms of jitter, so they only nudge the clock.
+const FIX_GAIN: f64 = 0.1;
+// PPS edges are accurate to microseconds, trust them more
+const PPS_GAIN: f64 = 0.5;
+// Errors bigger than this are a new time source or a receiver reset, not drift
+const STEP_MS: f64 = 1000.0;
+// Fixes are ignored for fine adjustment while PPS is arriving
+const PPS_TIMEOUT_MS: u64 = 2000;
+// Drift is measured as the slope of the filtered offset over this much uptime,
+// long enough that the remaining fix jitter is a few ppm
+const DRIFT_WINDOW_MS: u64 = 300_000;
+// Crystals are good to ~100ppm, anything more is noise
+const MAX_DRIFT: f64 = 500e-6;
+
+/// Maps local uptime to GPS time. Each fix (or PPS edge) pulls the offset toward the
+/// observed value, and the long term drift of the local crystal is tracked so the
+/// clock holds time between fixes and through GPS outages.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    // GPS time minus uptime at `last_sync`, ms
+    offset: f64,
+    // rate of change of the offset, ms per ms of uptime
+    drift: f64,
+    last_sync: Option<u64>,
+    last_pps: Option<u64>,
+    // (uptime, offset) at the start of the current drift window
+    drift_anchor: Option<(u64, f64)>,
+}
+
+impl Clock {
+    pub fn is_synced(&self) -> bool {
+        self.last_sync.is_some()
+    }
+
+    fn offset_at(&self, uptime: u64) -> f64 {
+        match self.last_sync {
+            Some(last_sync) => self.offset + self.drift * (uptime as f64 - last_sync as f64),
+            None => 0.0,
+        }
+    }
+
+    /// GPS time in ms since the unix epoch, or uptime if we've never synced
+    pub fn now(&self, uptime: u64) -> u64 {
+        let now = uptime as f64 + self.offset_at(uptime);
+        if now > 0.0 {
+            now as u64
+        } else {
+            0
+        }
+    }
+
+    /// Drift of the local clock against GPS, in ppm
+    pub fn drift_ppm(&self) -> f64 {
+        self.drift * 1e6
+    }
+
+    /// ms of uptime since the last fix or PPS edge was applied
+    pub fn sync_age(&self, uptime: u64) -> Option<u64> {
+        self.last_sync.map(|last_sync| uptime.saturating_sub(last_sync))
+    }
+
+    pub fn has_pps(&self, uptime: u64) -> bool {
+        matches!(self.last_pps, Some(last_pps) if uptime.saturating_sub(last_pps) < PPS_TIMEOUT_MS)
+    }
+
+    /// Apply a fix time received at `uptime`
+    pub fn fix_event(&mut self, time: u64, uptime: u64) {
+        let measured = time as f64 - uptime as f64;
+        if self.has_pps(uptime) {
+            // PPS keeps the sub-second part, fixes only catch a wrong second
+            let error = measured - self.offset_at(uptime);
+            if (-STEP_MS / 2.0..STEP_MS / 2.0).contains(&error) {
+                return;
+            }
+        }
+        self.discipline(measured, uptime, FIX_GAIN);
+    }
+
+    /// Apply a PPS edge seen at `uptime`. The edge marks the start of a GPS second,
+    /// so it's only useful once fixes have put us within half a second.
+    pub fn pps_event(&mut self, uptime: u64) {
+        if !self.is_synced() {
+            return;
+        }
+
+        let estimate = self.now(uptime) as i64;
+        let second = (estimate + 500).div_euclid(1000) * 1000;
+        self.last_pps = Some(uptime);
+        self.discipline(second as f64 - uptime as f64, uptime, PPS_GAIN);
+    }
+
+    fn discipline(&mut self, measured: f64, uptime: u64, gain: f64) {
+        let error = measured - self.offset_at(uptime);
+        if self.last_sync.is_none() || !(-STEP_MS..=STEP_MS).contains(&error) {
+            log::info!("clock step to offset {} ms", measured as i64);
+            self.offset = measured;
+            self.drift_anchor = None;
+        } else {
+            self.offset = self.offset_at(uptime) + gain * error;
+        }
+        self.last_sync = Some(uptime);
+
+        match self.drift_anchor {
+            None => self.drift_anchor = Some((uptime, self.offset)),
+            Some((anchor_uptime, anchor_offset)) => {
+                let window = uptime.saturating_sub(anchor_uptime);
+                if window >= DRIFT_WINDOW_MS {
+                    let measured_drift = (self.offset - anchor_offset) / window as f64;
+                    // the first window sets the drift, later windows refine it
+                    let drift = if self.drift == 0.0 {
+                        measured_drift
+                    } else {
+                        (self.drift + measured_drift) / 2.0
+                    };
+                    self.drift = drift.clamp(-MAX_DRIFT, MAX_DRIFT);
+                    self.drift_anchor = Some((uptime, self.offset));
+                }
+            }
+        }
+    }
+}
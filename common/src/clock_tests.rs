@@ -0,0 +1,161 @@
+mod tests {
+    use crate::clock::Clock;
+
+    // 2024-03-14T02:30:40Z
+    const GPS_START: u64 = 1_710_383_440_000;
+    // fixes are received 30-50ms after the epoch they describe
+    const LATENCY_MS: u64 = 40;
+
+    /// A local crystal running `ppm` fast, with fixes arriving over a jittery UART
+    struct Sim {
+        ppm: f64,
+        boot_uptime: u64,
+        seed: u32,
+        last_received: u64,
+    }
+
+    impl Sim {
+        fn new(ppm: f64) -> Self {
+            Self {
+                ppm,
+                boot_uptime: 5_000,
+                seed: 1,
+                last_received: 0,
+            }
+        }
+
+        // uptime at GPS time `time`
+        fn uptime(&self, time: u64) -> u64 {
+            let elapsed = (time - GPS_START) as f64;
+            self.boot_uptime + (elapsed * (1.0 + self.ppm * 1e-6)) as u64
+        }
+
+        fn jitter(&mut self) -> u64 {
+            self.seed = self.seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (self.seed >> 16) as u64 % 21
+        }
+
+        // deliver the fix for GPS time `time`, at 10Hz
+        fn fix(&mut self, clock: &mut Clock, time: u64) {
+            let received = time + LATENCY_MS - 10 + self.jitter();
+            self.last_received = self.uptime(received);
+            clock.fix_event(time, self.last_received);
+        }
+
+        // run 10Hz fixes from `from` for `seconds`, returning the worst error seen
+        // (against GPS time, less the average latency) once the filter has settled
+        fn run(&mut self, clock: &mut Clock, from: u64, seconds: u64) -> i64 {
+            let mut worst = 0;
+            for i in 0..seconds * 10 {
+                let time = from + i * 100;
+                self.fix(clock, time);
+                if i > 50 {
+                    worst = worst.max(self.error(clock, time + 50).abs());
+                }
+            }
+            worst
+        }
+
+        fn error(&self, clock: &Clock, time: u64) -> i64 {
+            clock.now(self.uptime(time)) as i64 - (time - LATENCY_MS) as i64
+        }
+    }
+
+    #[test]
+    fn test_first_fix() {
+        let mut clock = Clock::default();
+        assert!(!clock.is_synced());
+        assert_eq!(clock.now(1234), 1234);
+        assert_eq!(clock.sync_age(1234), None);
+
+        clock.fix_event(GPS_START, 1_000);
+        assert!(clock.is_synced());
+        assert_eq!(clock.now(1_000), GPS_START);
+        // sub-second
+        assert_eq!(clock.now(1_250), GPS_START + 250);
+        assert_eq!(clock.sync_age(1_250), Some(250));
+        assert_eq!(clock.drift_ppm(), 0.0);
+    }
+
+    #[test]
+    fn test_drift() {
+        // a cheap crystal, 80ppm fast is ~0.6s over a two hour race day
+        let mut sim = Sim::new(80.0);
+        let mut clock = Clock::default();
+
+        let worst = sim.run(&mut clock, GPS_START, 2 * 60 * 60);
+        assert!(worst <= 15, "worst error {} ms", worst);
+
+        // local ticks are fast, so the offset to GPS time shrinks
+        let drift = clock.drift_ppm();
+        assert!(drift > -90.0 && drift < -70.0, "drift {} ppm", drift);
+    }
+
+    #[test]
+    fn test_holdover() {
+        let mut sim = Sim::new(-60.0);
+        let mut clock = Clock::default();
+        sim.run(&mut clock, GPS_START, 60 * 60);
+        let last_fix = GPS_START + 60 * 60 * 1000;
+
+        // lose the antenna for half an hour, uncorrected that's 108ms
+        let later = last_fix + 30 * 60 * 1000;
+        let error = sim.error(&clock, later);
+        assert!(error.abs() < 30, "error {} ms", error);
+        assert_eq!(
+            clock.sync_age(sim.uptime(later)),
+            Some(sim.uptime(later) - sim.last_received),
+        );
+    }
+
+    #[test]
+    fn test_step() {
+        let mut sim = Sim::new(20.0);
+        let mut clock = Clock::default();
+        sim.run(&mut clock, GPS_START, 60);
+
+        // receiver reset with a time an hour out, then corrected
+        let time = GPS_START + 61_000;
+        clock.fix_event(time + 3_600_000, sim.uptime(time + LATENCY_MS));
+        assert_eq!(sim.error(&clock, time + LATENCY_MS), 3_600_000);
+
+        let worst = sim.run(&mut clock, time + 100, 60);
+        assert!(worst <= 15, "worst error {} ms", worst);
+
+        // small errors are filtered, not stepped
+        let time = GPS_START + 122_000;
+        let before = sim.error(&clock, time + LATENCY_MS);
+        clock.fix_event(time + 500, sim.uptime(time + LATENCY_MS));
+        let after = sim.error(&clock, time + LATENCY_MS);
+        assert!(after - before > 0 && after - before < 100);
+    }
+
+    #[test]
+    fn test_pps() {
+        let mut sim = Sim::new(40.0);
+        let mut clock = Clock::default();
+
+        // PPS is ignored until fixes give us the second
+        clock.pps_event(sim.uptime(GPS_START));
+        assert!(!clock.is_synced());
+
+        sim.run(&mut clock, GPS_START, 10);
+        for second in 10..600 {
+            let edge = GPS_START + second * 1000;
+            clock.pps_event(sim.uptime(edge));
+            for i in 0..10 {
+                sim.fix(&mut clock, edge + i * 100);
+            }
+            assert!(clock.has_pps(sim.uptime(edge + 900)));
+        }
+
+        // PPS removes the UART latency fixes can't see
+        let time = GPS_START + 600_000;
+        let error = clock.now(sim.uptime(time)) as i64 - time as i64;
+        assert!(error.abs() <= 1, "error {} ms", error);
+
+        // and fixes still catch a wrong second
+        clock.fix_event(time + 2_000, sim.uptime(time));
+        assert_eq!(clock.now(sim.uptime(time)), time + 2_000);
+    }
+}
@@ -1,12 +1,17 @@
 // Standard library imports
 use core::{
+    cell::Cell,
     fmt::{Debug, Display},
     sync::atomic::Ordering,
 };
 
 // Embassy framework imports
 use embassy_futures::select::{select, Either};
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, pubsub::PubSubChannel};
+use embassy_sync::{
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex as BlockingMutex},
+    pubsub::PubSubChannel,
+    signal::Signal,
+};
 use embassy_time::{Duration, Timer};
 
 // Networking imports
@@ -26,14 +31,18 @@ use edge_net::{
 use embedded_io_async::{Read, Write};
 use heapless::Vec;
 // use panic_probe as _;
-use portable_atomic::AtomicU64;
+use portable_atomic::AtomicU32;
 
-use extreme_traits::RawEngine;
+use crate::clock::Clock;
+use crate::storage::{decode_record, encode_record, Storage, MAX_RECORD_SIZE};
+use extreme_traits::{Fix, RawEngine};
 
 // Constants
-pub const MAX_MESSAGE_SIZE: usize = 512;
+pub use extreme_traits::MAX_MESSAGE_SIZE;
 pub const MAX_WEB_SOCKETS: usize = 4;
-pub const SOCKET_BUFFER_SIZE: usize = MAX_MESSAGE_SIZE * 4;
+// the TCP buffers didn't grow with the messages, but still hold a couple of frames
+pub const SOCKET_BUFFER_SIZE: usize = 2048;
+const _: () = assert!(SOCKET_BUFFER_SIZE >= 2 * (MAX_MESSAGE_SIZE + FrameHeader::MAX_LEN));
 
 // Type aliases
 type UpdateMessage = Vec<u8, MAX_MESSAGE_SIZE>;
@@ -43,9 +52,12 @@ where
     Engine: RawEngine,
 {
     engine: embassy_sync::mutex::Mutex<CriticalSectionRawMutex, Engine>,
-    tick_offset: AtomicU64,
+    clock: BlockingMutex<CriticalSectionRawMutex, Cell<Clock>>,
+    gps_dropped: AtomicU32,
     sleep_channel: PubSubChannel<CriticalSectionRawMutex, u64, 1, 4, 4>,
     broadcast_channel: PubSubChannel<CriticalSectionRawMutex, UpdateMessage, 1, 4, 4>,
+    // raised when a client changes the engine, so the storage task saves it
+    save_signal: Signal<CriticalSectionRawMutex, ()>,
 }
 
 impl<Engine> HttpHandler<Engine>
@@ -57,35 +69,65 @@ where
             broadcast_channel: PubSubChannel::new(),
             sleep_channel: PubSubChannel::new(),
             engine: embassy_sync::mutex::Mutex::new(engine),
-            tick_offset: AtomicU64::new(0),
+            clock: BlockingMutex::new(Cell::new(Clock::default())),
+            gps_dropped: AtomicU32::new(0),
+            save_signal: Signal::new(),
         }
     }
 
-    pub async fn location_event(
-        &self,
-        time: Option<u64>,
-        location: Option<(f64, f64)>,
-        speed: Option<(f64, f64)>,
-    ) {
-        // log::info!("location_event: {:?}, {:?}, {:?}", time, location, speed);
-        let timestamp = match time {
-            Some(timestamp) => {
-                let mut offset = self.tick_offset.load(Ordering::Relaxed);
-                let uptime = embassy_time::Instant::now().as_millis() as u64;
-                if offset == 0 {
-                    offset = timestamp - uptime;
-                    self.tick_offset.store(offset, Ordering::Relaxed);
+    /// Record the number of GPS sentences dropped by the parser, reported to clients with each update
+    pub fn set_gps_dropped(&self, dropped: u32) {
+        self.gps_dropped.store(dropped, Ordering::Relaxed);
+    }
+
+    /// Current GPS time in ms since the unix epoch, or uptime until the first fix
+    pub fn now(&self) -> u64 {
+        let uptime = embassy_time::Instant::now().as_millis();
+        self.clock.lock(|clock| clock.get().now(uptime))
+    }
+
+    fn update_clock(&self, update: impl FnOnce(&mut Clock, u64)) {
+        let uptime = embassy_time::Instant::now().as_millis();
+        self.clock.lock(|cell| {
+            let mut clock = cell.get();
+            update(&mut clock, uptime);
+            cell.set(clock);
+        });
+    }
+
+    /// Discipline the clock from a PPS edge, call as close to the edge as possible
+    pub fn pps_event(&self) {
+        self.update_clock(|clock, uptime| clock.pps_event(uptime));
+    }
+
+    fn wrap_message(&self, message: &[u8]) -> Result<UpdateMessage, ()> {
+        let uptime = embassy_time::Instant::now().as_millis();
+        let clock = self.clock.lock(|clock| clock.get());
+        let sync = clock
+            .sync_age(uptime)
+            .map(|sync_age| (sync_age, clock.drift_ppm() as i64));
+        wrap_message(
+            message,
+            clock.now(uptime),
+            sync,
+            self.gps_dropped.load(Ordering::Relaxed),
+        )
+    }
+
+    pub async fn location_event(&self, fix: Fix) {
+        // log::info!("location_event: {:?}", fix);
+        if let Some(time) = fix.time {
+            // a void fix can still carry the receiver's clock, good enough to start with
+            self.update_clock(|clock, uptime| {
+                if fix.is_valid() || !clock.is_synced() {
+                    clock.fix_event(time, uptime);
                 }
-                offset + timestamp
-            }
-            None => {
-                self.tick_offset.load(Ordering::Relaxed)
-                    + embassy_time::Instant::now().as_millis() as u64
-            }
-        };
+            });
+        }
+        let timestamp = self.now();
 
         let mut engine = self.engine.lock().await;
-        let (update, timer) = (*engine).location_event(timestamp, location, speed);
+        let (update, timer) = (*engine).location_event(timestamp, &fix);
 
         // handle state update if there was one
         if let Some(()) = update {
@@ -142,9 +184,7 @@ where
                 Some(wake_time) => {
                     // so sleep!
                     // convert absolute wake time to a duration
-                    let offset = self.tick_offset.load(Ordering::Relaxed);
-
-                    let now = embassy_time::Instant::now().as_millis() + offset;
+                    let now = self.now();
                     let sleep_ms = if wake_time > now { wake_time - now } else { 0 };
 
                     log::info!("sleeping for {} ms", sleep_ms);
@@ -195,6 +235,61 @@ where
             }
         }
     }
+
+    /// Restore the engine from `storage`, then save it whenever a client changes it
+    pub async fn run_storage<S: Storage>(&self, storage: &mut S) -> ! {
+        let mut record = [0_u8; MAX_RECORD_SIZE];
+        // what's in storage, so unchanged state isn't written again
+        let mut saved: Vec<u8, MAX_RECORD_SIZE> = Vec::new();
+
+        match storage.read(&mut record).await {
+            Ok(len) => {
+                if let Some(data) = decode_record(&record[..len]) {
+                    let mut engine = self.engine.lock().await;
+                    if (*engine).load(data).is_ok() {
+                        log::info!("restored saved state");
+                        saved = Vec::from_slice(&record[..len]).unwrap_or_default();
+                        if let (Ok(message), Ok(publisher)) =
+                            ((*engine).to_vec(), self.broadcast_channel.publisher())
+                        {
+                            publisher.publish_immediate(message);
+                        }
+                    } else {
+                        log::warn!("failed to restore saved state");
+                    }
+                }
+            }
+            Err(_) => log::error!("failed to read storage"),
+        }
+
+        loop {
+            self.save_signal.wait().await;
+            // let a burst of changes settle, flash doesn't like being written
+            Timer::after(Duration::from_secs(1)).await;
+            self.save_signal.reset();
+
+            let mut data = [0_u8; MAX_RECORD_SIZE];
+            let result = {
+                let engine = self.engine.lock().await;
+                (*engine).save(&mut data)
+            };
+            let len = match result.and_then(|len| encode_record(&data[..len], &mut record)) {
+                Ok(len) => len,
+                Err(_) => {
+                    log::error!("Failed to save engine state");
+                    continue;
+                }
+            };
+
+            if saved.as_slice() == &record[..len] {
+                continue;
+            }
+            match storage.write(&record[..len]).await {
+                Ok(()) => saved = Vec::from_slice(&record[..len]).unwrap_or_default(),
+                Err(_) => log::error!("Failed to write storage"),
+            }
+        }
+    }
 }
 
 impl<Engine> Handler for HttpHandler<Engine>
@@ -261,20 +356,8 @@ where
             };
 
             // scoped so we release the lock ASAP
-            match vec {
-                Ok(message) => {
-                    // Build JSON wrapper manually since message is already serialized JSON
-                    let mut wrapper = UpdateMessage::new();
-                    wrapper.extend_from_slice(b"{\"timestamp\":").unwrap();
-                    let timestamp = embassy_time::Instant::now().as_millis()
-                        + self.tick_offset.load(Ordering::Relaxed);
-                    if u64_to_heapless_vec(timestamp, &mut wrapper).is_err() {
-                        log::error!("failed to render timestamp");
-                    }
-                    wrapper.extend_from_slice(b",\"engine\":").unwrap();
-                    wrapper.extend_from_slice(&message).unwrap();
-                    wrapper.extend_from_slice(b"}").unwrap();
-
+            match vec.and_then(|message| self.wrap_message(&message)) {
+                Ok(wrapper) => {
                     let header = FrameHeader {
                         mask_key: None,
                         frame_type: FrameType::Text(false), // no clue why false is required, but it is
@@ -357,10 +440,9 @@ where
                         // );
 
                         // get the current time
-                        let offset = self.tick_offset.load(Ordering::Relaxed);
                         let (update, timer) = {
                             let mut engine = self.engine.lock().await;
-                            let now = embassy_time::Instant::now().as_millis() + offset;
+                            let now = self.now();
 
                             // handle the event
                             match RawEngine::external_event(&mut *engine, now, payload) {
@@ -374,6 +456,7 @@ where
 
                         // handle state update if there was one
                         if let Some(update) = update {
+                            self.save_signal.signal(());
                             if let Ok(publisher) = self.broadcast_channel.publisher() {
                                 publisher.publish_immediate(update);
                             } else {
@@ -395,17 +478,13 @@ where
                         // break on any comms error
                         // log::info!("broadcast message");
 
-                        // Build JSON wrapper manually since message is already serialized JSON
-                        let mut wrapper = UpdateMessage::new();
-                        wrapper.extend_from_slice(b"{\"timestamp\":").unwrap();
-                        let timestamp = embassy_time::Instant::now().as_millis()
-                            + self.tick_offset.load(Ordering::Relaxed);
-                        if u64_to_heapless_vec(timestamp, &mut wrapper).is_err() {
-                            log::error!("failed to render timestamp");
-                        }
-                        wrapper.extend_from_slice(b",\"engine\":").unwrap();
-                        wrapper.extend_from_slice(&message).unwrap();
-                        wrapper.extend_from_slice(b"}").unwrap();
+                        let wrapper = match self.wrap_message(&message) {
+                            Ok(wrapper) => wrapper,
+                            Err(_) => {
+                                log::error!("Failed to wrap engine state");
+                                continue;
+                            }
+                        };
 
                         let header = FrameHeader {
                             mask_key: None,
@@ -430,6 +509,35 @@ where
     }
 }
 
+/// Wrap an engine's message for the clients with the time, the clock's sync age and
+/// drift in ppm if it's been synced, and the GPS sentences dropped. Built by hand since
+/// the message is already serialized JSON.
+pub fn wrap_message(
+    message: &[u8],
+    timestamp: u64,
+    sync: Option<(u64, i64)>,
+    gps_dropped: u32,
+) -> Result<UpdateMessage, ()> {
+    let mut wrapper = UpdateMessage::new();
+    wrapper.extend_from_slice(b"{\"timestamp\":")?;
+    u64_to_heapless_vec(timestamp, &mut wrapper)?;
+    if let Some((sync_age, drift)) = sync {
+        wrapper.extend_from_slice(b",\"sync_age\":")?;
+        u64_to_heapless_vec(sync_age, &mut wrapper)?;
+        wrapper.extend_from_slice(b",\"drift_ppm\":")?;
+        if drift < 0 {
+            wrapper.push(b'-').map_err(|_| ())?;
+        }
+        u64_to_heapless_vec(drift.unsigned_abs(), &mut wrapper)?;
+    }
+    wrapper.extend_from_slice(b",\"gps_dropped\":")?;
+    u64_to_heapless_vec(gps_dropped as u64, &mut wrapper)?;
+    wrapper.extend_from_slice(b",\"engine\":")?;
+    wrapper.extend_from_slice(message)?;
+    wrapper.extend_from_slice(b"}")?;
+    Ok(wrapper)
+}
+
 fn u64_to_heapless_vec<const N: usize>(mut num: u64, vec: &mut Vec<u8, N>) -> Result<(), ()> {
     if num == 0 {
         vec.extend_from_slice(&[b'0'])?;
@@ -1,4 +1,11 @@
 #![no_std]
 
 // Re-export modules
-pub mod http; 
\ No newline at end of file
+pub mod clock;
+pub mod http; 
+pub mod storage;
+
+#[cfg(test)]
+mod clock_tests;
+#[cfg(test)]
+mod storage_tests;
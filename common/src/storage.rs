@@ -0,0 +1,67 @@
+// Start of every record, erased flash reads as 0xFF and a missing file as nothing
+const MAGIC: [u8; 2] = *b"XS";
+// Bump when the record layout changes, old records are ignored. Engines version their
+// own saved state.
+pub const RECORD_VERSION: u8 = 1;
+const HEADER_LEN: usize = 5;
+const CHECKSUM_LEN: usize = 2;
+/// Largest record, header and checksum included
+pub const MAX_RECORD_SIZE: usize = 256;
+
+/// A single block of non-volatile storage, holding the engine state that survives a
+/// reboot. Flash on the boards, a file on the desktop.
+#[allow(async_fn_in_trait)]
+pub trait Storage {
+    /// Read the stored block into `buf`, returning the number of bytes read. Storage
+    /// that was never written can return anything, records are checked by the caller.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()>;
+
+    /// Replace the stored block with `data`
+    async fn write(&mut self, data: &[u8]) -> Result<(), ()>;
+}
+
+fn fletcher16(data: &[u8]) -> u16 {
+    let (a, b) = data.iter().fold((0u16, 0u16), |(a, b), &byte| {
+        let a = (a + byte as u16) % 255;
+        (a, (b + a) % 255)
+    });
+    (b << 8) | a
+}
+
+/// Wrap `data` in a record with a header and checksum, returning its length
+#[allow(clippy::result_unit_err)]
+pub fn encode_record(data: &[u8], buf: &mut [u8]) -> Result<usize, ()> {
+    let len = HEADER_LEN + data.len() + CHECKSUM_LEN;
+    if len > buf.len() || len > MAX_RECORD_SIZE {
+        return Err(());
+    }
+
+    buf[..2].copy_from_slice(&MAGIC);
+    buf[2] = RECORD_VERSION;
+    buf[3..5].copy_from_slice(&(data.len() as u16).to_le_bytes());
+    buf[HEADER_LEN..HEADER_LEN + data.len()].copy_from_slice(data);
+    let checksum = fletcher16(&buf[2..HEADER_LEN + data.len()]);
+    buf[HEADER_LEN + data.len()..len].copy_from_slice(&checksum.to_le_bytes());
+    Ok(len)
+}
+
+/// The data in a record written by `encode_record`, or None if the record is blank,
+/// corrupt or from another version
+pub fn decode_record(record: &[u8]) -> Option<&[u8]> {
+    if record.len() < HEADER_LEN + CHECKSUM_LEN || record[..2] != MAGIC {
+        return None;
+    }
+    if record[2] != RECORD_VERSION {
+        log::info!("ignoring saved state version {}", record[2]);
+        return None;
+    }
+
+    let len = u16::from_le_bytes([record[3], record[4]]) as usize;
+    let end = HEADER_LEN + len;
+    let checksum = record.get(end..end + CHECKSUM_LEN)?;
+    if fletcher16(&record[2..end]).to_le_bytes() != checksum {
+        log::warn!("saved state is corrupt");
+        return None;
+    }
+    Some(&record[HEADER_LEN..end])
+}
@@ -0,0 +1,51 @@
+mod tests {
+    use crate::storage::{decode_record, encode_record, MAX_RECORD_SIZE, RECORD_VERSION};
+
+    fn record(data: &[u8]) -> ([u8; MAX_RECORD_SIZE], usize) {
+        let mut buf = [0_u8; MAX_RECORD_SIZE];
+        let len = encode_record(data, &mut buf).unwrap();
+        (buf, len)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = [1, 0x7F, 0xFF, 0, 42];
+        let (buf, len) = record(&data);
+        assert_eq!(len, data.len() + 7);
+        assert_eq!(buf[2], RECORD_VERSION);
+        assert_eq!(decode_record(&buf[..len]), Some(&data[..]));
+        // storage hands back the whole block, trailing bytes are ignored
+        assert_eq!(decode_record(&buf), Some(&data[..]));
+
+        // no engine selected
+        let (buf, len) = record(&[]);
+        assert_eq!(decode_record(&buf[..len]), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_bad_records() {
+        // blank flash, missing file
+        assert_eq!(decode_record(&[0xFF; 64]), None);
+        assert_eq!(decode_record(&[]), None);
+
+        let (buf, len) = record(&[1, 2, 3, 4]);
+        for i in 0..len {
+            let mut corrupt = buf;
+            corrupt[i] ^= 0x04;
+            assert_eq!(decode_record(&corrupt[..len]), None, "byte {}", i);
+        }
+
+        // truncated
+        assert_eq!(decode_record(&buf[..len - 1]), None);
+
+        // older layout
+        let mut old = buf;
+        old[2] = RECORD_VERSION.wrapping_sub(1);
+        assert_eq!(decode_record(&old[..len]), None);
+
+        // too big
+        let mut buf = [0_u8; MAX_RECORD_SIZE + 16];
+        assert_eq!(encode_record(&[0; MAX_RECORD_SIZE], &mut buf), Err(()));
+        assert_eq!(encode_record(&[0; 8], &mut buf[..10]), Err(()));
+    }
+}
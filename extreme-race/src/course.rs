@@ -0,0 +1,126 @@
+use crate::geo_math::distance;
+use crate::line::R;
+use crate::types::Location;
+use extreme_traits::{SnapshotReader, SnapshotWriter};
+
+/// Most marks a course can have
+pub const MAX_MARKS: usize = 8;
+
+// The boat has rounded a mark once it's been this close and sailed away again, metres
+const ROUNDING_DISTANCE: f64 = 30.0;
+
+/// The marks of the course in the order they're rounded, and the one sailed to next
+#[derive(Copy, Clone, PartialEq, Default)]
+pub struct Course {
+    marks: [Location; MAX_MARKS],
+    count: usize,
+    // index of the next mark, `count` once the last has been rounded
+    leg: usize,
+    // the boat has been within the rounding distance of the next mark
+    at_mark: bool,
+}
+
+impl Course {
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Index of the next mark
+    pub fn leg(&self) -> usize {
+        self.leg
+    }
+
+    /// The mark being sailed to, None before there are marks or after the last
+    pub fn next_mark(&self) -> Option<Location> {
+        self.marks[..self.count].get(self.leg).copied()
+    }
+
+    /// Metres from `start` round the marks to `finish`, None without at least two
+    /// points to measure between
+    pub fn length(&self, start: Option<Location>, finish: Option<Location>) -> Option<f64> {
+        let mut points = start
+            .into_iter()
+            .chain(self.marks[..self.count].iter().copied())
+            .chain(finish);
+        let mut from = points.next()?;
+        let mut length = None;
+        for to in points {
+            *length.get_or_insert(0.0) += distance(from.lat, from.lon, to.lat, to.lon, R);
+            from = to;
+        }
+        length
+    }
+
+    /// Add a mark to the end of the course, None if it's full
+    pub fn add(&mut self, mark: Location) -> Option<()> {
+        if self.count == MAX_MARKS {
+            return None;
+        }
+        self.marks[self.count] = mark;
+        self.count += 1;
+        Some(())
+    }
+
+    pub fn clear(&mut self) -> Option<()> {
+        if self.is_empty() {
+            return None;
+        }
+        *self = Course::default();
+        Some(())
+    }
+
+    /// Sail to the next mark without rounding this one
+    pub fn advance(&mut self) -> Option<()> {
+        self.next_mark()?;
+        self.leg += 1;
+        self.at_mark = false;
+        Some(())
+    }
+
+    /// Back to the first mark, for a new race
+    pub fn restart(&mut self) {
+        self.leg = 0;
+        self.at_mark = false;
+    }
+
+    /// Move on to the next leg once the boat has been to the mark and left it
+    pub fn update_location(&mut self, location: Location) -> Option<()> {
+        let mark = self.next_mark()?;
+        let near = distance(location.lat, location.lon, mark.lat, mark.lon, R) < ROUNDING_DISTANCE;
+        if near {
+            self.at_mark = true;
+            return None;
+        }
+        if !self.at_mark {
+            return None;
+        }
+        self.advance()
+    }
+
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u8(self.count as u8);
+        for mark in &self.marks[..self.count] {
+            mark.snapshot(out);
+        }
+        out.u8(self.leg as u8);
+        out.bool(self.at_mark);
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        let mut course = Course::default();
+        for _ in 0..input.u8()? {
+            let mark = Location::restore(input)?;
+            course.add(mark).ok_or(())?;
+        }
+        course.leg = input.u8()? as usize;
+        if course.leg > course.count {
+            return Err(());
+        }
+        course.at_mark = input.bool()?;
+        Ok(course)
+    }
+}
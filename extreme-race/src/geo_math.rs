@@ -1,6 +1,6 @@
 use core::f64::consts::PI;
 
-use libm::{asin, atan2, cos, fmax, fmod, pow, sin, sqrt};
+use libm::{acos, asin, atan2, cos, fmax, fmin, fmod, pow, sin, sqrt};
 
 fn _local_radius(lat: f64) -> f64 {
     let wgs_ellipsoid = (6378137.0, 6356752.314);
@@ -79,14 +79,7 @@ fn intersection_point(
     r: f64,
 ) -> (f64, f64) {
     // Calculate the end point of the second line using the distance and heading
-    let distance = 5000.0;
-    let ep_lat =
-        asin(sin(pt_lat) * cos(distance / r) + cos(pt_lat) * sin(distance / r) * cos(pt_heading));
-    let ep_lon = pt_lon
-        + atan2(
-            sin(pt_heading) * sin(distance / r) * cos(pt_lat),
-            cos(distance / r) - sin(pt_lat) * sin(ep_lat),
-        );
+    let (ep_lat, ep_lon) = destination(pt_lat, pt_lon, pt_heading, 5000.0, r);
 
     // Find the intersection point using the great_circle_intersection function
     let (intersection_lat, intersection_lon) =
@@ -105,6 +98,21 @@ pub fn distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64, r: f64) -> f64 {
     r * c
 }
 
+/// The point `distance` metres from (lat, lon) on initial bearing `heading`, all
+/// angles in radians
+pub fn destination(lat: f64, lon: f64, heading: f64, distance: f64, r: f64) -> (f64, f64) {
+    let angle = distance / r;
+    let dest_lat = asin(sin(lat) * cos(angle) + cos(lat) * sin(angle) * cos(heading));
+    let dest_lon = lon
+        + atan2(
+            sin(heading) * sin(angle) * cos(lat),
+            cos(angle) - sin(lat) * sin(dest_lat),
+        );
+
+    // Normalize longitude to the range -pi to pi
+    (dest_lat, fmod(dest_lon + 3.0 * PI, 2.0 * PI) - PI)
+}
+
 pub fn bearing(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
     let dl = lon2 - lon1;
     let x = cos(lat2) * sin(dl);
@@ -116,7 +124,48 @@ pub fn bearing(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
     bearing_rad
 }
 
-fn simple_diff(heading1: f64, heading2: f64) -> f64 {
+/// Distance from a point to the great circle through points 1 and 2, in the units
+/// of `r`. Positive when the point is to the right looking from 1 to 2.
+pub fn cross_track_distance(
+    lat: f64,
+    lon: f64,
+    lat1: f64,
+    lon1: f64,
+    lat2: f64,
+    lon2: f64,
+    r: f64,
+) -> f64 {
+    let angular_distance = distance(lat1, lon1, lat, lon, 1.0);
+    let path_bearing = bearing(lat1, lon1, lat2, lon2);
+    let point_bearing = bearing(lat1, lon1, lat, lon);
+    asin(sin(angular_distance) * sin(point_bearing - path_bearing)) * r
+}
+
+/// Distance from point 1, along the great circle through points 1 and 2, to the
+/// point on it closest to (lat, lon). Negative when that's behind point 1.
+pub fn along_track_distance(
+    lat: f64,
+    lon: f64,
+    lat1: f64,
+    lon1: f64,
+    lat2: f64,
+    lon2: f64,
+    r: f64,
+) -> f64 {
+    let angular_distance = distance(lat1, lon1, lat, lon, 1.0);
+    let angle = bearing(lat1, lon1, lat, lon) - bearing(lat1, lon1, lat2, lon2);
+    let cross_track = asin(sin(angular_distance) * sin(angle));
+    // rounding can push the ratio just past 1 for a point on the line
+    let along = acos(fmin(cos(angular_distance) / cos(cross_track), 1.0));
+    if cos(angle) < 0.0 {
+        -along * r
+    } else {
+        along * r
+    }
+}
+
+/// heading1 - heading2, in radians between -pi and pi
+pub fn simple_diff(heading1: f64, heading2: f64) -> f64 {
     let mut heading1 = fmod(heading1, 2.0 * PI);
     let mut heading2 = fmod(heading2, 2.0 * PI);
 
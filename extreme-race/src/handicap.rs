@@ -0,0 +1,87 @@
+use extreme_traits::{SnapshotReader, SnapshotWriter};
+use serde::{Deserialize, Serialize};
+
+/// A boat's handicap, in the system it's raced under. Times are seconds.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum Rating {
+    /// IRC, ORC or PHRF time-on-time: corrected is elapsed times the TCF
+    TimeOnTime { tcf: f64 },
+    /// PHRF time-on-distance: corrected is elapsed less `rating` seconds a mile
+    TimeOnDistance { rating: f64 },
+    /// Portsmouth Yardstick: corrected is elapsed times 1000 over the number
+    Portsmouth { number: f64 },
+}
+
+impl Rating {
+    /// False for a rating that would divide by zero or go backwards
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Rating::TimeOnTime { tcf } => tcf.is_finite() && tcf > 0.0,
+            Rating::TimeOnDistance { rating } => rating.is_finite(),
+            Rating::Portsmouth { number } => number.is_finite() && number > 0.0,
+        }
+    }
+
+    /// Corrected time for `elapsed`, over a course of `distance` nautical miles. None
+    /// for time-on-distance without a distance.
+    pub fn corrected(&self, elapsed: f64, distance: Option<f64>) -> Option<f64> {
+        match *self {
+            Rating::TimeOnTime { tcf } => Some(elapsed * tcf),
+            Rating::TimeOnDistance { rating } => Some(elapsed - rating * distance?),
+            Rating::Portsmouth { number } => Some(elapsed * 1000.0 / number),
+        }
+    }
+
+    /// The elapsed time that corrects to `corrected`
+    pub fn elapsed(&self, corrected: f64, distance: Option<f64>) -> Option<f64> {
+        match *self {
+            Rating::TimeOnTime { tcf } => Some(corrected / tcf),
+            Rating::TimeOnDistance { rating } => Some(corrected + rating * distance?),
+            Rating::Portsmouth { number } => Some(corrected * number / 1000.0),
+        }
+    }
+
+    /// The rating to four decimals, which is more than any system publishes, for
+    /// reporting
+    pub fn rounded(&self) -> Rating {
+        let round = |value: f64| libm::round(value * 10_000.0) / 10_000.0;
+        match *self {
+            Rating::TimeOnTime { tcf } => Rating::TimeOnTime { tcf: round(tcf) },
+            Rating::TimeOnDistance { rating } => Rating::TimeOnDistance { rating: round(rating) },
+            Rating::Portsmouth { number } => Rating::Portsmouth { number: round(number) },
+        }
+    }
+
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        let (tag, value) = match *self {
+            Rating::TimeOnTime { tcf } => (0, tcf),
+            Rating::TimeOnDistance { rating } => (1, rating),
+            Rating::Portsmouth { number } => (2, number),
+        };
+        out.u8(tag);
+        out.f64(value);
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        let tag = input.u8()?;
+        let value = input.f64()?;
+        match tag {
+            0 => Ok(Rating::TimeOnTime { tcf: value }),
+            1 => Ok(Rating::TimeOnDistance { rating: value }),
+            2 => Ok(Rating::Portsmouth { number: value }),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Seconds `rating` can finish after `target` and still beat it on corrected time,
+/// when `target` has sailed for `elapsed`. Negative when `rating` owes the time.
+pub fn time_owed(
+    rating: &Rating,
+    target: &Rating,
+    elapsed: f64,
+    distance: Option<f64>,
+) -> Option<f64> {
+    let corrected = target.corrected(elapsed, distance)?;
+    Some(rating.elapsed(corrected, distance)? - elapsed)
+}
@@ -1,10 +1,15 @@
 #![no_std]
 
+mod course;
 mod geo_math;
+mod handicap;
 mod line;
 mod race;
+mod sequence;
 mod types;
 pub use race::Race;
 
 #[cfg(test)]
 mod race_tests;
+#[cfg(test)]
+mod test_helpers;
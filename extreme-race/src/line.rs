@@ -1,8 +1,14 @@
-use crate::geo_math::{bearing, distance, seconds_to_line};
+use crate::geo_math::{
+    along_track_distance, bearing, cross_track_distance, destination, distance, seconds_to_line,
+    simple_diff,
+};
+use core::f64::consts::FRAC_PI_2;
+use libm::sin;
 use crate::types::Location;
+use extreme_traits::{SnapshotReader, SnapshotWriter};
 use serde::Serialize;
 
-const R: f64 = 6371e3; // radius of earth in meters
+pub(crate) const R: f64 = 6371e3; // radius of earth in meters
 
 #[derive(Copy, Clone, PartialEq, Default, Serialize)]
 #[serde(tag = "line")]
@@ -23,6 +29,8 @@ pub enum Line {
     Both {
         line_timestamp: u64,
         line_cross: u8,
+        // metres from the line, positive on the pre-start side, once there's been a fix
+        line_distance: Option<f64>,
 
         #[serde(skip)]
         stbd: Location,
@@ -37,6 +45,117 @@ pub enum Line {
 }
 
 impl Line {
+    /// The (stbd, port) pins that have been set
+    pub fn pins(&self) -> (Option<Location>, Option<Location>) {
+        match *self {
+            Line::None => (None, None),
+            Line::Stbd { stbd_location } => (Some(stbd_location), None),
+            Line::Port { port_location } => (None, Some(port_location)),
+            Line::Both { stbd, port, .. } => (Some(stbd), Some(port)),
+        }
+    }
+
+    // where the boat is against a line with both pins. The pins themselves are saved
+    // with the rest of the settings.
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        let Line::Both {
+            line_timestamp,
+            line_cross,
+            line_distance,
+            ..
+        } = *self
+        else {
+            return;
+        };
+        out.u64(line_timestamp);
+        out.u8(line_cross);
+        out.bool(line_distance.is_some());
+        out.f64(line_distance.unwrap_or_default());
+    }
+
+    /// Read what `snapshot` wrote, into the line with the same pins
+    pub fn restore(&mut self, input: &mut SnapshotReader) -> Result<(), ()> {
+        let Line::Both {
+            line_timestamp,
+            line_cross,
+            line_distance,
+            ..
+        } = self
+        else {
+            return Ok(());
+        };
+        *line_timestamp = input.u64()?;
+        *line_cross = input.u8()?;
+        *line_distance = match (input.bool()?, input.f64()?) {
+            (true, distance) => Some(distance),
+            (false, _) => None,
+        };
+        Ok(())
+    }
+
+    /// How far the line is off square to a wind from `wind` (radians), as (angle in
+    /// radians, metres). Positive when the port end is upwind and favoured, the metres
+    /// are how much further upwind it is.
+    pub fn bias(&self, wind: f64) -> Option<(f64, f64)> {
+        let Line::Both {
+            bearing, length, ..
+        } = *self
+        else {
+            return None;
+        };
+        // looking upwind from behind a square line, port is to the left
+        let angle = simple_diff(bearing, wind - FRAC_PI_2);
+        Some((angle, length * sin(angle)))
+    }
+
+    /// Halfway between the pins
+    pub fn centre(&self) -> Option<Location> {
+        let Line::Both {
+            stbd,
+            bearing,
+            length,
+            ..
+        } = *self
+        else {
+            return None;
+        };
+        let (lat, lon) = destination(stbd.lat, stbd.lon, bearing, length / 2.0, R);
+        Some(Location { lat, lon })
+    }
+
+    /// How far along the line `location` is, 0 at the stbd pin and 1 at the port pin
+    pub fn position(&self, location: Location) -> Option<f64> {
+        let Line::Both {
+            stbd, port, length, ..
+        } = *self
+        else {
+            return None;
+        };
+        if length <= 0.0 {
+            return None;
+        }
+        let along = along_track_distance(
+            location.lat,
+            location.lon,
+            stbd.lat,
+            stbd.lon,
+            port.lat,
+            port.lon,
+            R,
+        );
+        Some(along / length)
+    }
+
+    /// Distance in metres from `location` to the nearest pin
+    pub fn distance_to_pins(&self, location: Location) -> Option<f64> {
+        let (stbd, port) = self.pins();
+        [stbd, port]
+            .into_iter()
+            .flatten()
+            .map(|pin| distance(location.lat, location.lon, pin.lat, pin.lon, R))
+            .reduce(f64::min)
+    }
+
     pub fn set_stbd(&mut self, location: Location) -> Option<()> {
         match self {
             Line::None => {
@@ -53,6 +172,7 @@ impl Line {
                 *self = Line::Both {
                     line_timestamp: 0,
                     line_cross: 0,
+                    line_distance: None,
                     stbd: location,
                     port: *loc,
                     bearing: bearing(location.lat, location.lon, loc.lat, loc.lon),
@@ -91,6 +211,7 @@ impl Line {
                 *self = Line::Both {
                     line_timestamp: 0,
                     line_cross: 0,
+                    line_distance: None,
                     stbd: *loc,
                     port: location,
                     bearing: bearing(loc.lat, loc.lon, location.lat, location.lon),
@@ -115,6 +236,25 @@ impl Line {
         }
     }
 
+    /// Set the port pin `length` metres from the stbd pin, on `line_bearing` (radians)
+    pub fn set_port_from_stbd(&mut self, line_bearing: f64, length: f64) -> Option<()> {
+        let (Some(stbd), _) = self.pins() else {
+            return None;
+        };
+        let (lat, lon) = destination(stbd.lat, stbd.lon, line_bearing, length, R);
+        self.set_port(Location { lat, lon })
+    }
+
+    /// Set the stbd pin `length` metres from the port pin, on `pin_bearing` (radians)
+    /// from the port pin
+    pub fn set_stbd_from_port(&mut self, pin_bearing: f64, length: f64) -> Option<()> {
+        let (_, Some(port)) = self.pins() else {
+            return None;
+        };
+        let (lat, lon) = destination(port.lat, port.lon, pin_bearing, length, R);
+        self.set_stbd(Location { lat, lon })
+    }
+
     pub fn update_location(
         &mut self,
         timestamp: u64,
@@ -126,11 +266,11 @@ impl Line {
             Line::Both {
                 line_timestamp,
                 line_cross,
+                line_distance,
                 stbd,
                 port,
                 bearing,
                 length,
-                ..
             } => {
                 let (_on_line, new_point, new_time) = seconds_to_line(
                     location.0, location.1, heading, speed, stbd.lat, stbd.lon, port.lat, port.lon,
@@ -148,6 +288,11 @@ impl Line {
                 }
                 *line_cross = (new_point * 100.0) as u8;
 
+                // the course side is to the right looking from stbd to port
+                *line_distance = Some(-cross_track_distance(
+                    location.0, location.1, stbd.lat, stbd.lon, port.lat, port.lon, R,
+                ));
+
                 Some(())
             }
             _ => {
@@ -3,9 +3,13 @@ use core::f64::consts::PI;
 use serde::ser::SerializeStruct;
 use serde::Serialize;
 
-use crate::line::Line;
-use crate::types::Location;
-use extreme_traits::Engine;
+use crate::course::Course;
+use crate::geo_math::{bearing, distance};
+use crate::handicap::{self, Rating};
+use crate::line::{Line, R};
+use crate::sequence::{Sequence, Signal};
+use crate::types::{BoatGeometry, FixState, Location, RecentFixes};
+use extreme_traits::{ping_course, Engine, Fix, SnapshotReader, SnapshotWriter};
 
 include!(concat!(env!("OUT_DIR"), "/static_files.rs"));
 
@@ -15,8 +19,105 @@ pub struct Race {
     pub state: State,
     pub line: Line,
     pub location: Location,
+    pub fix: FixState,
+    pub hdop: Option<f64>,
+    // the line came from storage and hasn't been checked against a fix yet
+    pub line_restored: bool,
+    pub geometry: BoatGeometry,
+    // course over ground of the last fix that had one, radians
+    pub heading: Option<f64>,
+    // for the mean course a wind ping takes
+    recent: RecentFixes,
+    // time of the last valid fix
+    pub timestamp: u64,
+    // on the course side in the last minute, or over at the gun and not yet back
+    pub ocs: bool,
+    // direction the wind is coming from, radians
+    pub wind: Option<f64>,
+    // when and how the boat crossed the line after the gun
+    pub start: Option<StartReport>,
+    // the bow at the last fix, to interpolate the crossing from
+    line_point: Option<LinePoint>,
+    pub course: Course,
+    pub finish_line: Line,
+    // the bow against the finish line at the last fix
+    finish_point: Option<LinePoint>,
+    // the result of the last race, until the next sequence
+    pub finish: Option<FinishReport>,
+    pub sequence: Sequence,
+    // the signal the timer is set for, and when
+    pub next_signal: Option<(u64, Signal)>,
+    pub rating: Option<Rating>,
+    // the boat to beat on corrected time
+    pub target: Option<Rating>,
 }
 
+/// How the boat started, reported once it's crossed the line after the gun
+#[derive(Serialize, Copy, Clone, PartialEq)]
+pub struct StartReport {
+    /// When the bow crossed, ms
+    pub time: u64,
+    /// Seconds after the gun
+    pub late: f64,
+    /// Knots
+    pub speed: f64,
+    /// Where along the line, 0 at the stbd pin and 1 at the port pin
+    pub position: f64,
+}
+
+/// How the boat finished, once it's crossed the finish line
+#[derive(Serialize, Copy, Clone, PartialEq)]
+pub struct FinishReport {
+    /// When the bow crossed, ms
+    pub time: u64,
+    /// Seconds from the gun
+    pub elapsed: f64,
+    /// Knots
+    pub speed: f64,
+    /// Where along the line, 0 at the stbd pin and 1 at the port pin
+    pub position: f64,
+}
+
+// The bow relative to the line at a fix
+#[derive(Copy, Clone, PartialEq)]
+struct LinePoint {
+    timestamp: u64,
+    distance: f64,
+    position: f64,
+    speed: f64,
+}
+
+// A restored line further than this from the boat is from another day's racing
+const MAX_RESTORED_LINE_DISTANCE: f64 = 5000.0;
+
+// Longer than any start line, a length past this is a units mistake
+const MAX_LINE_LENGTH: f64 = 2000.0;
+
+// flags in the first byte of saved state, followed by the pins that are set
+const SAVED_STBD: u8 = 0x01;
+const SAVED_PORT: u8 = 0x02;
+const SAVED_GEOMETRY: u8 = 0x04;
+const SAVED_FINISH_STBD: u8 = 0x08;
+const SAVED_FINISH_PORT: u8 = 0x10;
+const SAVED_SEQUENCE: u8 = 0x20;
+const SAVED_RATING: u8 = 0x40;
+const SAVED_TARGET: u8 = 0x80;
+
+// After AP comes down, the warning signal is made a minute later, ms
+const POSTPONEMENT_DELAY: u64 = 60_000;
+
+// OCS is only flagged this close to the gun, ms
+const OCS_WINDOW: u64 = 60_000;
+
+// Below this, knots, the boat isn't going anywhere and time to the line or a mark is
+// meaningless
+const MIN_SPEED: f64 = 0.5;
+const KNOTS_TO_MPS: f64 = 1852.0 / 3600.0;
+const NAUTICAL_MILE: f64 = 1852.0;
+
+// Antenna offsets past this are a units mistake, metres
+const MAX_GEOMETRY_OFFSET: f64 = 30.0;
+
 #[derive(Serialize, Copy, Clone, PartialEq)]
 #[serde(tag = "state")]
 pub enum State {
@@ -32,6 +133,101 @@ pub enum State {
         speed: f64,
         heading: f64,
     },
+    // AP flag up, the sequence is on hold
+    Postponed {
+        speed: f64,
+    },
+}
+
+impl StartReport {
+    fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u64(self.time);
+        out.f64(self.late);
+        out.f64(self.speed);
+        out.f64(self.position);
+    }
+
+    fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        Ok(StartReport {
+            time: input.u64()?,
+            late: input.f64()?,
+            speed: input.f64()?,
+            position: input.f64()?,
+        })
+    }
+}
+
+impl FinishReport {
+    fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u64(self.time);
+        out.f64(self.elapsed);
+        out.f64(self.speed);
+        out.f64(self.position);
+    }
+
+    fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        Ok(FinishReport {
+            time: input.u64()?,
+            elapsed: input.f64()?,
+            speed: input.f64()?,
+            position: input.f64()?,
+        })
+    }
+}
+
+impl LinePoint {
+    // The bow against `line`, once the line has been updated for the fix
+    fn new(line: &Line, timestamp: u64, bow: Location, speed: f64) -> Option<Self> {
+        let Line::Both {
+            line_distance: Some(distance),
+            ..
+        } = *line
+        else {
+            return None;
+        };
+        Some(LinePoint {
+            timestamp,
+            distance,
+            position: line.position(bow)?,
+            speed,
+        })
+    }
+
+    // Where the bow went through the line since `previous`, interpolating between the
+    // two fixes, as (time, speed, position). None if it didn't, or went round an end.
+    fn crossing(&self, previous: &LinePoint) -> Option<(u64, f64, f64)> {
+        if (previous.distance < 0.0) == (self.distance < 0.0) {
+            return None;
+        }
+        let fraction = previous.distance / (previous.distance - self.distance);
+        let lerp = |from: f64, to: f64| from + (to - from) * fraction;
+        let position = lerp(previous.position, self.position);
+        if !(0.0..=1.0).contains(&position) {
+            return None;
+        }
+        let elapsed = self.timestamp.saturating_sub(previous.timestamp) as f64 * fraction;
+        Some((
+            previous.timestamp + elapsed as u64,
+            lerp(previous.speed, self.speed),
+            position,
+        ))
+    }
+
+    fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u64(self.timestamp);
+        out.f64(self.distance);
+        out.f64(self.position);
+        out.f64(self.speed);
+    }
+
+    fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        Ok(LinePoint {
+            timestamp: input.u64()?,
+            distance: input.f64()?,
+            position: input.f64()?,
+            speed: input.f64()?,
+        })
+    }
 }
 
 impl Default for State {
@@ -40,13 +236,109 @@ impl Default for State {
     }
 }
 
+impl State {
+    fn snapshot(&self, out: &mut SnapshotWriter) {
+        match *self {
+            State::Active { speed } => {
+                out.u8(0);
+                out.f64(speed);
+            }
+            State::InSequence { start_time, speed } => {
+                out.u8(1);
+                out.u64(start_time);
+                out.f64(speed);
+            }
+            State::Racing {
+                start_time,
+                speed,
+                heading,
+            } => {
+                out.u8(2);
+                out.u64(start_time);
+                out.f64(speed);
+                out.f64(heading);
+            }
+            State::Postponed { speed } => {
+                out.u8(3);
+                out.f64(speed);
+            }
+        }
+    }
+
+    fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        match input.u8()? {
+            0 => Ok(State::Active {
+                speed: input.f64()?,
+            }),
+            1 => Ok(State::InSequence {
+                start_time: input.u64()?,
+                speed: input.f64()?,
+            }),
+            2 => Ok(State::Racing {
+                start_time: input.u64()?,
+                speed: input.f64()?,
+                heading: input.f64()?,
+            }),
+            3 => Ok(State::Postponed {
+                speed: input.f64()?,
+            }),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub enum EventType {
+    // pins at the boat's location
     LineStbd,
     LinePort,
 
+    // where the antenna is, metres from the bow and the centreline
+    Geometry { bow: f64, beam: f64 },
+
+    // pins from published coordinates, degrees
+    LineStbdAt { lat: f64, lon: f64 },
+    LinePortAt { lat: f64, lon: f64 },
+
+    // the other pin from one that's set, degrees true from the set pin and metres
+    LineFromStbd { bearing: f64, length: f64 },
+    LineFromPort { bearing: f64, length: f64 },
+
+    // wind direction, degrees true it comes from
+    Wind { direction: f64 },
+    // the boat is head to wind, take the wind direction from its course
+    WindPing,
+
+    // marks in the order they're rounded, at the boat's location or from coordinates
+    Mark,
+    MarkAt { lat: f64, lon: f64 },
+    ClearMarks,
+    // skip to the next mark, if the rounding was missed
+    NextMark,
+
+    // finish line pins, at the boat's location or from coordinates
+    FinishStbd,
+    FinishPort,
+    FinishStbdAt { lat: f64, lon: f64 },
+    FinishPortAt { lat: f64, lon: f64 },
+
+    // the start sequence profile
+    Sequence { sequence: Sequence },
+
     BumpSeq { timestamp: u64, seconds: i32 },
 
+    // first substitute, the sequence starts again with the gun `seconds` after `timestamp`
+    GeneralRecall { timestamp: u64, seconds: u32 },
+    // AP up, before the warning or during the sequence, on hold until AP down at `timestamp`
+    Postpone,
+    Resume { timestamp: u64 },
+    // X flag, the boat was over and has to come back and start again
+    IndividualRecall,
+
+    // handicaps for corrected time, this boat's and the one it's racing, null to clear
+    Rating { rating: Option<Rating> },
+    TargetRating { rating: Option<Rating> },
+
     RaceFinish,
 }
 
@@ -57,6 +349,221 @@ pub struct Event {
     pub event: EventType,
 }
 
+impl Race {
+    /// Seconds for the bow to reach the line sailing straight at it at the current
+    /// speed, negative when it's over the line
+    pub fn time_to_line(&self) -> Option<f64> {
+        let Line::Both {
+            line_distance: Some(distance),
+            ..
+        } = self.line
+        else {
+            return None;
+        };
+        let speed = match self.state {
+            State::Active { speed }
+            | State::InSequence { speed, .. }
+            | State::Postponed { speed } => speed,
+            State::Racing { .. } => return None,
+        };
+        if speed < MIN_SPEED {
+            return None;
+        }
+        Some(distance / (speed * KNOTS_TO_MPS))
+    }
+
+    /// Seconds to spare before heading for the line to hit it at the gun, negative
+    /// when the boat is late
+    pub fn time_to_burn(&self) -> Option<f64> {
+        let State::InSequence { start_time, .. } = self.state else {
+            return None;
+        };
+        let time_to_gun = (start_time as f64 - self.timestamp as f64) / 1000.0;
+        Some(time_to_gun - self.time_to_line()?)
+    }
+
+    /// Line bias to the wind as (degrees, metres), positive when the port end is
+    /// favoured
+    pub fn line_bias(&self) -> Option<(f64, f64)> {
+        let (angle, metres) = self.line.bias(self.wind?)?;
+        Some((angle * 180.0 / PI, metres))
+    }
+
+    /// Bearing in degrees true and distance in metres to the next mark
+    pub fn to_mark(&self) -> Option<(f64, f64)> {
+        let mark = self.course.next_mark()?;
+        let bow = self.bow_location();
+        Some((
+            bearing(bow.lat, bow.lon, mark.lat, mark.lon) * 180.0 / PI,
+            distance(bow.lat, bow.lon, mark.lat, mark.lon, R),
+        ))
+    }
+
+    /// Velocity made good towards the next mark while racing, knots
+    pub fn vmc(&self) -> Option<f64> {
+        let State::Racing { speed, heading, .. } = self.state else {
+            return None;
+        };
+        let (mark_bearing, _) = self.to_mark()?;
+        Some(speed * libm::cos((mark_bearing - heading) * PI / 180.0))
+    }
+
+    /// Seconds to the next mark at the current VMC
+    pub fn eta(&self) -> Option<f64> {
+        let vmc = self.vmc()?;
+        if vmc < MIN_SPEED {
+            return None;
+        }
+        let (_, distance) = self.to_mark()?;
+        Some(distance / (vmc * KNOTS_TO_MPS))
+    }
+
+    /// Nautical miles from the start line round the marks to the finish line, for
+    /// time-on-distance
+    pub fn course_distance(&self) -> Option<f64> {
+        let length = self
+            .course
+            .length(self.line.centre(), self.finish_line.centre())?;
+        Some(length / NAUTICAL_MILE)
+    }
+
+    /// Corrected time of the last finish, seconds
+    pub fn corrected_time(&self) -> Option<f64> {
+        let elapsed = self.finish?.elapsed;
+        self.rating?.corrected(elapsed, self.course_distance())
+    }
+
+    /// Seconds the boat could finish behind the target boat right now and still beat
+    /// it on corrected time, negative when it owes the target time
+    pub fn time_owed(&self) -> Option<f64> {
+        let State::Racing { start_time, .. } = self.state else {
+            return None;
+        };
+        let elapsed = self.timestamp.saturating_sub(start_time) as f64 / 1000.0;
+        handicap::time_owed(&self.rating?, &self.target?, elapsed, self.course_distance())
+    }
+
+    // The bow is on the course side of the line
+    fn over_line(&self) -> bool {
+        matches!(self.line, Line::Both { line_distance: Some(distance), .. } if distance < 0.0)
+    }
+
+    // Record the start if the bow crossed the line since the last fix, interpolating
+    // between the two fixes for when, where and how fast
+    fn cross_line(&mut self, timestamp: u64, speed: f64) -> Option<()> {
+        let previous = self.line_point.take();
+        if self.start.is_some() {
+            return None;
+        }
+        let point = LinePoint::new(&self.line, timestamp, self.bow_location(), speed)?;
+        self.line_point = Some(point);
+
+        let State::Racing { start_time, .. } = self.state else {
+            return None;
+        };
+        let previous = previous?;
+        if previous.distance < 0.0 {
+            // coming back from the course side
+            return None;
+        }
+        let (time, speed, position) = point.crossing(&previous)?;
+        if time < start_time {
+            // over before the gun, between the last fix and the one after it
+            self.ocs = true;
+            return Some(());
+        }
+
+        self.start = Some(StartReport {
+            time,
+            late: (time - start_time) as f64 / 1000.0,
+            speed,
+            position,
+        });
+        Some(())
+    }
+
+    // Finish the race if the bow crossed the finish line since the last fix, either
+    // way, once the boat has started and sailed the course
+    fn cross_finish(&mut self, timestamp: u64, speed: f64) -> Option<()> {
+        let previous = self.finish_point.take();
+        let point = LinePoint::new(&self.finish_line, timestamp, self.bow_location(), speed)?;
+        self.finish_point = Some(point);
+
+        let State::Racing { start_time, .. } = self.state else {
+            return None;
+        };
+        // without a start line there's no start to wait for
+        let started = self.start.is_some() || !matches!(self.line, Line::Both { .. });
+        if !started || self.ocs || self.course.next_mark().is_some() {
+            return None;
+        }
+        let (time, speed, position) = point.crossing(&previous?)?;
+
+        self.finish = Some(FinishReport {
+            time,
+            elapsed: time.saturating_sub(start_time) as f64 / 1000.0,
+            speed,
+            position,
+        });
+        self.end_race();
+        Some(())
+    }
+
+    // Set the timer for the next signal after `now`, or the gun once they've all gone
+    fn schedule_signal(&mut self, now: u64) -> Option<u64> {
+        let State::InSequence { start_time, .. } = self.state else {
+            self.next_signal = None;
+            return None;
+        };
+        self.next_signal = self.sequence.next_signal(start_time, now);
+        Some(self.next_signal.map_or(start_time, |(time, _)| time))
+    }
+
+    // Back to Active, keeping the boat's speed
+    fn end_race(&mut self) {
+        let speed = match self.state {
+            State::Active { speed } => speed,
+            State::InSequence { speed, .. } => speed,
+            State::Racing { speed, .. } => speed,
+            State::Postponed { speed } => speed,
+        };
+        self.state = State::Active { speed };
+        self.ocs = false;
+        self.start = None;
+        self.next_signal = None;
+        self.course.restart();
+    }
+
+    // Where the bow is, as best we know. Without a course yet, the antenna will do.
+    fn bow_location(&self) -> Location {
+        match self.heading {
+            Some(heading) => self.geometry.bow_location(self.location, heading),
+            None => self.location,
+        }
+    }
+}
+
+// A pin from coordinates in degrees, None if they aren't on the earth
+fn pin_location(lat: f64, lon: f64) -> Option<Location> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return None;
+    }
+    Some(Location {
+        lat: lat * PI / 180.0,
+        lon: lon * PI / 180.0,
+    })
+}
+
+// Reported figures to `1 / scale`, the rest is noise that costs message space
+fn round_to(value: f64, scale: f64) -> f64 {
+    libm::round(value * scale) / scale
+}
+
+// A bearing in degrees and a line length in metres
+fn valid_leg(bearing: f64, length: f64) -> bool {
+    (0.0..=360.0).contains(&bearing) && length > 0.0 && length <= MAX_LINE_LENGTH
+}
+
 impl Engine for Race {
     type Event<'a> = Event;
 
@@ -69,7 +576,195 @@ impl Engine for Race {
         return None;
     }
 
+    // flags for what's set, then the pins, the geometry, the finish pins and the
+    // sequence
+    fn save(&self, out: &mut SnapshotWriter) {
+        let (stbd, port) = self.line.pins();
+        let (finish_stbd, finish_port) = self.finish_line.pins();
+        let geometry = Some(self.geometry).filter(|geometry| *geometry != BoatGeometry::default());
+        let sequence = Some(self.sequence).filter(|sequence| *sequence != Sequence::default());
+        out.u8(
+            stbd.map_or(0, |_| SAVED_STBD)
+                | port.map_or(0, |_| SAVED_PORT)
+                | geometry.map_or(0, |_| SAVED_GEOMETRY)
+                | finish_stbd.map_or(0, |_| SAVED_FINISH_STBD)
+                | finish_port.map_or(0, |_| SAVED_FINISH_PORT)
+                | sequence.map_or(0, |_| SAVED_SEQUENCE)
+                | self.rating.map_or(0, |_| SAVED_RATING)
+                | self.target.map_or(0, |_| SAVED_TARGET),
+        );
+        for pin in [stbd, port].into_iter().flatten() {
+            pin.snapshot(out);
+        }
+        if let Some(geometry) = geometry {
+            geometry.snapshot(out);
+        }
+        for pin in [finish_stbd, finish_port].into_iter().flatten() {
+            pin.snapshot(out);
+        }
+        if let Some(sequence) = sequence {
+            sequence.snapshot(out);
+        }
+        for rating in [self.rating, self.target].into_iter().flatten() {
+            rating.snapshot(out);
+        }
+    }
+
+    fn load(&mut self, input: &mut SnapshotReader) -> Result<(), ()> {
+        let flags = input.u8()?;
+        let mut pin = |flag| match flags & flag {
+            0 => Ok(None),
+            _ => Location::restore(input).map(Some),
+        };
+        let (stbd, port) = (pin(SAVED_STBD)?, pin(SAVED_PORT)?);
+        let geometry = match flags & SAVED_GEOMETRY {
+            0 => BoatGeometry::default(),
+            _ => BoatGeometry::restore(input)?,
+        };
+        let mut pin = |flag| match flags & flag {
+            0 => Ok(None),
+            _ => Location::restore(input).map(Some),
+        };
+        let (finish_stbd, finish_port) = (pin(SAVED_FINISH_STBD)?, pin(SAVED_FINISH_PORT)?);
+        let sequence = match flags & SAVED_SEQUENCE {
+            0 => Sequence::default(),
+            _ => Sequence::restore(input)?,
+        };
+        let mut rating = |flag| match flags & flag {
+            0 => Ok(None),
+            _ => Rating::restore(input).map(Some),
+        };
+        let (rating, target) = (rating(SAVED_RATING)?, rating(SAVED_TARGET)?);
+
+        let line = |stbd: Option<Location>, port: Option<Location>| {
+            let mut line = Line::None;
+            if let Some(stbd) = stbd {
+                line.set_stbd(stbd);
+            }
+            if let Some(port) = port {
+                line.set_port(port);
+            }
+            line
+        };
+
+        self.line = line(stbd, port);
+        self.finish_line = line(finish_stbd, finish_port);
+        self.line_restored = self.line != Line::None || self.finish_line != Line::None;
+        self.geometry = geometry;
+        self.sequence = sequence;
+        self.rating = rating;
+        self.target = target;
+        Ok(())
+    }
+
+    const SNAPSHOT_VERSION: u8 = 1;
+
+    // everything `save` doesn't keep, in field order
+    fn snapshot(&self, out: &mut SnapshotWriter) {
+        self.state.snapshot(out);
+        self.line.snapshot(out);
+        self.location.snapshot(out);
+        self.fix.snapshot(out);
+        out.bool(self.hdop.is_some());
+        out.f64(self.hdop.unwrap_or_default());
+        out.bool(self.line_restored);
+        out.bool(self.heading.is_some());
+        out.f64(self.heading.unwrap_or_default());
+        self.recent.snapshot(out);
+        out.u64(self.timestamp);
+        out.bool(self.ocs);
+        out.bool(self.wind.is_some());
+        out.f64(self.wind.unwrap_or_default());
+        out.bool(self.start.is_some());
+        if let Some(start) = &self.start {
+            start.snapshot(out);
+        }
+        out.bool(self.line_point.is_some());
+        if let Some(point) = &self.line_point {
+            point.snapshot(out);
+        }
+        self.course.snapshot(out);
+        self.finish_line.snapshot(out);
+        out.bool(self.finish_point.is_some());
+        if let Some(point) = &self.finish_point {
+            point.snapshot(out);
+        }
+        out.bool(self.finish.is_some());
+        if let Some(finish) = &self.finish {
+            finish.snapshot(out);
+        }
+        out.bool(self.next_signal.is_some());
+        if let Some((time, signal)) = &self.next_signal {
+            out.u64(*time);
+            signal.snapshot(out);
+        }
+    }
+
+    fn restore(&mut self, mut input: SnapshotReader) -> Result<Option<u64>, ()> {
+        self.state = State::restore(&mut input)?;
+        self.line.restore(&mut input)?;
+        self.location = Location::restore(&mut input)?;
+        self.fix = FixState::restore(&mut input)?;
+        self.hdop = match (input.bool()?, input.f64()?) {
+            (true, hdop) => Some(hdop),
+            (false, _) => None,
+        };
+        self.line_restored = input.bool()?;
+        self.heading = match (input.bool()?, input.f64()?) {
+            (true, heading) => Some(heading),
+            (false, _) => None,
+        };
+        self.recent = RecentFixes::restore(&mut input)?;
+        self.timestamp = input.u64()?;
+        self.ocs = input.bool()?;
+        self.wind = match (input.bool()?, input.f64()?) {
+            (true, wind) => Some(wind),
+            (false, _) => None,
+        };
+        self.start = match input.bool()? {
+            true => Some(StartReport::restore(&mut input)?),
+            false => None,
+        };
+        self.line_point = match input.bool()? {
+            true => Some(LinePoint::restore(&mut input)?),
+            false => None,
+        };
+        self.course = Course::restore(&mut input)?;
+        self.finish_line.restore(&mut input)?;
+        self.finish_point = match input.bool()? {
+            true => Some(LinePoint::restore(&mut input)?),
+            false => None,
+        };
+        self.finish = match input.bool()? {
+            true => Some(FinishReport::restore(&mut input)?),
+            false => None,
+        };
+        self.next_signal = match input.bool()? {
+            true => Some((input.u64()?, Signal::restore(&mut input)?)),
+            false => None,
+        };
+        input.finish()?;
+
+        // a sequence picks up where it left off, even if the start has passed
+        match self.state {
+            State::InSequence { start_time, .. } => Ok(Some(
+                self.next_signal.map_or(start_time, |(time, _)| time),
+            )),
+            _ => Ok(Some(0)),
+        }
+    }
+
     fn timer_event(&mut self, timestamp: u64) -> (Option<()>, Option<u64>) {
+        match self.state {
+            // a signal before the gun, on to the next one
+            State::InSequence { start_time, .. } if timestamp < start_time => {
+                return (Some(()), self.schedule_signal(timestamp));
+            }
+            // a timer from before the postponement, the sequence waits for AP down
+            State::Postponed { .. } => return (None, None),
+            _ => {}
+        }
+
         let (start_time, speed) = if let State::InSequence {
             start_time, speed, ..
         } = self.state
@@ -85,6 +780,10 @@ impl Engine for Race {
             speed: speed,
             heading: 0.0,
         };
+        self.next_signal = None;
+        // over at the gun, the boat has to come back and start again
+        self.ocs = self.over_line();
+        self.course.restart();
 
         // state is updated, no new timer
         (Some(()), None)
@@ -92,15 +791,109 @@ impl Engine for Race {
 
     fn external_event<'a>(
         &mut self,
-        _timestamp: u64,
+        timestamp: u64,
         event: &Self::Event<'a>,
     ) -> (Option<()>, Option<u64>) {
         match event.event {
             EventType::LineStbd => {
-                return (self.line.set_stbd(self.location), None);
+                return (self.line.set_stbd(self.bow_location()), None);
             }
             EventType::LinePort => {
-                return (self.line.set_port(self.location), None);
+                return (self.line.set_port(self.bow_location()), None);
+            }
+            EventType::Geometry { bow, beam } => {
+                if !(0.0..=MAX_GEOMETRY_OFFSET).contains(&bow)
+                    || !(-MAX_GEOMETRY_OFFSET..=MAX_GEOMETRY_OFFSET).contains(&beam)
+                {
+                    return (None, None);
+                }
+                let geometry = BoatGeometry { bow, beam };
+                if geometry == self.geometry {
+                    return (None, None);
+                }
+                self.geometry = geometry;
+                return (Some(()), None);
+            }
+            EventType::LineStbdAt { lat, lon } => {
+                let Some(location) = pin_location(lat, lon) else {
+                    return (None, None);
+                };
+                return (self.line.set_stbd(location), None);
+            }
+            EventType::LinePortAt { lat, lon } => {
+                let Some(location) = pin_location(lat, lon) else {
+                    return (None, None);
+                };
+                return (self.line.set_port(location), None);
+            }
+            EventType::LineFromStbd { bearing, length } => {
+                if !valid_leg(bearing, length) {
+                    return (None, None);
+                }
+                let bearing = bearing * PI / 180.0;
+                return (self.line.set_port_from_stbd(bearing, length), None);
+            }
+            EventType::LineFromPort { bearing, length } => {
+                if !valid_leg(bearing, length) {
+                    return (None, None);
+                }
+                let bearing = bearing * PI / 180.0;
+                return (self.line.set_stbd_from_port(bearing, length), None);
+            }
+            EventType::Wind { direction } => {
+                if !(0.0..=360.0).contains(&direction) {
+                    return (None, None);
+                }
+                self.wind = Some(direction * PI / 180.0);
+                return (Some(()), None);
+            }
+            EventType::WindPing => {
+                let Some(course) = ping_course(self.recent.iter()) else {
+                    return (None, None);
+                };
+                self.wind = Some(course * PI / 180.0);
+                return (Some(()), None);
+            }
+            EventType::Mark => {
+                return (self.course.add(self.bow_location()), None);
+            }
+            EventType::MarkAt { lat, lon } => {
+                let Some(location) = pin_location(lat, lon) else {
+                    return (None, None);
+                };
+                return (self.course.add(location), None);
+            }
+            EventType::ClearMarks => {
+                return (self.course.clear(), None);
+            }
+            EventType::NextMark => {
+                return (self.course.advance(), None);
+            }
+            EventType::FinishStbd => {
+                return (self.finish_line.set_stbd(self.bow_location()), None);
+            }
+            EventType::FinishPort => {
+                return (self.finish_line.set_port(self.bow_location()), None);
+            }
+            EventType::FinishStbdAt { lat, lon } => {
+                let Some(location) = pin_location(lat, lon) else {
+                    return (None, None);
+                };
+                return (self.finish_line.set_stbd(location), None);
+            }
+            EventType::FinishPortAt { lat, lon } => {
+                let Some(location) = pin_location(lat, lon) else {
+                    return (None, None);
+                };
+                return (self.finish_line.set_port(location), None);
+            }
+            EventType::Sequence { sequence } => {
+                if sequence == self.sequence {
+                    return (None, None);
+                }
+                self.sequence = sequence;
+                // a sequence already running carries on with the new signals
+                return (Some(()), self.schedule_signal(timestamp));
             }
             EventType::BumpSeq { timestamp, seconds } => {
                 match &mut self.state {
@@ -119,7 +912,7 @@ impl Engine for Race {
                         }
 
                         // updated, and new timer
-                        return (Some(()), Some(*start_time));
+                        return (Some(()), self.schedule_signal(timestamp));
                     }
 
                     _ => {
@@ -135,6 +928,7 @@ impl Engine for Race {
                             State::Active { speed } => *speed,
                             State::InSequence { speed, .. } => *speed,
                             State::Racing { speed, .. } => *speed,
+                            State::Postponed { speed } => *speed,
                         };
 
                         // update now that start is scheduled
@@ -142,21 +936,84 @@ impl Engine for Race {
                             start_time: new_start,
                             speed: old_speed,
                         };
+                        self.start = None;
+                        self.finish = None;
 
-                        return (Some(()), Some(new_start));
+                        return (Some(()), self.schedule_signal(timestamp));
                     }
                 }
             }
-            EventType::RaceFinish => {
-                let old_speed = match &self.state {
-                    State::Active { speed } => *speed,
-                    State::InSequence { speed, .. } => *speed,
-                    State::Racing { speed, .. } => *speed,
+            EventType::GeneralRecall { timestamp, seconds } => {
+                let (State::InSequence { speed, .. } | State::Racing { speed, .. }) = self.state
+                else {
+                    return (None, None);
                 };
-
+                self.state = State::InSequence {
+                    start_time: timestamp + seconds as u64 * 1000,
+                    speed,
+                };
+                // the line and course stay, the start didn't happen
+                self.ocs = false;
+                self.start = None;
+                self.course.restart();
+                return (Some(()), self.schedule_signal(timestamp));
+            }
+            EventType::Postpone => {
+                // before the warning signal or during the sequence
+                let (State::Active { speed } | State::InSequence { speed, .. }) = self.state else {
+                    return (None, None);
+                };
+                self.state = State::Postponed { speed };
+                self.ocs = false;
+                self.next_signal = None;
+                return (Some(()), Some(0));
+            }
+            EventType::Resume { timestamp } => {
+                let State::Postponed { speed } = self.state else {
+                    return (None, None);
+                };
+                let (warning, _) = self.sequence.signals()[0];
+                self.state = State::InSequence {
+                    start_time: timestamp + POSTPONEMENT_DELAY + warning * 1000,
+                    speed,
+                };
+                // postponed before the warning, a new race as for a start from Active
+                self.start = None;
+                self.finish = None;
+                return (Some(()), self.schedule_signal(timestamp));
+            }
+            EventType::IndividualRecall => {
+                if !matches!(self.state, State::Racing { .. }) || self.ocs {
+                    return (None, None);
+                }
+                // over until the boat is back on the pre-start side
+                self.ocs = true;
+                self.start = None;
+                return (Some(()), None);
+            }
+            EventType::Rating { rating } => {
+                if rating == self.rating || !rating.is_none_or(|rating| rating.is_valid()) {
+                    return (None, None);
+                }
+                self.rating = rating;
+                return (Some(()), None);
+            }
+            EventType::TargetRating { rating } => {
+                if rating == self.target || !rating.is_none_or(|rating| rating.is_valid()) {
+                    return (None, None);
+                }
+                self.target = rating;
+                return (Some(()), None);
+            }
+            EventType::RaceFinish => {
                 if !matches!(self.state, State::Active { .. }) {
-                    self.state = State::Active { speed: old_speed };
-                    return (Some(()), None);
+                    // a sequence has signals to come
+                    let timer = match self.state {
+                        State::InSequence { .. } => Some(0),
+                        _ => None,
+                    };
+                    self.end_race();
+                    return (Some(()), timer);
                 } else {
                     return (None, None);
                 }
@@ -164,15 +1021,32 @@ impl Engine for Race {
         }
     }
 
-    fn location_event(
-        &mut self,
-        timestamp: u64,
-        location: Option<(f64, f64)>,
-        speed: Option<(f64, f64)>,
-    ) -> (Option<()>, Option<u64>) {
+    fn location_event(&mut self, timestamp: u64, fix: &Fix) -> (Option<()>, Option<u64>) {
         let mut result = None;
 
+        let fix_state = if fix.is_valid() {
+            FixState::Valid
+        } else {
+            FixState::Void
+        };
+        if fix_state != self.fix {
+            self.fix = fix_state;
+            result = Some(());
+        }
+        self.hdop = fix.hdop;
+
+        // a void fix can carry stale or garbage data, don't let it move the boat
+        if fix_state == FixState::Void {
+            return (result, None);
+        }
+
+        self.timestamp = timestamp;
+        let location = fix.location;
+        let speed = fix.velocity();
+
         if let Some((new_speed, new_heading)) = speed {
+            self.heading = Some(new_heading * PI / 180.0);
+            self.recent.push(new_heading, new_speed, timestamp);
             match &mut self.state {
                 State::Active { speed } => {
                     *speed = new_speed;
@@ -184,6 +1058,9 @@ impl Engine for Race {
                     *speed = new_speed;
                     *heading = new_heading;
                 }
+                State::Postponed { speed } => {
+                    *speed = new_speed;
+                }
             }
             result = Some(());
         };
@@ -193,18 +1070,65 @@ impl Engine for Race {
             let lon = lon * PI / 180.0;
             self.location = Location { lat, lon };
 
-            if !matches!(self.state, State::Racing { .. }) {
+            if self.line_restored {
+                self.line_restored = false;
+                for line in [&mut self.line, &mut self.finish_line] {
+                    if let Some(distance) = line.distance_to_pins(self.location) {
+                        if distance > MAX_RESTORED_LINE_DISTANCE {
+                            *line = Line::None;
+                            result = Some(());
+                        }
+                    }
+                }
+            }
+
+            // until it's started the boat still needs the line, to see it cross
+            if !matches!(self.state, State::Racing { .. }) || self.start.is_none() {
                 if let Some((speed, heading)) = speed {
                     let heading = heading * PI / 180.0;
+                    // time to line is the bow's
+                    let bow = self.geometry.bow_location(self.location, heading);
                     if Some(())
                         == self
                             .line
-                            .update_location(timestamp, (lat, lon), heading, speed)
+                            .update_location(timestamp, (bow.lat, bow.lon), heading, speed)
                     {
-                        return (Some(()), None);
+                        result = Some(());
                     }
                 }
             }
+
+            let ocs = match self.state {
+                State::InSequence { start_time, .. } => {
+                    self.over_line() && start_time.saturating_sub(timestamp) <= OCS_WINDOW
+                }
+                State::Racing { .. } => self.ocs && self.over_line(),
+                State::Active { .. } | State::Postponed { .. } => false,
+            };
+            if ocs != self.ocs {
+                self.ocs = ocs;
+                result = Some(());
+            }
+
+            if let Some((speed, heading)) = speed {
+                let heading = heading * PI / 180.0;
+                let bow = self.geometry.bow_location(self.location, heading);
+                self.finish_line
+                    .update_location(timestamp, (bow.lat, bow.lon), heading, speed);
+
+                // the finish first, a start through the same line doesn't finish it
+                if self.cross_finish(timestamp, speed).is_some()
+                    || self.cross_line(timestamp, speed).is_some()
+                {
+                    result = Some(());
+                }
+            }
+
+            if matches!(self.state, State::Racing { .. })
+                && self.course.update_location(self.bow_location()).is_some()
+            {
+                result = Some(());
+            }
         }
         return (result, None);
     }
@@ -215,17 +1139,25 @@ impl Serialize for Race {
     where
         S: serde::Serializer,
     {
-        let mut s = serializer.serialize_struct("Race", 7)?;
+        let mut s = serializer.serialize_struct("Race", 9)?;
 
         match &self.state {
             State::Active { speed } => {
                 s.serialize_field("state", "Active")?;
-                s.serialize_field("speed", speed)?;
+                s.serialize_field("speed", &round_to(*speed, 100.0))?;
+            }
+            State::Postponed { speed } => {
+                s.serialize_field("state", "Postponed")?;
+                s.serialize_field("speed", &round_to(*speed, 100.0))?;
             }
             State::InSequence { start_time, speed } => {
                 s.serialize_field("state", "InSequence")?;
                 s.serialize_field("start_time", start_time)?;
-                s.serialize_field("speed", speed)?;
+                s.serialize_field("speed", &round_to(*speed, 100.0))?;
+                if let Some((time, signal)) = &self.next_signal {
+                    s.serialize_field("signal", signal)?;
+                    s.serialize_field("signal_time", time)?;
+                }
             }
             State::Racing {
                 start_time,
@@ -234,11 +1166,67 @@ impl Serialize for Race {
             } => {
                 s.serialize_field("state", "Racing")?;
                 s.serialize_field("start_time", start_time)?;
-                s.serialize_field("speed", speed)?;
-                s.serialize_field("heading", heading)?;
+                s.serialize_field("speed", &round_to(*speed, 100.0))?;
+                s.serialize_field("heading", &round_to(*heading, 10.0))?;
+                if let Some(start) = &self.start {
+                    s.serialize_field("start", start)?;
+                }
+                if let Some((mark_bearing, mark_distance)) = self.to_mark() {
+                    s.serialize_field("mark", &self.course.leg())?;
+                    s.serialize_field("mark_bearing", &round_to(mark_bearing, 10.0))?;
+                    s.serialize_field("mark_distance", &round_to(mark_distance, 1.0))?;
+                }
+                if let Some(vmc) = self.vmc() {
+                    s.serialize_field("vmc", &round_to(vmc, 100.0))?;
+                }
+                if let Some(eta) = self.eta() {
+                    s.serialize_field("eta", &round_to(eta, 1.0))?;
+                }
+                if let Some(time_owed) = self.time_owed() {
+                    s.serialize_field("time_owed", &round_to(time_owed, 10.0))?;
+                }
             }
         }
 
+        s.serialize_field("fix", &self.fix)?;
+        if self.geometry != BoatGeometry::default() {
+            s.serialize_field("bow", &round_to(self.geometry.bow, 100.0))?;
+            s.serialize_field("beam", &round_to(self.geometry.beam, 100.0))?;
+        }
+        if self.sequence != Sequence::default() {
+            s.serialize_field("sequence", &self.sequence)?;
+        }
+        if let Some(hdop) = self.hdop {
+            s.serialize_field("hdop", &round_to(hdop, 100.0))?;
+        }
+        if self.ocs {
+            s.serialize_field("ocs", &true)?;
+        }
+        if let Some(wind) = self.wind {
+            s.serialize_field("wind", &round_to(wind * 180.0 / PI, 10.0))?;
+        }
+        if !self.course.is_empty() {
+            s.serialize_field("marks", &self.course.len())?;
+        }
+        match &self.finish_line {
+            Line::None => {}
+            Line::Stbd { .. } => s.serialize_field("finish_line", "Stbd")?,
+            Line::Port { .. } => s.serialize_field("finish_line", "Port")?,
+            Line::Both { .. } => s.serialize_field("finish_line", "Both")?,
+        }
+        if let Some(finish) = &self.finish {
+            s.serialize_field("finish", finish)?;
+        }
+        if let Some(rating) = &self.rating {
+            s.serialize_field("rating", &rating.rounded())?;
+        }
+        if let Some(target) = &self.target {
+            s.serialize_field("target", &target.rounded())?;
+        }
+        if let Some(corrected) = self.corrected_time() {
+            s.serialize_field("corrected", &round_to(corrected, 10.0))?;
+        }
+
         // Conditionally serialize the `line` field based on `state`
         if !matches!(self.state, State::Racing { .. }) {
             // s.serialize_field("line", &self.line)?;
@@ -255,11 +1243,28 @@ impl Serialize for Race {
                 Line::Both {
                     line_cross,
                     line_timestamp,
+                    line_distance,
                     ..
                 } => {
                     s.serialize_field("line", "Both")?;
                     s.serialize_field("line_cross", line_cross)?;
                     s.serialize_field("line_timestamp", line_timestamp)?;
+
+                    if let Some(distance) = line_distance {
+                        s.serialize_field("distance_to_line", &round_to(*distance, 10.0))?;
+                    }
+                    if let Some(time_to_line) = self.time_to_line() {
+                        s.serialize_field("time_to_line", &round_to(time_to_line, 10.0))?;
+                    }
+                    if let Some(time_to_burn) = self.time_to_burn() {
+                        s.serialize_field("time_to_burn", &round_to(time_to_burn, 10.0))?;
+                    }
+                    if let Some((bias, metres)) = self.line_bias() {
+                        s.serialize_field("bias", &round_to(bias, 10.0))?;
+                        s.serialize_field("bias_distance", &round_to(metres, 10.0))?;
+                        let favoured = if bias < 0.0 { "Stbd" } else { "Port" };
+                        s.serialize_field("favoured", favoured)?;
+                    }
                 }
             }
         }
@@ -3,7 +3,9 @@ mod tests {
     use crate::race::*;
     use crate::line::Line;
     use core::f64::consts::PI;
-    use extreme_traits::Engine;
+    use crate::test_helpers::{event, fix};
+    use crate::types::FixState;
+    use extreme_traits::{Engine, Fix, FixStatus};
     use serde_json;
     use serde_json::json;
 
@@ -17,17 +19,19 @@ mod tests {
         //
         assert_json_eq(json!({
             "state": "Active",
+            "fix": "None",
             "speed": 0.0,
             "line": "None",
         }), race);
 
         assert_eq!(
-            race.location_event(0, None, Some((23.2, 350.0))),
+            race.location_event(0, &fix(None, Some((23.2, 350.0)))),
             (Some(()), None),
         );
 
         assert_json_eq(json!({
             "state": "Active",
+            "fix": "Valid",
             "speed": 23.2,
             "line": "None",
         }), race);
@@ -36,13 +40,14 @@ mod tests {
         assert_eq!(
             race.external_event(
                 0, 
-                Event::LineStbd,
+                &event(EventType::LineStbd),
             ),
             (Some(()), None),
         );
 
         assert_json_eq(json!({
             "state": "Active",
+            "fix": "Valid",
             "speed": 23.2,
             "line": "Stbd",
         }), race);
@@ -55,8 +60,11 @@ mod tests {
 
         assert_json_eq(json!({
             "state": "InSequence",
+            "fix": "Valid",
             "speed": 23.2,
             "start_time": 31_000,
+            "signal": "Start",
+            "signal_time": 31_000,
             "line": "Stbd",
         }), race);
 
@@ -65,15 +73,18 @@ mod tests {
         assert_eq!(
             race.external_event(
                 0, 
-                Event::LinePort,
+                &event(EventType::LinePort),
             ),
             (Some(()), None),
         );
 
         assert_json_eq(json!({
             "state": "InSequence",
+            "fix": "Valid",
             "speed": 23.2,
             "start_time": 31_000,
+            "signal": "Start",
+            "signal_time": 31_000,
             "line": "Both",
             "line_cross": 0,
             "line_timestamp": 0,
@@ -90,6 +101,7 @@ mod tests {
 
         assert_json_eq(json!({
             "state": "Racing",
+            "fix": "Valid",
             "speed": 23.2,
             // we don't keep speed across the state change
             "heading": 0.0, 
@@ -97,12 +109,13 @@ mod tests {
         }), race);
 
         assert_eq!(
-            race.location_event(0, None, Some((17.5, 253.0))),
+            race.location_event(0, &fix(None, Some((17.5, 253.0)))),
             (Some(()), None),
         );
 
         assert_json_eq(json!({
             "state": "Racing",
+            "fix": "Valid",
             "speed": 17.5,
             "heading": 253.0, 
             "start_time": 31_000,
@@ -114,13 +127,14 @@ mod tests {
         assert_eq!(
             race.external_event(
                 0, 
-                Event::RaceFinish,
+                &event(EventType::RaceFinish),
             ),
             (Some(()), None),
         );
 
         assert_json_eq(json!({
             "state": "Active",
+            "fix": "Valid",
             "speed": 17.5,
             "line": "Both",
             "line_cross": 0,
@@ -136,10 +150,11 @@ mod tests {
         let loc2 = (32.3, -113.2);
 
         // set a location for stbd
-        assert_eq!(race.location_event(0, Some(loc1), None), (None, None));
+        // the first fix changes the fix state
+        assert_eq!(race.location_event(0, &fix(Some(loc1), None)), (Some(()), None));
 
         assert_eq!(
-            race.external_event(0, Event::LineStbd),
+            race.external_event(0, &event(EventType::LineStbd)),
             (Some(()), None)
         );
 
@@ -151,12 +166,12 @@ mod tests {
         }
 
         // set a new location for stbd
-        let (updated, timer) = race.location_event(0, Some(loc2), None);
+        let (updated, timer) = race.location_event(0, &fix(Some(loc2), None));
         assert_eq!(None, updated);
         assert_eq!(None, timer);
 
         assert_eq!(
-            race.external_event(0, Event::LineStbd),
+            race.external_event(0, &event(EventType::LineStbd)),
             (None, None)
         );
         if let Line::Stbd { stbd_location } = race.line {
@@ -168,11 +183,11 @@ mod tests {
 
         // set port and check that line is Both
         assert_eq!(
-            race.location_event(0, Some(loc1), None),
+            race.location_event(0, &fix(Some(loc1), None)),
             (None, None)
         );
         assert_eq!(
-            race.external_event(0, Event::LinePort),
+            race.external_event(0, &event(EventType::LinePort)),
             (Some(()), None)
         );
         assert!(matches!(race.line, Line::Both { .. }));
@@ -188,11 +203,11 @@ mod tests {
         // set a new location for port
         let loc3 = (42.3, -113.2);
         assert_eq!(
-            race.location_event(0, Some(loc3), None),
+            race.location_event(0, &fix(Some(loc3), None)),
             (None, None)
         );
         assert_eq!(
-            race.external_event(0, Event::LinePort),
+            race.external_event(0, &event(EventType::LinePort)),
             (Some(()), None)
         );
 
@@ -206,6 +221,919 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_manual_line() {
+        let mut race = Race::default();
+        let stbd = (-34.956404, 138.503427);
+        let port = (-34.957152, 138.503438);
+
+        // published coordinates, without going anywhere near them
+        let stbd_at = EventType::LineStbdAt { lat: stbd.0, lon: stbd.1 };
+        assert_eq!(race.external_event(0, &event(stbd_at)), (Some(()), None));
+        assert!(matches!(race.line, Line::Stbd { .. }));
+        let port_at = EventType::LinePortAt { lat: port.0, lon: port.1 };
+        assert_eq!(race.external_event(0, &event(port_at)), (Some(()), None));
+
+        let mut sailed = Race::default();
+        set_line(&mut sailed, &stbd, &port);
+        assert!(race.line == sailed.line);
+
+        // off the earth
+        let bad = EventType::LinePortAt { lat: 91.0, lon: 0.0 };
+        assert_eq!(race.external_event(0, &event(bad)), (None, None));
+        let bad = EventType::LineStbdAt { lat: 0.0, lon: f64::NAN };
+        assert_eq!(race.external_event(0, &event(bad)), (None, None));
+        assert!(race.line == sailed.line);
+
+        // one pin and the line's bearing and length
+        let mut race = Race::default();
+        let from_stbd = EventType::LineFromStbd { bearing: 180.0, length: 83.0 };
+        assert_eq!(race.external_event(0, &event(from_stbd)), (None, None));
+        race.external_event(0, &event(EventType::LineStbdAt { lat: stbd.0, lon: stbd.1 }));
+        let from_stbd = EventType::LineFromStbd { bearing: 180.0, length: 83.0 };
+        assert_eq!(race.external_event(0, &event(from_stbd)), (Some(()), None));
+        if let Line::Both { stbd: pin, port, bearing, length, .. } = race.line {
+            assert_eq!(pin.lat, to_rad(stbd.0));
+            assert!((bearing - PI).abs() < 1e-6);
+            assert!((length - 83.0).abs() < 1e-6);
+            assert!(port.lat < pin.lat);
+        } else {
+            panic!("Line was not Both as expected");
+        }
+
+        // from the port end the bearing is to the stbd pin
+        let mut race = Race::default();
+        race.external_event(0, &event(EventType::LinePortAt { lat: port.0, lon: port.1 }));
+        let from_port = EventType::LineFromPort { bearing: 45.0, length: 150.0 };
+        assert_eq!(race.external_event(0, &event(from_port)), (Some(()), None));
+        if let Line::Both { stbd, bearing, length, .. } = race.line {
+            assert!((bearing - to_rad(225.0)).abs() < 1e-4);
+            assert!((length - 150.0).abs() < 1e-6);
+            assert!(stbd.lat > to_rad(port.0));
+        } else {
+            panic!("Line was not Both as expected");
+        }
+
+        let too_long = EventType::LineFromPort { bearing: 45.0, length: 15_000.0 };
+        assert_eq!(race.external_event(0, &event(too_long)), (None, None));
+        let no_bearing = EventType::LineFromPort { bearing: 400.0, length: 100.0 };
+        assert_eq!(race.external_event(0, &event(no_bearing)), (None, None));
+
+        // as the UI sends them
+        let mut race = Race::default();
+        let json = br#"{"event":{"LineStbdAt":{"lat":-34.956404,"lon":138.503427}}}"#;
+        let (update, _) = extreme_traits::RawEngine::external_event(&mut race, 0, json).unwrap();
+        assert!(update.is_some());
+        let json = br#"{"event":{"LineFromStbd":{"bearing":180,"length":83.5}}}"#;
+        extreme_traits::RawEngine::external_event(&mut race, 0, json).unwrap();
+        assert!(matches!(race.line, Line::Both { .. }));
+    }
+
+    #[test]
+    fn test_geometry() {
+        let mut race = Race::default();
+        let antenna = (-34.956404, 138.503427);
+
+        let geometry = EventType::Geometry { bow: 4.0, beam: 0.0 };
+        assert_eq!(race.external_event(0, &event(geometry)), (Some(()), None));
+        let geometry = EventType::Geometry { bow: 4.0, beam: 0.0 };
+        assert_eq!(race.external_event(0, &event(geometry)), (None, None));
+        let backwards = EventType::Geometry { bow: -1.0, beam: 0.0 };
+        assert_eq!(race.external_event(0, &event(backwards)), (None, None));
+
+        // heading north, the bow is 4m up the chart from the antenna
+        race.location_event(0, &fix(Some(antenna), Some((0.1, 0.0))));
+        race.external_event(0, &event(EventType::LineStbd));
+        let Line::Stbd { stbd_location } = race.line else {
+            panic!("Line was not Stbd as expected");
+        };
+        let metres = (stbd_location.lat - to_rad(antenna.0)) * 6371e3;
+        assert!((metres - 4.0).abs() < 0.01);
+        assert!((stbd_location.lon - to_rad(antenna.1)).abs() < 1e-12);
+
+        // heading east with the antenna 1m to starboard, the bow is east and north
+        let geometry = EventType::Geometry { bow: 4.0, beam: 1.0 };
+        race.external_event(0, &event(geometry));
+        race.location_event(0, &fix(Some(antenna), Some((0.1, 90.0))));
+        race.external_event(0, &event(EventType::LinePort));
+        let Line::Both { port, .. } = race.line else {
+            panic!("Line was not Both as expected");
+        };
+        let north = (port.lat - to_rad(antenna.0)) * 6371e3;
+        let east = (port.lon - to_rad(antenna.1)) * 6371e3 * libm::cos(port.lat);
+        assert!((north - 1.0).abs() < 0.01);
+        assert!((east - 4.0).abs() < 0.01);
+
+        assert_json_eq(json!({
+            "state": "Active",
+            "fix": "Valid",
+            "speed": 0.1,
+            "bow": 4.0,
+            "beam": 1.0,
+            "line": "Both",
+            "line_cross": 0,
+            "line_timestamp": 0,
+        }), race);
+
+        // setup survives a reboot
+        let mut buf = [0u8; 64];
+        let len = extreme_traits::RawEngine::save(&race, &mut buf).unwrap();
+        let mut restored = Race::default();
+        extreme_traits::RawEngine::load(&mut restored, &buf[..len]).unwrap();
+        assert!(restored.geometry == race.geometry);
+        assert!(restored.line == race.line);
+    }
+
+    #[test]
+    fn test_geometry_time_to_line() {
+        let stbd = (-34.956404, 138.503427);
+        let port = (-34.957152, 138.503438);
+        let boat_loc = (-34.956800, 138.504157);
+        let boat_velocity = (10.0, 270.0);
+
+        let mut point = Race::default();
+        set_line(&mut point, &stbd, &port);
+        expect_cross(&mut point, &boat_loc, &boat_velocity, 47, 12828);
+
+        // the bow gets there 10m, just under 2s at 10kn, before the antenna
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        race.external_event(0, &event(EventType::Geometry { bow: 10.0, beam: 0.0 }));
+        expect_cross(&mut race, &boat_loc, &boat_velocity, 47, 12828 - 1944);
+    }
+
+    #[test]
+    fn test_distance_to_line() {
+        let mut race = Race::default();
+        // a north-south line, the course is to the west
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        set_line(&mut race, &stbd, &port);
+
+        // nothing to report until the boat's been placed against the line
+        assert!(race.time_to_line().is_none());
+        let json = serde_json::to_value(race).unwrap();
+        assert!(json.get("distance_to_line").is_none());
+
+        // 100m short of the middle, 5kn straight at it
+        let metres_east = |metres: f64| 138.503430 + metres / (6371e3 * libm::cos(to_rad(-34.9568))) * 180.0 / PI;
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(21_000, &fix(Some((-34.9568, metres_east(100.0))), Some((5.0, 270.0))));
+
+        let Line::Both { line_distance: Some(distance), .. } = race.line else {
+            panic!("Line was not Both as expected");
+        };
+        assert!((distance - 100.0).abs() < 0.01);
+        let time_to_line = race.time_to_line().unwrap();
+        assert!((time_to_line - 100.0 / (5.0 * 1852.0 / 3600.0)).abs() < 0.01);
+        // 40s to the gun, less the 38.9s it takes to get there
+        let time_to_burn = race.time_to_burn().unwrap();
+        assert!((time_to_burn - (40.0 - time_to_line)).abs() < 1e-9);
+
+        let json = serde_json::to_value(race).unwrap();
+        assert_eq!(json["state"], "InSequence");
+        // to the tenth
+        assert!((json["distance_to_line"].as_f64().unwrap() - distance).abs() <= 0.05);
+        assert!((json["time_to_line"].as_f64().unwrap() - time_to_line).abs() <= 0.05);
+        assert!((json["time_to_burn"].as_f64().unwrap() - time_to_burn).abs() <= 0.05);
+
+        // over the line, both go negative
+        race.location_event(30_000, &fix(Some((-34.9568, metres_east(-20.0))), Some((5.0, 90.0))));
+        assert!((race.time_to_line().unwrap() + 20.0 / (5.0 * 1852.0 / 3600.0)).abs() < 0.01);
+        let json = serde_json::to_value(race).unwrap();
+        assert!(json["distance_to_line"].as_f64().unwrap() < -19.9);
+        assert!(json["time_to_burn"].as_f64().unwrap() > 31.0 - 0.01);
+
+        // head to wind, there's no sensible time
+        race.location_event(31_000, &fix(Some((-34.9568, metres_east(-20.0))), Some((0.2, 0.0))));
+        assert!(race.time_to_line().is_none());
+        let json = serde_json::to_value(race).unwrap();
+        assert!(json.get("distance_to_line").is_some());
+        assert!(json.get("time_to_line").is_none());
+        assert!(json.get("time_to_burn").is_none());
+
+        // no burning once the sequence is over
+        race.location_event(32_000, &fix(Some((-34.9568, metres_east(50.0))), Some((5.0, 270.0))));
+        race.external_event(0, &event(EventType::RaceFinish));
+        assert!(race.time_to_line().is_some());
+        assert!(race.time_to_burn().is_none());
+    }
+
+    #[test]
+    fn test_ocs() {
+        // a north-south line, the course is to the west
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 120, 121_000);
+
+        let west = (-34.9568, 138.503330);
+        let east = (-34.9568, 138.503530);
+        let sail = |race: &mut Race, timestamp, location, heading| {
+            race.location_event(timestamp, &fix(Some(location), Some((5.0, heading))))
+        };
+
+        // over early is fine, there's time to get back
+        sail(&mut race, 30_000, west, 270.0);
+        assert!(!race.ocs);
+
+        // over in the last minute
+        assert_eq!(sail(&mut race, 70_000, west, 270.0), (Some(()), None));
+        assert!(race.ocs);
+        assert_eq!(serde_json::to_value(race).unwrap()["ocs"], true);
+
+        // dipped back
+        sail(&mut race, 80_000, east, 90.0);
+        assert!(!race.ocs);
+        assert!(serde_json::to_value(race).unwrap().get("ocs").is_none());
+
+        // over at the gun stays OCS until the boat comes back
+        sail(&mut race, 120_000, west, 270.0);
+        assert!(race.ocs);
+        assert_eq!(race.timer_event(121_000), (Some(()), None));
+        assert!(race.ocs);
+        sail(&mut race, 125_000, west, 200.0);
+        assert!(race.ocs);
+        assert_eq!(serde_json::to_value(race).unwrap()["ocs"], true);
+        assert_eq!(sail(&mut race, 130_000, east, 90.0), (Some(()), None));
+        assert!(!race.ocs);
+
+        // and then starts properly
+        sail(&mut race, 140_000, west, 270.0);
+        assert!(!race.ocs);
+    }
+
+    #[test]
+    fn test_ocs_either_way() {
+        // pins the other way round, the course is to the east
+        let stbd = (-34.957152, 138.503430);
+        let port = (-34.956404, 138.503430);
+        let west = (-34.9568, 138.503330);
+        let east = (-34.9568, 138.503530);
+
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(10_000, &fix(Some(west), Some((5.0, 90.0))));
+        assert!(!race.ocs);
+        race.location_event(20_000, &fix(Some(east), Some((5.0, 90.0))));
+        assert!(race.ocs);
+
+        // a clean start from the pre-start side
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(60_000, &fix(Some(west), Some((5.0, 90.0))));
+        race.timer_event(61_000);
+        assert!(!race.ocs);
+        race.location_event(62_000, &fix(Some(east), Some((5.0, 90.0))));
+        assert!(!race.ocs);
+
+        // no line, no OCS
+        let mut race = Race::default();
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(60_000, &fix(Some(east), Some((5.0, 90.0))));
+        race.timer_event(61_000);
+        assert!(!race.ocs);
+    }
+
+    #[test]
+    fn test_start_report() {
+        // a north-south line, the course is to the west
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let west = (-34.9568, 138.503330);
+        let east = (-34.9568, 138.503530);
+
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(60_000, &fix(Some(east), Some((4.0, 270.0))));
+        race.timer_event(61_000);
+        race.location_event(62_000, &fix(Some(east), Some((4.0, 270.0))));
+        assert!(race.start.is_none());
+        assert!(serde_json::to_value(race).unwrap().get("start").is_none());
+
+        // through the line halfway between the fixes
+        assert_eq!(
+            race.location_event(64_000, &fix(Some(west), Some((6.0, 270.0)))),
+            (Some(()), None)
+        );
+        let start = race.start.unwrap();
+        assert!(start.time.abs_diff(63_000) <= 1, "{}", start.time);
+        assert!((start.late - 2.0).abs() < 0.002, "{}", start.late);
+        assert!((start.speed - 5.0).abs() < 0.01, "{}", start.speed);
+        assert!((start.position - 0.5294).abs() < 0.001, "{}", start.position);
+        let json = serde_json::to_value(race).unwrap();
+        assert_eq!(json["start"]["time"], start.time);
+        assert!((json["start"]["position"].as_f64().unwrap() - start.position).abs() < 1e-9);
+
+        // and survives a snapshot
+        let mut buf = [0u8; 512];
+        let len = extreme_traits::RawEngine::snapshot(&race, &mut buf).unwrap();
+        let mut restored = Race::default();
+        extreme_traits::RawEngine::restore(&mut restored, &buf[..len]).unwrap();
+        assert!(restored == race);
+
+        // only the first crossing counts
+        race.location_event(66_000, &fix(Some(east), Some((6.0, 90.0))));
+        race.location_event(68_000, &fix(Some(west), Some((6.0, 270.0))));
+        assert!(race.start == Some(start));
+
+        race.external_event(70_000, &event(EventType::RaceFinish));
+        assert!(race.start.is_none());
+    }
+
+    #[test]
+    fn test_start_report_edges() {
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let west = (-34.9568, 138.503330);
+        let east = (-34.9568, 138.503530);
+
+        // crossed between the last fix before the gun and the first after it, early
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(60_000, &fix(Some(east), Some((5.0, 270.0))));
+        race.timer_event(61_000);
+        assert!(!race.ocs);
+        race.location_event(61_500, &fix(Some(west), Some((5.0, 270.0))));
+        assert!(race.ocs);
+        assert!(race.start.is_none());
+
+        // back, and start again
+        race.location_event(65_000, &fix(Some(east), Some((5.0, 90.0))));
+        assert!(!race.ocs);
+        race.location_event(70_000, &fix(Some(west), Some((5.0, 270.0))));
+        assert!((race.start.unwrap().late - 6.5).abs() < 0.01);
+
+        // round the outside of the stbd pin isn't a start
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 60, 61_000);
+        race.timer_event(61_000);
+        race.location_event(62_000, &fix(Some((-34.9560, 138.503530)), Some((5.0, 270.0))));
+        race.location_event(64_000, &fix(Some((-34.9560, 138.503330)), Some((5.0, 270.0))));
+        assert!(race.start.is_none());
+
+        // crossing before the gun isn't one either
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(2_000, &fix(Some(east), Some((5.0, 270.0))));
+        race.location_event(4_000, &fix(Some(west), Some((5.0, 270.0))));
+        assert!(race.start.is_none());
+    }
+
+    #[test]
+    fn test_finish() {
+        // start through a north-south line heading west, finish through another
+        // 200m further on
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let finish_stbd = (-34.956404, 138.501230);
+        let finish_port = (-34.957152, 138.501230);
+        let behind = (-34.9568, 138.503530);
+        let started = (-34.9568, 138.503330);
+        let short = (-34.9568, 138.501330);
+        let finished = (-34.9568, 138.501130);
+
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        assert_eq!(
+            race.external_event(0, &event(EventType::FinishStbdAt { lat: finish_stbd.0, lon: finish_stbd.1 })),
+            (Some(()), None)
+        );
+        assert_eq!(serde_json::to_value(race).unwrap()["finish_line"], "Stbd");
+        race.external_event(0, &event(EventType::FinishPortAt { lat: finish_port.0, lon: finish_port.1 }));
+        assert_eq!(serde_json::to_value(race).unwrap()["finish_line"], "Both");
+
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(61_000, &fix(Some(behind), Some((5.0, 270.0))));
+        race.timer_event(61_000);
+        race.location_event(63_000, &fix(Some(started), Some((5.0, 270.0))));
+        assert!(race.start.is_some());
+        race.location_event(150_000, &fix(Some(short), Some((5.0, 270.0))));
+        assert!(race.finish.is_none());
+
+        // through the finish halfway between fixes
+        assert_eq!(
+            race.location_event(152_000, &fix(Some(finished), Some((7.0, 270.0)))),
+            (Some(()), None)
+        );
+        let finish = race.finish.unwrap();
+        assert!(finish.time.abs_diff(151_000) <= 1, "{}", finish.time);
+        assert!((finish.elapsed - 90.0).abs() < 0.002, "{}", finish.elapsed);
+        assert!((finish.speed - 6.0).abs() < 0.01, "{}", finish.speed);
+        assert!((finish.position - 0.5294).abs() < 0.001, "{}", finish.position);
+        assert!(matches!(race.state, State::Active { .. }));
+        assert!(race.start.is_none());
+
+        // the result stays up until the next sequence
+        let json = serde_json::to_value(race).unwrap();
+        assert_eq!(json["finish"]["time"], finish.time);
+        race.location_event(160_000, &fix(Some(short), Some((5.0, 90.0))));
+        assert!(race.finish == Some(finish));
+        bump(&mut race, 200_000, 60, 260_000);
+        assert!(race.finish.is_none());
+        assert!(serde_json::to_value(race).unwrap().get("finish").is_none());
+    }
+
+    #[test]
+    fn test_finish_edges() {
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let behind = (-34.9568, 138.503530);
+        let started = (-34.9568, 138.503330);
+
+        // the start line is the finish line, out through it and back
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+        race.external_event(0, &event(EventType::FinishStbdAt { lat: stbd.0, lon: stbd.1 }));
+        race.external_event(0, &event(EventType::FinishPortAt { lat: port.0, lon: port.1 }));
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(61_000, &fix(Some(behind), Some((5.0, 270.0))));
+        race.timer_event(61_000);
+        race.location_event(63_000, &fix(Some(started), Some((5.0, 270.0))));
+        assert!(race.start.is_some());
+        assert!(race.finish.is_none());
+        race.location_event(100_000, &fix(Some(started), Some((5.0, 90.0))));
+        race.location_event(102_000, &fix(Some(behind), Some((5.0, 90.0))));
+        assert!((race.finish.unwrap().elapsed - 40.0).abs() < 0.002);
+
+        // marks to go round first
+        let mut race = Race::default();
+        race.external_event(0, &event(EventType::FinishStbdAt { lat: stbd.0, lon: stbd.1 }));
+        race.external_event(0, &event(EventType::FinishPortAt { lat: port.0, lon: port.1 }));
+        race.external_event(0, &event(EventType::MarkAt { lat: -34.95, lon: 138.5 }));
+        bump(&mut race, 1000, 60, 61_000);
+        race.timer_event(61_000);
+        race.location_event(62_000, &fix(Some(behind), Some((5.0, 270.0))));
+        race.location_event(64_000, &fix(Some(started), Some((5.0, 270.0))));
+        assert!(race.finish.is_none());
+
+        // with no start line, any crossing after the gun finishes
+        race.external_event(0, &event(EventType::NextMark));
+        race.location_event(66_000, &fix(Some(behind), Some((5.0, 90.0))));
+        assert!(race.finish.is_some());
+
+        // not before it
+        let mut race = Race::default();
+        race.external_event(0, &event(EventType::FinishStbdAt { lat: stbd.0, lon: stbd.1 }));
+        race.external_event(0, &event(EventType::FinishPortAt { lat: port.0, lon: port.1 }));
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(2_000, &fix(Some(behind), Some((5.0, 270.0))));
+        race.location_event(4_000, &fix(Some(started), Some((5.0, 270.0))));
+        assert!(race.finish.is_none());
+    }
+
+    #[test]
+    fn test_line_bias() {
+        // a north-south line, square to a westerly
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let mut race = Race::default();
+        assert_eq!(
+            race.external_event(0, &event(EventType::Wind { direction: 270.0 })),
+            (Some(()), None)
+        );
+        assert_eq!(race.line_bias(), None);
+
+        set_line(&mut race, &stbd, &port);
+        let (bias, metres) = race.line_bias().unwrap();
+        assert!(bias.abs() < 0.01, "{}", bias);
+        assert!(metres.abs() < 0.1, "{}", metres);
+
+        // veered, the stbd end is upwind
+        race.external_event(0, &event(EventType::Wind { direction: 280.0 }));
+        let (bias, metres) = race.line_bias().unwrap();
+        assert!((bias + 10.0).abs() < 0.01, "{}", bias);
+        let length = 0.000748 * PI / 180.0 * 6371e3;
+        assert!((metres + length * to_rad(10.0).sin()).abs() < 0.1, "{}", metres);
+        let json = serde_json::to_value(race).unwrap();
+        assert_eq!(json["favoured"], "Stbd");
+        assert!((json["wind"].as_f64().unwrap() - 280.0).abs() < 1e-9);
+
+        // backed, the port end
+        race.external_event(0, &event(EventType::Wind { direction: 255.0 }));
+        let (bias, metres) = race.line_bias().unwrap();
+        assert!((bias - 15.0).abs() < 0.01, "{}", bias);
+        assert!(metres > 0.0);
+        assert_eq!(serde_json::to_value(race).unwrap()["favoured"], "Port");
+
+        // a northerly, the line points into the wind with the stbd end at the top
+        race.external_event(0, &event(EventType::Wind { direction: 0.0 }));
+        let (bias, metres) = race.line_bias().unwrap();
+        assert!((bias + 90.0).abs() < 0.01, "{}", bias);
+        assert!((metres + length).abs() < 0.1, "{}", metres);
+
+        assert_eq!(
+            race.external_event(0, &event(EventType::Wind { direction: 361.0 })),
+            (None, None)
+        );
+
+        // head to wind, the wind comes from wherever the boat is heading
+        let mut race = Race::default();
+        assert_eq!(
+            race.external_event(0, &event(EventType::WindPing)),
+            (None, None)
+        );
+        race.location_event(1000, &fix(Some(stbd), Some((3.0, 265.0))));
+        assert_eq!(
+            race.external_event(1000, &event(EventType::WindPing)),
+            (Some(()), None)
+        );
+        assert!((race.wind.unwrap() - to_rad(265.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_wind_ping() {
+        let stbd = (-34.956404, 138.503427);
+        let mut race = Race::default();
+
+        // drifting, COG is all over the place and the ping is refused
+        for (i, heading) in [350.0, 20.0, 300.0, 90.0, 330.0, 40.0].iter().enumerate() {
+            race.location_event(i as u64 * 1000, &fix(Some(stbd), Some((0.5, *heading))));
+        }
+        assert_eq!(
+            race.external_event(6_000, &event(EventType::WindPing)),
+            (None, None)
+        );
+        assert!(race.wind.is_none());
+
+        // head to wind, one stray fix doesn't take the wind with it
+        for (i, heading) in [345.0, 355.0, 340.0, 0.0, 350.0, 20.0].iter().enumerate() {
+            race.location_event(10_000 + i as u64 * 1000, &fix(Some(stbd), Some((2.0, *heading))));
+        }
+        assert_eq!(
+            race.external_event(15_000, &event(EventType::WindPing)),
+            (Some(()), None)
+        );
+        let wind = race.wind.unwrap() * 180.0 / PI;
+        assert!((wind - 350.0).abs() < 5.0, "{}", wind);
+    }
+
+    #[test]
+    fn test_course() {
+        let start = (-34.9568, 138.5034);
+        let windward = (-34.9523, 138.5034);
+        let mut race = Race::default();
+
+        // a mark by coordinates, then one pinged at the start
+        assert_eq!(
+            race.external_event(0, &event(EventType::MarkAt { lat: windward.0, lon: windward.1 })),
+            (Some(()), None)
+        );
+        assert_eq!(
+            race.external_event(0, &event(EventType::MarkAt { lat: 91.0, lon: 0.0 })),
+            (None, None)
+        );
+        race.location_event(1000, &fix(Some(start), Some((0.0, 0.0))));
+        assert_eq!(race.external_event(0, &event(EventType::Mark)), (Some(()), None));
+        assert_eq!(race.course.len(), 2);
+        assert_eq!(serde_json::to_value(race).unwrap()["marks"], 2);
+
+        // heading straight for the windward mark, 500m away
+        bump(&mut race, 1000, 30, 31_000);
+        race.timer_event(31_000);
+        race.location_event(32_000, &fix(Some(start), Some((6.0, 0.0))));
+        let (bearing, distance) = race.to_mark().unwrap();
+        assert!(bearing.abs() < 0.1 || (bearing - 360.0).abs() < 0.1, "{}", bearing);
+        assert!((distance - 500.4).abs() < 1.0, "{}", distance);
+        assert!((race.vmc().unwrap() - 6.0).abs() < 0.01);
+        let eta = distance / (6.0 * 1852.0 / 3600.0);
+        assert!((race.eta().unwrap() - eta).abs() < 0.5, "{}", race.eta().unwrap());
+        let json = serde_json::to_value(race).unwrap();
+        assert_eq!(json["mark"], 0);
+        // to the metre
+        assert!((json["mark_distance"].as_f64().unwrap() - distance).abs() <= 0.5);
+
+        // on a tack, half as fast again to the mark
+        race.location_event(33_000, &fix(Some(start), Some((6.0, 45.0))));
+        assert!((race.vmc().unwrap() - 6.0 * to_rad(45.0).cos()).abs() < 0.01);
+
+        // sailing away from it, no eta
+        race.location_event(34_000, &fix(Some(start), Some((6.0, 180.0))));
+        assert!(race.vmc().unwrap() < 0.0);
+        assert_eq!(race.eta(), None);
+        assert!(serde_json::to_value(race).unwrap().get("eta").is_none());
+
+        // round it, the leg moves on once the boat leaves the mark
+        let near = (-34.95225, 138.5034);
+        let away = (-34.95200, 138.5030);
+        assert_eq!(
+            race.location_event(40_000, &fix(Some(near), Some((6.0, 0.0)))),
+            (Some(()), None)
+        );
+        assert_eq!(race.course.leg(), 0);
+        race.location_event(50_000, &fix(Some(away), Some((6.0, 270.0))));
+        assert_eq!(race.course.leg(), 1);
+        let (bearing, _) = race.to_mark().unwrap();
+        assert!((bearing - 180.0).abs() < 20.0, "{}", bearing);
+
+        // skip the last, the course is done
+        assert_eq!(race.external_event(0, &event(EventType::NextMark)), (Some(()), None));
+        assert_eq!(race.external_event(0, &event(EventType::NextMark)), (None, None));
+        assert_eq!(race.to_mark(), None);
+        assert!(serde_json::to_value(race).unwrap().get("mark").is_none());
+
+        // the next race starts from the first mark
+        race.external_event(0, &event(EventType::RaceFinish));
+        assert_eq!(race.course.leg(), 0);
+        assert_eq!(race.vmc(), None);
+        assert!(serde_json::to_value(race).unwrap().get("mark").is_none());
+
+        assert_eq!(race.external_event(0, &event(EventType::ClearMarks)), (Some(()), None));
+        assert_eq!(race.external_event(0, &event(EventType::ClearMarks)), (None, None));
+        for _ in 0..crate::course::MAX_MARKS {
+            assert_eq!(race.external_event(0, &event(EventType::Mark)), (Some(()), None));
+        }
+        assert_eq!(race.external_event(0, &event(EventType::Mark)), (None, None));
+    }
+
+    #[test]
+    fn test_signals() {
+        use crate::sequence::{Sequence, Signal};
+
+        // 5-4-1-0, a timer for each signal and then the gun
+        let mut race = Race::default();
+        bump(&mut race, 1000, 301, 302_000);
+        assert!(race.next_signal == Some((2_000, Signal::Warning)));
+        let json = serde_json::to_value(race).unwrap();
+        assert_eq!(json["signal"], "Warning");
+        assert_eq!(json["signal_time"], 2_000);
+        assert!(json.get("sequence").is_none());
+
+        assert_eq!(race.timer_event(2_000), (Some(()), Some(62_000)));
+        assert_eq!(serde_json::to_value(race).unwrap()["signal"], "Prep");
+        assert_eq!(race.timer_event(62_000), (Some(()), Some(242_000)));
+        assert_eq!(serde_json::to_value(race).unwrap()["signal"], "PrepDown");
+        assert_eq!(race.timer_event(242_000), (Some(()), Some(302_000)));
+        assert_eq!(serde_json::to_value(race).unwrap()["signal"], "Start");
+        assert!(matches!(race.state, State::InSequence { .. }));
+        assert_eq!(race.timer_event(302_000), (Some(()), None));
+        assert!(matches!(race.state, State::Racing { .. }));
+        assert!(race.next_signal.is_none());
+
+        // 3-2-1-0, started with the warning signal already gone
+        let mut race = Race::default();
+        let three = event(EventType::Sequence { sequence: Sequence::ThreeTwoOne });
+        assert_eq!(race.external_event(0, &three), (Some(()), None));
+        assert_eq!(race.external_event(0, &three), (None, None));
+        assert_eq!(serde_json::to_value(race).unwrap()["sequence"], "ThreeTwoOne");
+        assert_eq!(
+            race.external_event(0, &event(EventType::BumpSeq { timestamp: 1000, seconds: 180 })),
+            (Some(()), Some(61_000))
+        );
+        assert!(race.next_signal == Some((61_000, Signal::Prep)));
+
+        // a minute later, the warning signal is back in the future
+        assert_eq!(
+            race.external_event(0, &event(EventType::BumpSeq { timestamp: 1000, seconds: -60 })),
+            (Some(()), Some(61_000))
+        );
+
+        // 10-5-0 mid sequence picks up from now
+        assert_eq!(
+            race.external_event(
+                20_000,
+                &event(EventType::Sequence { sequence: Sequence::TenFive })
+            ),
+            (Some(()), Some(241_000))
+        );
+        assert!(race.next_signal == Some((241_000, Signal::Start)));
+
+        // abandoned, the signal timer is cancelled
+        assert_eq!(
+            race.external_event(30_000, &event(EventType::RaceFinish)),
+            (Some(()), Some(0))
+        );
+        assert!(race.next_signal.is_none());
+
+        // the profile is kept over a reboot, and a snapshot comes back on the next signal
+        let mut buf = [0u8; 512];
+        let len = extreme_traits::RawEngine::save(&race, &mut buf).unwrap();
+        let mut restored = Race::default();
+        extreme_traits::RawEngine::load(&mut restored, &buf[..len]).unwrap();
+        assert!(restored.sequence == Sequence::TenFive);
+
+        let mut race = Race::default();
+        bump(&mut race, 1000, 301, 302_000);
+        race.timer_event(2_000);
+        let len = extreme_traits::RawEngine::snapshot(&race, &mut buf).unwrap();
+        assert_eq!(
+            extreme_traits::RawEngine::restore(&mut restored, &buf[..len]),
+            Ok(Some(62_000))
+        );
+        assert!(restored == race);
+    }
+
+    #[test]
+    fn test_general_recall() {
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let behind = (-34.9568, 138.503530);
+        let over = (-34.9568, 138.503330);
+
+        let mut race = Race::default();
+        let recall = |timestamp, seconds| event(EventType::GeneralRecall { timestamp, seconds });
+        assert_eq!(race.external_event(0, &recall(0, 360)), (None, None));
+
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(61_000, &fix(Some(behind), Some((5.0, 270.0))));
+        race.timer_event(61_000);
+        race.location_event(63_000, &fix(Some(over), Some((5.0, 270.0))));
+        assert!(race.start.is_some());
+
+        // six minutes to the new gun, the warning signal is the next one
+        assert_eq!(race.external_event(70_000, &recall(70_000, 360)), (Some(()), Some(130_000)));
+        assert!(matches!(race.state, State::InSequence { start_time: 430_000, .. }));
+        assert!(race.start.is_none());
+        assert!(matches!(race.line, Line::Both { .. }));
+
+        // and from the sequence itself
+        assert_eq!(race.external_event(80_000, &recall(80_000, 60)), (Some(()), Some(140_000)));
+        assert!(matches!(race.state, State::InSequence { start_time: 140_000, .. }));
+    }
+
+    #[test]
+    fn test_postpone() {
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+
+        // AP up, the timer is cancelled and the line is still there
+        bump(&mut race, 1000, 60, 61_000);
+        assert_eq!(race.external_event(5_000, &event(EventType::Postpone)), (Some(()), Some(0)));
+        assert_eq!(race.external_event(5_000, &event(EventType::Postpone)), (None, None));
+        assert!(matches!(race.state, State::Postponed { .. }));
+        assert!(race.next_signal.is_none());
+        let json = serde_json::to_value(race).unwrap();
+        assert_eq!(json["state"], "Postponed");
+        assert_eq!(json["line"], "Both");
+        assert!(json.get("start_time").is_none());
+
+        // a timer already on its way doesn't start the race
+        assert_eq!(race.timer_event(61_000), (None, None));
+        assert!(matches!(race.state, State::Postponed { .. }));
+
+        // AP down, the warning signal a minute later and the gun five after that
+        assert_eq!(
+            race.external_event(0, &event(EventType::Resume { timestamp: 100_000 })),
+            (Some(()), Some(160_000))
+        );
+        assert!(matches!(race.state, State::InSequence { start_time: 460_000, .. }));
+        assert_eq!(serde_json::to_value(race).unwrap()["signal"], "Warning");
+        assert_eq!(
+            race.external_event(0, &event(EventType::Resume { timestamp: 100_000 })),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn test_postpone_before_warning() {
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let mut race = Race::default();
+        set_line(&mut race, &stbd, &port);
+
+        // AP up before the sequence has started
+        assert_eq!(race.external_event(5_000, &event(EventType::Postpone)), (Some(()), Some(0)));
+        assert!(matches!(race.state, State::Postponed { .. }));
+        assert_eq!(serde_json::to_value(race).unwrap()["state"], "Postponed");
+
+        // AP down starts the sequence, warning a minute later
+        assert_eq!(
+            race.external_event(0, &event(EventType::Resume { timestamp: 100_000 })),
+            (Some(()), Some(160_000))
+        );
+        assert!(matches!(race.state, State::InSequence { start_time: 460_000, .. }));
+        assert_eq!(serde_json::to_value(race).unwrap()["signal"], "Warning");
+
+        // once racing it's too late
+        assert_eq!(race.timer_event(460_000).0, Some(()));
+        assert!(matches!(race.state, State::Racing { .. }));
+        assert_eq!(race.external_event(470_000, &event(EventType::Postpone)), (None, None));
+    }
+
+    #[test]
+    fn test_individual_recall() {
+        let stbd = (-34.956404, 138.503430);
+        let port = (-34.957152, 138.503430);
+        let behind = (-34.9568, 138.503530);
+        let over = (-34.9568, 138.503330);
+
+        let mut race = Race::default();
+        assert_eq!(race.external_event(0, &event(EventType::IndividualRecall)), (None, None));
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 60, 61_000);
+        race.location_event(60_900, &fix(Some(behind), Some((5.0, 270.0))));
+        race.timer_event(61_000);
+        race.location_event(61_900, &fix(Some(over), Some((5.0, 270.0))));
+        assert!(race.start.is_some());
+        assert!(!race.ocs);
+
+        // X flag, the committee saw the boat over and the start doesn't count
+        assert_eq!(
+            race.external_event(62_000, &event(EventType::IndividualRecall)),
+            (Some(()), None)
+        );
+        assert_eq!(race.external_event(62_000, &event(EventType::IndividualRecall)), (None, None));
+        assert!(race.ocs);
+        assert!(race.start.is_none());
+        race.location_event(64_000, &fix(Some(over), Some((5.0, 90.0))));
+        assert!(race.ocs);
+        assert_eq!(serde_json::to_value(race).unwrap()["ocs"], true);
+
+        // back across, and start again
+        race.location_event(70_000, &fix(Some(behind), Some((5.0, 90.0))));
+        assert!(!race.ocs);
+        assert!(race.start.is_none());
+        race.location_event(80_000, &fix(Some(over), Some((5.0, 270.0))));
+        assert!((race.start.unwrap().late - 14.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_handicap() {
+        use crate::handicap::Rating;
+
+        let tot = |tcf| Some(Rating::TimeOnTime { tcf });
+        let mut race = Race::default();
+        assert_eq!(
+            race.external_event(0, &event(EventType::Rating { rating: tot(1.0) })),
+            (Some(()), None)
+        );
+        assert_eq!(
+            race.external_event(0, &event(EventType::Rating { rating: tot(1.0) })),
+            (None, None)
+        );
+        assert_eq!(
+            race.external_event(0, &event(EventType::TargetRating { rating: tot(0.0) })),
+            (None, None)
+        );
+        race.external_event(0, &event(EventType::TargetRating { rating: tot(1.1) }));
+        let json = serde_json::to_value(race).unwrap();
+        assert_eq!(json["rating"], json!({"TimeOnTime": {"tcf": 1.0}}));
+        assert_eq!(json["target"], json!({"TimeOnTime": {"tcf": 1.1}}));
+        assert_eq!(race.time_owed(), None);
+
+        // an hour in, the faster boat owes us six minutes
+        bump(&mut race, 1000, 60, 61_000);
+        race.timer_event(61_000);
+        race.location_event(3_661_000, &fix(Some((-34.9568, 138.5034)), Some((5.0, 0.0))));
+        assert!((race.time_owed().unwrap() - 360.0).abs() < 1e-6);
+        let json = serde_json::to_value(race).unwrap();
+        assert!((json["time_owed"].as_f64().unwrap() - 360.0).abs() < 1e-6);
+        // the ratings are still there while racing
+        assert_eq!(json["rating"], json!({"TimeOnTime": {"tcf": 1.0}}));
+        assert_eq!(json["target"], json!({"TimeOnTime": {"tcf": 1.1}}));
+
+        // Portsmouth, the slower boat gets a tenth
+        let portsmouth = br#"{"event":{"Rating":{"rating":{"Portsmouth":{"number":1100.0}}}}}"#;
+        assert!(extreme_traits::RawEngine::external_event(&mut race, 0, portsmouth).is_ok());
+        race.external_event(0, &event(EventType::TargetRating { rating: Some(Rating::Portsmouth { number: 1000.0 }) }));
+        assert!((race.time_owed().unwrap() - 360.0).abs() < 1e-6);
+
+        // time on distance needs a course, a nautical mile of it
+        race.external_event(0, &event(EventType::Rating { rating: Some(Rating::TimeOnDistance { rating: 180.0 }) }));
+        race.external_event(0, &event(EventType::TargetRating { rating: Some(Rating::TimeOnDistance { rating: 150.0 }) }));
+        assert_eq!(race.time_owed(), None);
+        race.external_event(0, &event(EventType::MarkAt { lat: -34.95, lon: 138.5 }));
+        race.external_event(0, &event(EventType::MarkAt { lat: -34.95 + 1.0 / 60.0, lon: 138.5 }));
+        let distance = race.course_distance().unwrap();
+        assert!((distance - 1.0).abs() < 0.001, "{}", distance);
+        assert!((race.time_owed().unwrap() - 30.0 * distance).abs() < 1e-6);
+
+        // no target, nothing owed
+        let clear = br#"{"event":{"TargetRating":{"rating":null}}}"#;
+        assert!(extreme_traits::RawEngine::external_event(&mut race, 0, clear).is_ok());
+        assert!(race.target.is_none());
+        assert_eq!(race.time_owed(), None);
+
+        // corrected time once finished, out of the race
+        race.external_event(0, &event(EventType::Rating { rating: tot(0.95) }));
+        race.external_event(0, &event(EventType::RaceFinish));
+        assert_eq!(race.corrected_time(), None);
+        race.finish = Some(FinishReport { time: 3_661_000, elapsed: 3600.0, speed: 5.0, position: 0.5 });
+        assert!((race.corrected_time().unwrap() - 3420.0).abs() < 1e-6);
+        let json = serde_json::to_value(race).unwrap();
+        assert!((json["corrected"].as_f64().unwrap() - 3420.0).abs() < 1e-6);
+        assert!(json.get("time_owed").is_none());
+
+        // the ratings stay over a reboot
+        race.external_event(0, &event(EventType::TargetRating { rating: tot(1.1) }));
+        let mut buf = [0u8; 64];
+        let len = extreme_traits::RawEngine::save(&race, &mut buf).unwrap();
+        assert_eq!(len, 20);
+        let mut restored = Race::default();
+        extreme_traits::RawEngine::load(&mut restored, &buf[..len]).unwrap();
+        assert!(restored.rating == race.rating && restored.target == race.target);
+    }
+
     #[test]
     fn test_bump_sequence() {
         let mut race = Race::default();
@@ -248,7 +1176,7 @@ mod tests {
         }
 
         assert_eq!(
-            race.external_event(0, Event::RaceFinish),
+            race.external_event(0, &event(EventType::RaceFinish)),
             (Some(()), None)
         );
         assert!(
@@ -299,12 +1227,13 @@ mod tests {
         let mut race = Race::default();
 
         assert_eq!(
-            race.location_event(0, Some((42.3, -113.2)), Some((12.5, 270.0))),
+            race.location_event(0, &fix(Some((42.3, -113.2)), Some((12.5, 270.0)))),
             (Some(()), None),
         );
 
         assert_json_eq(json!({
             "state": "Active",
+            "fix": "Valid",
             "speed": 12.5,
             "line": "None"
         }), race);
@@ -313,13 +1242,14 @@ mod tests {
         assert_eq!(
             race.external_event(
                 0, 
-                Event::LineStbd,
+                &event(EventType::LineStbd),
             ),
             (Some(()), None),
         );
 
         assert_json_eq(json!({
             "state": "Active",
+            "fix": "Valid",
             "speed": 12.5,
             "line": "Stbd",
         }), race);
@@ -327,13 +1257,14 @@ mod tests {
         assert_eq!(
             race.external_event(
                 0, 
-                Event::LinePort,
+                &event(EventType::LinePort),
             ),
             (Some(()), None),
         );
 
         assert_json_eq(json!({
             "state": "Active",
+            "fix": "Valid",
             "speed": 12.5,
             "line": "Both",
             "line_cross": 0,
@@ -342,6 +1273,164 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_void_fix() {
+        let mut race = Race::default();
+        let loc1 = (-34.956404, 138.503427);
+        let loc2 = (-34.956800, 138.504157);
+
+        assert_eq!(
+            race.location_event(0, &fix(Some(loc1), Some((5.0, 270.0)))),
+            (Some(()), None),
+        );
+        assert!(race.fix == FixState::Valid);
+
+        // void fixes are reported, but don't move the boat
+        let void = Fix {
+            status: FixStatus::Void,
+            ..fix(Some(loc2), Some((12.0, 90.0)))
+        };
+        assert_eq!(race.location_event(0, &void), (Some(()), None));
+        assert!(race.fix == FixState::Void);
+        assert_eq!(race.location.lat, to_rad(loc1.0));
+        assert_eq!(race.location.lon, to_rad(loc1.1));
+
+        assert_json_eq(json!({
+            "state": "Active",
+            "fix": "Void",
+            "speed": 5.0,
+            "line": "None",
+        }), race);
+
+        // repeated void fixes are not a state change
+        assert_eq!(race.location_event(0, &void), (None, None));
+
+        let mut good = fix(Some(loc2), Some((12.0, 90.0)));
+        good.hdop = Some(0.9);
+        assert_eq!(race.location_event(0, &good), (Some(()), None));
+        assert_eq!(race.location.lat, to_rad(loc2.0));
+
+        assert_json_eq(json!({
+            "state": "Active",
+            "fix": "Valid",
+            "hdop": 0.9,
+            "speed": 12.0,
+            "line": "None",
+        }), race);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut race = Race::default();
+        let mut buf = [0u8; 64];
+
+        // nothing set, just the version and the flags
+        assert_eq!(extreme_traits::RawEngine::save(&race, &mut buf), Ok(2));
+        let stbd = (-34.956404, 138.503427);
+        let port = (-34.957152, 138.503438);
+        set_line(&mut race, &stbd, &port);
+
+        let len = extreme_traits::RawEngine::save(&race, &mut buf).unwrap();
+        assert_eq!(len, 34);
+        assert!(extreme_traits::RawEngine::save(&race, &mut buf[..33]).is_err());
+
+        let mut restored = Race::default();
+        assert_eq!(extreme_traits::RawEngine::load(&mut restored, &buf[..len]), Ok(()));
+        assert!(restored.line == race.line);
+        assert!(restored.line_restored);
+        assert!(extreme_traits::RawEngine::load(&mut restored, &buf[..len - 1]).is_err());
+
+        // still at the club, the line is kept
+        let near = (-34.95, 138.51);
+        assert_eq!(
+            restored.location_event(0, &fix(Some(near), None)),
+            (Some(()), None)
+        );
+        assert!(matches!(restored.line, Line::Both { .. }));
+        assert!(!restored.line_restored);
+
+        // a stbd pin from another venue is dropped on the first fix
+        let mut race = Race::default();
+        race.location_event(0, &fix(Some(stbd), None));
+        race.external_event(0, &event(EventType::LineStbd));
+        let len = extreme_traits::RawEngine::save(&race, &mut buf).unwrap();
+        assert_eq!(len, 18);
+
+        let mut restored = Race::default();
+        assert_eq!(extreme_traits::RawEngine::load(&mut restored, &buf[..len]), Ok(()));
+        assert!(matches!(restored.line, Line::Stbd { .. }));
+        let far = (-34.7, 138.5);
+        assert_eq!(
+            restored.location_event(0, &fix(Some(far), None)),
+            (Some(()), None)
+        );
+        assert!(restored.line == Line::None);
+
+        // the finish line too
+        let mut race = Race::default();
+        race.external_event(0, &event(EventType::FinishStbdAt { lat: stbd.0, lon: stbd.1 }));
+        race.external_event(0, &event(EventType::FinishPortAt { lat: port.0, lon: port.1 }));
+        let len = extreme_traits::RawEngine::save(&race, &mut buf).unwrap();
+        assert_eq!(len, 34);
+        let mut restored = Race::default();
+        assert_eq!(extreme_traits::RawEngine::load(&mut restored, &buf[..len]), Ok(()));
+        assert!(restored.finish_line == race.finish_line);
+        assert!(restored.line == Line::None);
+        restored.location_event(0, &fix(Some(far), None));
+        assert!(restored.finish_line == Line::None);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        use extreme_traits::RawEngine;
+
+        let mut race = Race::default();
+        let stbd = (-34.956404, 138.503427);
+        let port = (-34.957152, 138.503438);
+        set_line(&mut race, &stbd, &port);
+        bump(&mut race, 1000, 30, 31_000);
+        let mut good = fix(Some((-34.956800, 138.504157)), Some((5.0, 270.0)));
+        good.hdop = Some(1.2);
+        Engine::location_event(&mut race, 2000, &good);
+        Engine::external_event(&mut race, 2000, &event(EventType::Wind { direction: 280.0 }));
+
+        let mut buf = [0u8; 256];
+        let len = RawEngine::snapshot(&race, &mut buf).unwrap();
+        assert_eq!(buf[0], Race::SNAPSHOT_VERSION);
+        assert!(RawEngine::snapshot(&race, &mut buf[..len - 1]).is_err());
+
+        // what's saved for a reboot is the start of the snapshot
+        let mut saved = [0u8; 256];
+        let saved_len = RawEngine::save(&race, &mut saved).unwrap();
+        assert!(saved_len < len);
+        assert_eq!(saved[..saved_len], buf[..saved_len]);
+
+        // everything comes back, the hidden line geometry included, and the start
+        // timer is set again
+        let mut restored = Race::default();
+        assert_eq!(RawEngine::restore(&mut restored, &buf[..len]), Ok(Some(31_000)));
+        assert!(restored == race);
+        assert_eq!(
+            serde_json::to_value(restored).unwrap(),
+            serde_json::to_value(race).unwrap()
+        );
+
+        // racing needs no timer
+        Engine::timer_event(&mut race, 31_000);
+        let len = RawEngine::snapshot(&race, &mut buf).unwrap();
+        assert_eq!(RawEngine::restore(&mut restored, &buf[..len]), Ok(Some(0)));
+        assert!(matches!(restored.state, State::Racing { start_time: 31_000, .. }));
+
+        // short, long, or another version, and the engine is left alone
+        let before = restored;
+        assert!(RawEngine::restore(&mut restored, &buf[..len - 1]).is_err());
+        buf[len] = 0;
+        assert!(RawEngine::restore(&mut restored, &buf[..len + 1]).is_err());
+        buf[0] = Race::SNAPSHOT_VERSION + 1;
+        assert!(RawEngine::restore(&mut restored, &buf[..len]).is_err());
+        assert!(restored == before);
+    }
+
     fn assert_json_eq<Actual: serde::Serialize>(expected: serde_json::Value, actual: Actual) {
         let json_result = serde_json::to_string(&actual).unwrap();
         // println!("{}", actual);
@@ -399,12 +1488,12 @@ mod tests {
         // set a location for stbd
         //
         assert_eq!(
-            race.location_event(0, Some(*stbd), None), 
-            (None, None)
+            race.location_event(0, &fix(Some(*stbd), None)),
+            (Some(()), None)
         );
 
         assert_eq!(
-            race.external_event(0, Event::LineStbd),
+            race.external_event(0, &event(EventType::LineStbd)),
             (Some(()), None)
         );
         assert!(matches!(race.line, Line::Stbd { .. }));
@@ -413,12 +1502,12 @@ mod tests {
         // set a new location for port
         //
         assert_eq!(
-            race.location_event(0, Some(*port), None),
+            race.location_event(0, &fix(Some(*port), None)),
             (None, None)
         );
 
         assert_eq!(
-            race.external_event(0, Event::LinePort),
+            race.external_event(0, &event(EventType::LinePort)),
             (Some(()), None)
         );
         assert!(matches!(race.line, Line::Both { .. }));
@@ -431,7 +1520,7 @@ mod tests {
         expected_cross: u8,
         expected_timestamp: u64,
     ) {
-        let (updated, timer) = race.location_event(0, Some(*boat_loc), Some(*boat_velocity));
+        let (updated, timer) = race.location_event(0, &fix(Some(*boat_loc), Some(*boat_velocity)));
         assert_eq!(Some(()), updated);
         assert_eq!(None, timer);
 
@@ -450,16 +1539,22 @@ mod tests {
     }
 
     fn bump(race: &mut Race, timestamp: u64, seconds: i32, expected_start: u64) {
+        // the timer is for the next 5-4-1-0 signal, or the gun
+        let expected_timer = [300_000, 240_000, 60_000, 0]
+            .into_iter()
+            .filter_map(|before| expected_start.checked_sub(before))
+            .find(|&signal| signal > timestamp)
+            .unwrap_or(expected_start);
 
         assert_eq!(
             race.external_event(
                 0, 
-                Event::BumpSeq {
+                &event(EventType::BumpSeq {
                     timestamp: timestamp,
                     seconds: seconds,
-                },
+                }),
             ),
-            (Some(()), Some(expected_start)),
+            (Some(()), Some(expected_timer)),
         );
 
         if let State::InSequence { start_time, .. } = race.state {
@@ -0,0 +1,89 @@
+use extreme_traits::{SnapshotReader, SnapshotWriter};
+use serde::{Deserialize, Serialize};
+
+/// A sound signal in the start sequence, and the flag that goes with it
+#[derive(Serialize, Copy, Clone, PartialEq)]
+pub enum Signal {
+    /// Class flag up
+    Warning,
+    /// Prep flag up
+    Prep,
+    /// Prep flag down, one minute to go
+    PrepDown,
+    /// Class flag down, the gun
+    Start,
+}
+
+impl Signal {
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u8(*self as u8);
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        match input.u8()? {
+            0 => Ok(Signal::Warning),
+            1 => Ok(Signal::Prep),
+            2 => Ok(Signal::PrepDown),
+            3 => Ok(Signal::Start),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The start sequence being run, by minutes before the gun
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Default)]
+pub enum Sequence {
+    /// 5-4-1-0
+    #[default]
+    FiveFourOne,
+    /// 3-2-1-0
+    ThreeTwoOne,
+    /// 10-5-0
+    TenFive,
+}
+
+impl Sequence {
+    /// Seconds before the gun of each signal, earliest first
+    pub fn signals(&self) -> &'static [(u64, Signal)] {
+        match self {
+            Sequence::FiveFourOne => &[
+                (300, Signal::Warning),
+                (240, Signal::Prep),
+                (60, Signal::PrepDown),
+                (0, Signal::Start),
+            ],
+            Sequence::ThreeTwoOne => &[
+                (180, Signal::Warning),
+                (120, Signal::Prep),
+                (60, Signal::PrepDown),
+                (0, Signal::Start),
+            ],
+            Sequence::TenFive => &[
+                (600, Signal::Warning),
+                (300, Signal::Prep),
+                (0, Signal::Start),
+            ],
+        }
+    }
+
+    /// The first signal after `now` for a gun at `start_time`, and when it is, ms
+    pub fn next_signal(&self, start_time: u64, now: u64) -> Option<(u64, Signal)> {
+        self.signals().iter().find_map(|&(seconds, signal)| {
+            let time = start_time.checked_sub(seconds * 1000)?;
+            (time > now).then_some((time, signal))
+        })
+    }
+
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u8(*self as u8);
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        match input.u8()? {
+            0 => Ok(Sequence::FiveFourOne),
+            1 => Ok(Sequence::ThreeTwoOne),
+            2 => Ok(Sequence::TenFive),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use extreme_traits::{Fix, FixMode, FixStatus};
+
+use crate::race::{Event, EventType};
+
+pub fn event(event: EventType) -> Event {
+    Event { event }
+}
+
+// velocity is (sog, cog)
+pub fn fix(location: Option<(f64, f64)>, velocity: Option<(f64, f64)>) -> Fix {
+    Fix {
+        location,
+        sog: velocity.map(|(sog, _)| sog),
+        cog: velocity.map(|(_, cog)| cog),
+        status: FixStatus::Active,
+        mode: FixMode::Autonomous,
+        ..Default::default()
+    }
+}
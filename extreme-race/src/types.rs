@@ -1,7 +1,128 @@
 use ::serde::Serialize;
+use core::f64::consts::FRAC_PI_2;
+use extreme_traits::{SnapshotReader, SnapshotWriter};
+
+use crate::geo_math::destination;
+use crate::line::R;
 
 #[derive(Serialize, Copy, Clone, PartialEq, Default)]
 pub struct Location {
     pub lat: f64,
     pub lon: f64,
 }
+
+/// Where the GPS antenna sits on the boat, in metres. The line is about the bow, so
+/// pings and time to line use the bow's position rather than the antenna's.
+#[derive(Serialize, Copy, Clone, PartialEq, Default)]
+pub struct BoatGeometry {
+    /// Antenna to the bow, along the centreline
+    pub bow: f64,
+    /// Antenna to the centreline, positive when the antenna is to starboard
+    pub beam: f64,
+}
+
+/// Quality of the most recent GPS fix, as reported to the UI
+#[derive(Serialize, Copy, Clone, PartialEq, Default)]
+pub enum FixState {
+    #[default]
+    None,
+    Void,
+    Valid,
+}
+
+/// How many fixes `RecentFixes` keeps, the whole ping window up to 6Hz
+pub const RECENT_FIXES: usize = 32;
+
+/// Course and speed of the last few fixes, for the course a wind ping takes
+#[derive(Copy, Clone, PartialEq, Default)]
+pub struct RecentFixes {
+    // (course in degrees, speed in knots, timestamp), oldest first
+    fixes: [(f64, f64, u64); RECENT_FIXES],
+    len: usize,
+}
+
+impl Location {
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.f64(self.lat);
+        out.f64(self.lon);
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        Ok(Location {
+            lat: input.f64()?,
+            lon: input.f64()?,
+        })
+    }
+}
+
+impl RecentFixes {
+    pub fn push(&mut self, course: f64, speed: f64, timestamp: u64) {
+        if self.len == RECENT_FIXES {
+            self.fixes.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.fixes[self.len] = (course, speed, timestamp);
+        self.len += 1;
+    }
+
+    /// Newest first
+    pub fn iter(&self) -> impl Iterator<Item = (f64, f64, u64)> + '_ {
+        self.fixes[..self.len].iter().rev().copied()
+    }
+
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u8(self.len as u8);
+        for &(course, speed, timestamp) in &self.fixes[..self.len] {
+            out.f64(course);
+            out.f64(speed);
+            out.u64(timestamp);
+        }
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        let mut recent = RecentFixes::default();
+        for _ in 0..input.u8()? {
+            recent.push(input.f64()?, input.f64()?, input.u64()?);
+        }
+        Ok(recent)
+    }
+}
+
+impl FixState {
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u8(*self as u8);
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        match input.u8()? {
+            0 => Ok(FixState::None),
+            1 => Ok(FixState::Void),
+            2 => Ok(FixState::Valid),
+            _ => Err(()),
+        }
+    }
+}
+
+impl BoatGeometry {
+    /// The bow's position for the antenna at `location`, on `heading` (radians)
+    pub fn bow_location(&self, location: Location, heading: f64) -> Location {
+        if *self == BoatGeometry::default() {
+            return location;
+        }
+        let (lat, lon) = destination(location.lat, location.lon, heading, self.bow, R);
+        let (lat, lon) = destination(lat, lon, heading - FRAC_PI_2, self.beam, R);
+        Location { lat, lon }
+    }
+
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.f64(self.bow);
+        out.f64(self.beam);
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        Ok(BoatGeometry {
+            bow: input.f64()?,
+            beam: input.f64()?,
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use core::f64::consts::PI;
+use libm::{atan2, cos, fmod, sin};
+
+/// A wind ping takes the mean course over this long, ms
+pub const PING_WINDOW: u64 = 5_000;
+/// Slower than this, knots, and COG is mostly noise
+pub const MIN_PING_SPEED: f64 = 1.0;
+
+/// The course for a wind ping, from `fixes` newest first as (course in degrees, speed
+/// in knots, timestamp). It's the time weighted mean over the last PING_WINDOW, each
+/// fix counting until the next one. None without a fix, or if the boat was too slow
+/// for its course to mean much.
+pub fn ping_course(fixes: impl Iterator<Item = (f64, f64, u64)>) -> Option<f64> {
+    let mut fixes = fixes.peekable();
+    let &(current_course, current_speed, timestamp) = fixes.peek()?;
+    let window_start = timestamp.saturating_sub(PING_WINDOW);
+
+    let mut sum_sin = 0.0;
+    let mut sum_cos = 0.0;
+    let mut sum_speed = 0.0;
+    let mut total_time = 0.0;
+    let mut prev_ts = timestamp;
+    for (course, speed, ts) in fixes {
+        let dt = prev_ts.saturating_sub(ts.max(window_start)) as f64 / 1000.0;
+        let course_rad = course * PI / 180.0;
+        sum_sin += sin(course_rad) * dt;
+        sum_cos += cos(course_rad) * dt;
+        sum_speed += speed * dt;
+        total_time += dt;
+        if ts < window_start {
+            break;
+        }
+        prev_ts = ts;
+    }
+
+    if total_time == 0.0 {
+        // just the one fix
+        return (current_speed >= MIN_PING_SPEED).then_some(current_course);
+    }
+    if sum_speed / total_time < MIN_PING_SPEED {
+        return None;
+    }
+    Some(fmod(atan2(sum_sin, sum_cos) * 180.0 / PI + 360.0, 360.0))
+}
@@ -2,11 +2,18 @@
 // #![feature(adt_const_params)]
 // #![feature(inline_const_pat)]
 
+mod course;
+pub use course::{ping_course, MIN_PING_SPEED, PING_WINDOW};
+
 mod selector;
 pub use selector::{EngineSelector, SelectorEvent, StringList};
 
+mod snapshot;
+pub use snapshot::{SnapshotReader, SnapshotWriter};
+
 mod traits;
 pub use crate::traits::*;
+pub use gps::{Fix, FixSource, Mode as FixMode, Status as FixStatus};
 pub use paste::paste;
 
 #[macro_export]
@@ -63,13 +70,12 @@ macro_rules! define_engines {
                 fn location_event(
                     &mut self,
                     timestamp: u64,
-                    location: Option<(f64, f64)>,
-                    speed: Option<(f64, f64)>,
+                    fix: &$crate::Fix,
                 ) -> (Option<()>, Option<u64>) {
                     match self {
-                        Self::Selector(engine) => engine.location_event(timestamp, location, speed),
+                        Self::Selector(engine) => engine.location_event(timestamp, fix),
                         $(
-                            Self::$variant(engine) => engine.location_event(timestamp, location, speed),
+                            Self::$variant(engine) => engine.location_event(timestamp, fix),
                         )*
                     }
                 }
@@ -127,9 +133,99 @@ macro_rules! define_engines {
                         )*
                     }
                 }
+
+                // the selected engine's name, so saved state survives the engine list
+                // changing, then whatever it wants to keep. Nothing at all if no engine
+                // is selected.
+                fn save(&self, buf: &mut [u8]) -> Result<usize, ()> {
+                    if let Self::Selector(_) = self {
+                        return Ok(0);
+                    }
+                    let header = self.write_name(buf)?;
+                    let rest = &mut buf[header..];
+                    let len = match self {
+                        Self::Selector(_) => 0,
+                        $(
+                            Self::$variant(engine) => $crate::RawEngine::save(engine, rest)?,
+                        )*
+                    };
+                    Ok(header + len)
+                }
+
+                fn load(&mut self, data: &[u8]) -> Result<(), ()> {
+                    if data.is_empty() {
+                        *self = Self::default();
+                        return Ok(());
+                    }
+
+                    let (mut engine, rest) = Self::read_name(data)?;
+                    match &mut engine {
+                        // saved with no engine selected, which writes nothing
+                        Self::Selector(_) => return Err(()),
+                        $(
+                            Self::$variant(engine) => $crate::RawEngine::load(engine, rest)?,
+                        )*
+                    }
+                    *self = engine;
+                    Ok(())
+                }
+
+                // the selected engine's name, then its versioned snapshot
+                fn snapshot(&self, buf: &mut [u8]) -> Result<usize, ()> {
+                    let header = self.write_name(buf)?;
+                    let rest = &mut buf[header..];
+                    let len = match self {
+                        Self::Selector(engine) => $crate::RawEngine::snapshot(engine, rest)?,
+                        $(
+                            Self::$variant(engine) => $crate::RawEngine::snapshot(engine, rest)?,
+                        )*
+                    };
+                    Ok(header + len)
+                }
+
+                fn restore(&mut self, data: &[u8]) -> Result<Option<u64>, ()> {
+                    let (mut engine, rest) = Self::read_name(data)?;
+                    let timer = match &mut engine {
+                        Self::Selector(engine) => $crate::RawEngine::restore(engine, rest)?,
+                        $(
+                            Self::$variant(engine) => $crate::RawEngine::restore(engine, rest)?,
+                        )*
+                    };
+                    *self = engine;
+                    Ok(timer)
+                }
             }
 
             impl $enum_name {
+                // the selected engine's name, empty for the selector
+                fn write_name(&self, buf: &mut [u8]) -> Result<usize, ()> {
+                    let name = match self {
+                        Self::Selector(_) => "",
+                        $(
+                            Self::$variant(_) => stringify!($variant),
+                        )*
+                    };
+                    let mut out = $crate::SnapshotWriter::new(buf);
+                    out.u8(name.len() as u8);
+                    out.bytes(name.as_bytes());
+                    out.finish()
+                }
+
+                // a default engine by the name written by `write_name`, and what
+                // follows the name. Fails for an engine this build doesn't have.
+                fn read_name(data: &[u8]) -> Result<(Self, &[u8]), ()> {
+                    let mut input = $crate::SnapshotReader::new(data);
+                    let len = input.u8()? as usize;
+                    let name = core::str::from_utf8(input.bytes(len)?).map_err(|_| ())?;
+                    let engine = if name.is_empty() {
+                        Self::default()
+                    } else {
+                        let index = <[<$enum_name Labels>] as $crate::StringList>::index_of(name).ok_or(())?;
+                        Self::from_index(index)
+                    };
+                    Ok((engine, input.rest()))
+                }
+
                 fn from_index(index: usize) -> Self {
                     match index {
                         $(
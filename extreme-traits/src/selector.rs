@@ -1,4 +1,6 @@
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
 use crate::traits::Engine;
+use gps::Fix;
 use core::fmt;
 use core::marker::PhantomData;
 use serde::de::{self, Visitor};
@@ -130,16 +132,21 @@ where
         }
     }
 
-    fn location_event(
-        &mut self,
-        _: u64,
-        _: Option<(f64, f64)>,
-        _: Option<(f64, f64)>,
-    ) -> (Option<()>, Option<u64>) {
+    fn location_event(&mut self, _: u64, _: &Fix) -> (Option<()>, Option<u64>) {
         (None, None)
     }
 
     fn timer_event(&mut self, _: u64) -> (Option<()>, Option<u64>) {
         (None, None)
     }
+
+    // nothing to keep, the selection lives in the engine type
+    const SNAPSHOT_VERSION: u8 = 1;
+
+    fn snapshot(&self, _out: &mut SnapshotWriter) {}
+
+    fn restore(&mut self, input: SnapshotReader) -> Result<Option<u64>, ()> {
+        input.finish()?;
+        Ok(Some(0))
+    }
 }
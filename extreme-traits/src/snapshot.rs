@@ -0,0 +1,124 @@
+/// Writes an engine snapshot into a fixed buffer. Values are little endian and
+/// untagged, the layout is whatever the engine's `SNAPSHOT_VERSION` says it is.
+/// Running out of room is sticky and reported by `finish`, so engines can write
+/// their state without checking every value.
+pub struct SnapshotWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    overflow: bool,
+}
+
+impl<'a> SnapshotWriter<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    pub fn bytes(&mut self, data: &[u8]) {
+        match self.buf.get_mut(self.len..self.len + data.len()) {
+            Some(dest) if !self.overflow => {
+                dest.copy_from_slice(data);
+                self.len += data.len();
+            }
+            _ => self.overflow = true,
+        }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    /// Number of bytes written, or Err if they didn't fit
+    #[allow(clippy::result_unit_err)]
+    pub fn finish(self) -> Result<usize, ()> {
+        if self.overflow {
+            Err(())
+        } else {
+            Ok(self.len)
+        }
+    }
+}
+
+/// Reads back a snapshot written by `SnapshotWriter`
+pub struct SnapshotReader<'a> {
+    data: &'a [u8],
+}
+
+#[allow(clippy::result_unit_err)]
+impl<'a> SnapshotReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], ()> {
+        if len > self.data.len() {
+            return Err(());
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ()> {
+        let (bytes, rest) = self.data.split_first_chunk::<N>().ok_or(())?;
+        self.data = rest;
+        Ok(*bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, ()> {
+        self.array::<1>().map(|[value]| value)
+    }
+
+    pub fn bool(&mut self) -> Result<bool, ()> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(()),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, ()> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, ()> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, ()> {
+        self.array().map(f64::from_le_bytes)
+    }
+
+    /// Everything the engine didn't read, the rest of a nested snapshot
+    pub fn rest(self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Err if anything is left over, the snapshot was written by a different layout
+    pub fn finish(self) -> Result<(), ()> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+}
@@ -1,20 +1,23 @@
 use core::option::Option;
 
-pub const MAX_MESSAGE_SIZE: usize = 512;
+use gps::Fix;
+
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
+/// Room for an engine's update message, and the wrapper the clients get it in
+pub const MAX_MESSAGE_SIZE: usize = 768;
+/// Room for a snapshot of any of the engines
+pub const MAX_SNAPSHOT_SIZE: usize = 2048;
 
 pub trait Engine: serde::Serialize {
     type Event<'a>: serde::Deserialize<'a>;
 
-    /// Update the location of the engine
+    /// Update the location of the engine. Fixes flagged void by the receiver are still
+    /// delivered so engines can report fix quality; check `Fix::is_valid` before use.
     /// Returns:
     /// * Some(()) if the engine state has changed, None otherwise
     /// * Some(timestamp) if a timer event is needed at `timestamp`. Some(0) will cancel any existing timer. None will result in no changes to any existing timer.
-    fn location_event(
-        &mut self,
-        timestamp: u64,
-        location: Option<(f64, f64)>,
-        speed: Option<(f64, f64)>,
-    ) -> (Option<()>, Option<u64>);
+    fn location_event(&mut self, timestamp: u64, fix: &Fix) -> (Option<()>, Option<u64>);
 
     fn external_event<'a>(
         &mut self,
@@ -26,6 +29,31 @@ pub trait Engine: serde::Serialize {
 
     /// Get a static file from the engine, if it exists
     fn get_static(&self, path: &str) -> Option<&'static [u8]>;
+
+    /// Write the state worth keeping across a reboot, like a start line. Engines with
+    /// nothing to keep write nothing. This is also the start of every snapshot.
+    fn save(&self, _out: &mut SnapshotWriter) {}
+
+    /// Read what `save` wrote into a default engine, at boot or as the first part of a
+    /// snapshot. Saved state may be hours old, so anything that depends on where the
+    /// boat is should be checked against the next fix.
+    fn load(&mut self, _input: &mut SnapshotReader) -> Result<(), ()> {
+        Ok(())
+    }
+
+    /// Layout version of `save` and `snapshot`, bump it whenever either changes in a
+    /// release. Saved state and snapshots with any other version are refused.
+    const SNAPSHOT_VERSION: u8;
+
+    /// Write the rest of the engine's state after what `save` wrote, enough for
+    /// `restore` to carry on exactly where it left off, from a replay checkpoint or on
+    /// another device
+    fn snapshot(&self, out: &mut SnapshotWriter);
+
+    /// Read what `snapshot` wrote into the engine `load` has just set up. The whole of
+    /// `input` should be consumed, use `SnapshotReader::finish`. Returns the timer the
+    /// restored state needs, Some(0) if it needs none.
+    fn restore(&mut self, input: SnapshotReader) -> Result<Option<u64>, ()>;
 }
 
 pub trait RawEngine {
@@ -37,20 +65,31 @@ pub trait RawEngine {
         event: &[u8],
     ) -> Result<(Option<heapless::Vec<u8, MAX_MESSAGE_SIZE>>, Option<u64>), ()>;
 
-    fn location_event(
-        &mut self,
-        timestamp: u64,
-        location: Option<(f64, f64)>,
-        speed: Option<(f64, f64)>,
-    ) -> (Option<()>, Option<u64>);
+    fn location_event(&mut self, timestamp: u64, fix: &Fix) -> (Option<()>, Option<u64>);
 
     fn timer_event(&mut self, timestamp: u64) -> (Option<()>, Option<u64>);
 
     /// Get a static file from the engine, if it exists
     fn get_static(&self, path: &str) -> Option<&'static [u8]>;
+
+    /// Write the versioned state worth keeping across a reboot into `buf`, returning
+    /// its length. It's the start of a snapshot, cut short.
+    fn save(&self, buf: &mut [u8]) -> Result<usize, ()>;
+
+    /// Replace the engine's state with state written by `save`, leaving it untouched
+    /// on failure
+    fn load(&mut self, data: &[u8]) -> Result<(), ()>;
+
+    /// Write a versioned snapshot of the engine's complete state into `buf`, returning
+    /// its length
+    fn snapshot(&self, buf: &mut [u8]) -> Result<usize, ()>;
+
+    /// Replace the engine's state with a snapshot, leaving it untouched on failure.
+    /// Returns the timer it needs.
+    fn restore(&mut self, data: &[u8]) -> Result<Option<u64>, ()>;
 }
 
-impl<E: Engine> RawEngine for E {
+impl<E: Engine + Default> RawEngine for E {
     fn to_vec(&self) -> Result<heapless::Vec<u8, MAX_MESSAGE_SIZE>, ()> {
         if let Ok(vec) = serde_json_core::to_vec(self) {
             Ok(vec)
@@ -80,13 +119,8 @@ impl<E: Engine> RawEngine for E {
         Ok((update, timer))
     }
 
-    fn location_event(
-        &mut self,
-        timestamp: u64,
-        location: Option<(f64, f64)>,
-        speed: Option<(f64, f64)>,
-    ) -> (Option<()>, Option<u64>) {
-        Engine::location_event(self, timestamp, location, speed)
+    fn location_event(&mut self, timestamp: u64, fix: &Fix) -> (Option<()>, Option<u64>) {
+        Engine::location_event(self, timestamp, fix)
     }
 
     fn timer_event(&mut self, timestamp: u64) -> (Option<()>, Option<u64>) {
@@ -96,4 +130,45 @@ impl<E: Engine> RawEngine for E {
     fn get_static(&self, path: &str) -> Option<&'static [u8]> {
         Engine::get_static(self, path)
     }
+
+    // the version, then what the engine saves
+    fn save(&self, buf: &mut [u8]) -> Result<usize, ()> {
+        let mut out = SnapshotWriter::new(buf);
+        out.u8(E::SNAPSHOT_VERSION);
+        Engine::save(self, &mut out);
+        out.finish()
+    }
+
+    fn load(&mut self, data: &[u8]) -> Result<(), ()> {
+        let mut input = SnapshotReader::new(data);
+        if input.u8()? != E::SNAPSHOT_VERSION {
+            return Err(());
+        }
+        let mut engine = E::default();
+        Engine::load(&mut engine, &mut input)?;
+        input.finish()?;
+        *self = engine;
+        Ok(())
+    }
+
+    // what `save` writes, then the rest of the engine's state
+    fn snapshot(&self, buf: &mut [u8]) -> Result<usize, ()> {
+        let mut out = SnapshotWriter::new(buf);
+        out.u8(E::SNAPSHOT_VERSION);
+        Engine::save(self, &mut out);
+        Engine::snapshot(self, &mut out);
+        out.finish()
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<Option<u64>, ()> {
+        let mut input = SnapshotReader::new(data);
+        if input.u8()? != E::SNAPSHOT_VERSION {
+            return Err(());
+        }
+        let mut engine = E::default();
+        Engine::load(&mut engine, &mut input)?;
+        let timer = Engine::restore(&mut engine, input)?;
+        *self = engine;
+        Ok(timer)
+    }
 }
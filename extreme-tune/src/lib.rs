@@ -1,7 +1,12 @@
 #![no_std]
 
+mod manoeuvre;
 mod tune;
 pub use tune::TuneSpeed;
 
+#[cfg(test)]
+mod manoeuvre_tests;
+#[cfg(test)]
+mod test_helpers;
 #[cfg(test)]
 mod tune_tests;
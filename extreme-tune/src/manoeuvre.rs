@@ -0,0 +1,345 @@
+use core::f64::consts::PI;
+use extreme_traits::{SnapshotReader, SnapshotWriter};
+use heapless::Deque;
+use libm::{atan2, cos, fabs, fmod, round, sin};
+use serde::{Serialize, Serializer};
+
+/// How many of the latest manoeuvres are kept
+pub const MAX_MANOEUVRES: usize = 3;
+
+// The course is this far off the steady course and the boat is turning, degrees
+const TURN_THRESHOLD: f64 = 30.0;
+// Turns smaller than this are course changes, not tacks or gybes, degrees
+const MIN_TURN: f64 = 60.0;
+// Turning slower than this for SETTLE_TIME and the boat is on its new course, degrees a second
+const SETTLED_RATE: f64 = 4.0;
+const SETTLE_TIME: u64 = 3_000;
+// Back up to this fraction of the entry speed and the speed has been rebuilt
+const REBUILT: f64 = 0.9;
+// Stop waiting for the turn to finish or the speed to come back after this, ms
+const TIMEOUT: u64 = 60_000;
+// Time constant of the steady course and speed, seconds
+const STEADY_TIME: f64 = 10.0;
+
+const KNOTS: f64 = 1852.0 / 3600.0;
+
+#[derive(Serialize, Copy, Clone, PartialEq)]
+pub enum Kind {
+    Tack,
+    Gybe,
+    /// Without a wind direction there's no telling which
+    Turn,
+}
+
+/// A tack or gybe and what it cost
+#[derive(Serialize, Copy, Clone, PartialEq)]
+pub struct Manoeuvre {
+    pub kind: Kind,
+    /// When the turn started, ms
+    pub time: u64,
+    /// Degrees turned, positive to starboard
+    pub turn: f64,
+    /// Steady speed before the turn and the slowest through it, knots
+    pub entry: f64,
+    pub min: f64,
+    /// Seconds from the start of the turn until the speed was rebuilt, None if it wasn't
+    pub rebuild: Option<f64>,
+    /// Metres behind a boat that carried on at the entry speed
+    pub loss: f64,
+}
+
+impl Manoeuvre {
+    fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u8(self.kind as u8);
+        out.u64(self.time);
+        out.f64(self.turn);
+        out.f64(self.entry);
+        out.f64(self.min);
+        out.bool(self.rebuild.is_some());
+        out.f64(self.rebuild.unwrap_or_default());
+        out.f64(self.loss);
+    }
+
+    fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        let kind = match input.u8()? {
+            0 => Kind::Tack,
+            1 => Kind::Gybe,
+            2 => Kind::Turn,
+            _ => return Err(()),
+        };
+        Ok(Manoeuvre {
+            kind,
+            time: input.u64()?,
+            turn: input.f64()?,
+            entry: input.f64()?,
+            min: input.f64()?,
+            rebuild: match (input.bool()?, input.f64()?) {
+                (true, rebuild) => Some(rebuild),
+                (false, _) => None,
+            },
+            loss: input.f64()?,
+        })
+    }
+}
+
+// A manoeuvre under way, through the turn and then while the speed builds again
+#[derive(Copy, Clone)]
+struct Turning {
+    start: u64,
+    entry_heading: f64,
+    entry_speed: f64,
+    // degrees, summed fix to fix so turns past 180 still count
+    turned: f64,
+    min_speed: f64,
+    loss: f64,
+    // when the turn rate dropped below SETTLED_RATE
+    settled_since: Option<u64>,
+    // on the new course, waiting for the speed
+    rebuilding: bool,
+}
+
+impl Turning {
+    fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.u64(self.start);
+        out.f64(self.entry_heading);
+        out.f64(self.entry_speed);
+        out.f64(self.turned);
+        out.f64(self.min_speed);
+        out.f64(self.loss);
+        out.bool(self.settled_since.is_some());
+        out.u64(self.settled_since.unwrap_or_default());
+        out.bool(self.rebuilding);
+    }
+
+    fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        Ok(Turning {
+            start: input.u64()?,
+            entry_heading: input.f64()?,
+            entry_speed: input.f64()?,
+            turned: input.f64()?,
+            min_speed: input.f64()?,
+            loss: input.f64()?,
+            settled_since: match (input.bool()?, input.u64()?) {
+                (true, since) => Some(since),
+                (false, _) => None,
+            },
+            rebuilding: input.bool()?,
+        })
+    }
+}
+
+/// Picks tacks and gybes out of changes in COG and measures the speed they cost
+#[derive(Default)]
+pub struct Manoeuvres {
+    /// Direction the wind is from, degrees, to tell tacks from gybes
+    pub wind: Option<f64>,
+    /// Newest last
+    pub history: Deque<Manoeuvre, MAX_MANOEUVRES>,
+
+    // low pass filtered course, as a unit vector, and speed
+    steady: Option<(f64, f64, f64)>,
+    turning: Option<Turning>,
+    // timestamp and course of the last fix
+    last: Option<(u64, f64)>,
+}
+
+impl Manoeuvres {
+    /// Feed a fix, Some when it completed a manoeuvre
+    pub fn update(&mut self, timestamp: u64, speed: f64, heading: f64) -> Option<()> {
+        let (dt, step) = match self.last {
+            Some((last_ts, last_heading)) if timestamp > last_ts => (
+                (timestamp - last_ts) as f64 / 1000.0,
+                angle_diff(heading, last_heading),
+            ),
+            _ => (0.0, 0.0),
+        };
+        let previous = self.last.map_or(timestamp, |(last_ts, _)| last_ts);
+        self.last = Some((timestamp, heading));
+
+        let Some(turning) = self.turning.as_mut() else {
+            self.sail_steady(previous, speed, heading, dt);
+            return None;
+        };
+
+        turning.min_speed = turning.min_speed.min(speed);
+        turning.loss += (turning.entry_speed - speed) * KNOTS * dt;
+
+        if !turning.rebuilding {
+            turning.turned += step;
+            if dt > 0.0 && fabs(step) / dt < SETTLED_RATE {
+                turning.settled_since.get_or_insert(timestamp);
+            } else if dt > 0.0 {
+                turning.settled_since = None;
+            }
+            let settled = turning
+                .settled_since
+                .is_some_and(|since| timestamp.saturating_sub(since) >= SETTLE_TIME);
+            if settled && fabs(turning.turned) < MIN_TURN {
+                // just bearing away or coming up
+                self.reset(speed, heading);
+                return None;
+            }
+            turning.rebuilding = settled;
+        }
+
+        let elapsed = timestamp.saturating_sub(turning.start);
+        if turning.rebuilding && speed >= turning.entry_speed * REBUILT {
+            self.record(Some(elapsed), speed, heading);
+            return Some(());
+        }
+        if elapsed >= TIMEOUT {
+            if !turning.rebuilding {
+                // going round in circles
+                self.reset(speed, heading);
+                return None;
+            }
+            self.record(None, speed, heading);
+            return Some(());
+        }
+        None
+    }
+
+    fn sail_steady(&mut self, previous: u64, speed: f64, heading: f64, dt: f64) {
+        let Some((sin_mean, cos_mean, mean_speed)) = self.steady else {
+            self.reset(speed, heading);
+            return;
+        };
+        let steady_heading = atan2(sin_mean, cos_mean) * 180.0 / PI;
+        let off = angle_diff(heading, steady_heading);
+        if fabs(off) > TURN_THRESHOLD {
+            self.turning = Some(Turning {
+                start: previous,
+                entry_heading: steady_heading,
+                entry_speed: mean_speed,
+                turned: off,
+                min_speed: speed,
+                loss: (mean_speed - speed) * KNOTS * dt,
+                settled_since: None,
+                rebuilding: false,
+            });
+            return;
+        }
+
+        let alpha = (dt / STEADY_TIME).min(1.0);
+        let heading_rad = heading * PI / 180.0;
+        self.steady = Some((
+            sin_mean + (sin(heading_rad) - sin_mean) * alpha,
+            cos_mean + (cos(heading_rad) - cos_mean) * alpha,
+            mean_speed + (speed - mean_speed) * alpha,
+        ));
+    }
+
+    fn record(&mut self, rebuild: Option<u64>, speed: f64, heading: f64) {
+        let Some(turning) = self.turning else {
+            return;
+        };
+        // a tack turns through the wind, a gybe through dead downwind
+        let middle = turning.entry_heading + turning.turned / 2.0;
+        let kind = match self.wind {
+            Some(wind) if fabs(angle_diff(middle, wind)) < 90.0 => Kind::Tack,
+            Some(_) => Kind::Gybe,
+            None => Kind::Turn,
+        };
+
+        if self.history.is_full() {
+            self.history.pop_front();
+        }
+        self.history
+            .push_back(Manoeuvre {
+                kind,
+                time: turning.start,
+                turn: round(turning.turned),
+                entry: round_to(turning.entry_speed, 100.0),
+                min: round_to(turning.min_speed, 100.0),
+                rebuild: rebuild.map(|ms| round_to(ms as f64 / 1000.0, 10.0)),
+                loss: round_to(turning.loss, 10.0),
+            })
+            .ok();
+        self.reset(speed, heading);
+    }
+
+    // steady on the current course and speed
+    fn reset(&mut self, speed: f64, heading: f64) {
+        let heading_rad = heading * PI / 180.0;
+        self.steady = Some((sin(heading_rad), cos(heading_rad), speed));
+        self.turning = None;
+    }
+
+    pub fn clear(&mut self) -> Option<()> {
+        if self.history.is_empty() {
+            return None;
+        }
+        self.history.clear();
+        Some(())
+    }
+
+    pub fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.bool(self.wind.is_some());
+        out.f64(self.wind.unwrap_or_default());
+        out.u8(self.history.len() as u8);
+        for manoeuvre in self.history.iter() {
+            manoeuvre.snapshot(out);
+        }
+        out.bool(self.steady.is_some());
+        let (sin_mean, cos_mean, mean_speed) = self.steady.unwrap_or_default();
+        out.f64(sin_mean);
+        out.f64(cos_mean);
+        out.f64(mean_speed);
+        out.bool(self.turning.is_some());
+        if let Some(turning) = &self.turning {
+            turning.snapshot(out);
+        }
+        out.bool(self.last.is_some());
+        let (last_ts, last_heading) = self.last.unwrap_or_default();
+        out.u64(last_ts);
+        out.f64(last_heading);
+    }
+
+    pub fn restore(input: &mut SnapshotReader) -> Result<Self, ()> {
+        let mut manoeuvres = Manoeuvres {
+            wind: match (input.bool()?, input.f64()?) {
+                (true, wind) => Some(wind),
+                (false, _) => None,
+            },
+            ..Default::default()
+        };
+        for _ in 0..input.u8()? {
+            let manoeuvre = Manoeuvre::restore(input)?;
+            if manoeuvres.history.is_full() {
+                manoeuvres.history.pop_front();
+            }
+            manoeuvres.history.push_back(manoeuvre).ok();
+        }
+        manoeuvres.steady = match (input.bool()?, input.f64()?, input.f64()?, input.f64()?) {
+            (true, sin_mean, cos_mean, mean_speed) => Some((sin_mean, cos_mean, mean_speed)),
+            (false, ..) => None,
+        };
+        if input.bool()? {
+            manoeuvres.turning = Some(Turning::restore(input)?);
+        }
+        manoeuvres.last = match (input.bool()?, input.u64()?, input.f64()?) {
+            (true, last_ts, last_heading) => Some((last_ts, last_heading)),
+            (false, ..) => None,
+        };
+        Ok(manoeuvres)
+    }
+}
+
+// serialized as just the list, oldest first
+impl Serialize for Manoeuvres {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.history.iter())
+    }
+}
+
+// `a - b` in degrees, normalised to [-180, 180)
+fn angle_diff(a: f64, b: f64) -> f64 {
+    fmod(fmod(a - b + 180.0, 360.0) + 360.0, 360.0) - 180.0
+}
+
+pub(crate) fn round_to(value: f64, scale: f64) -> f64 {
+    round(value * scale) / scale
+}
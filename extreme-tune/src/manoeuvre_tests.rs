@@ -0,0 +1,284 @@
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::manoeuvre::{Kind, Manoeuvres, MAX_MANOEUVRES};
+    use crate::test_helpers::{event, fix};
+    use crate::tune::EventType;
+    use crate::TuneSpeed;
+    use extreme_traits::Engine;
+
+    #[test]
+    fn test_tack() {
+        let mut manoeuvres = Manoeuvres::default();
+        manoeuvres.wind = Some(0.0);
+        let end = tack(&mut manoeuvres, 0, 45.0, -15.0);
+
+        assert_eq!(manoeuvres.history.len(), 1);
+        let tack = *manoeuvres.history.front().unwrap();
+        assert!(tack.kind == Kind::Tack);
+        assert_eq!(tack.time, 22_000);
+        assert!((-95.0..=-80.0).contains(&tack.turn));
+        assert!(tack.entry > 5.8 && tack.entry <= 6.0);
+        assert_eq!(tack.min, 3.0);
+        assert_eq!(tack.rebuild, Some(9.0));
+        assert!(tack.loss > 5.0 && tack.loss < 15.0);
+
+        // steady again on the new course
+        sail(&mut manoeuvres, end, end + 20_000, 315.0);
+        assert_eq!(manoeuvres.history.len(), 1);
+    }
+
+    #[test]
+    fn test_gybe() {
+        let mut manoeuvres = Manoeuvres::default();
+        manoeuvres.wind = Some(0.0);
+        tack(&mut manoeuvres, 0, 135.0, 15.0);
+
+        let gybe = *manoeuvres.history.front().unwrap();
+        assert!(gybe.kind == Kind::Gybe);
+        assert!((80.0..=95.0).contains(&gybe.turn));
+    }
+
+    #[test]
+    fn test_no_wind() {
+        let mut manoeuvres = Manoeuvres::default();
+        tack(&mut manoeuvres, 0, 45.0, -15.0);
+        assert!(manoeuvres.history.front().unwrap().kind == Kind::Turn);
+    }
+
+    #[test]
+    fn test_course_change() {
+        let mut manoeuvres = Manoeuvres::default();
+        sail(&mut manoeuvres, 0, 20_000, 45.0);
+
+        // bear away 40 degrees, too little for a tack or gybe
+        for (i, heading) in [55.0, 65.0, 75.0, 85.0].iter().enumerate() {
+            manoeuvres.update(21_000 + i as u64 * 1000, 6.0, *heading);
+        }
+        sail(&mut manoeuvres, 25_000, 60_000, 85.0);
+        assert!(manoeuvres.history.is_empty());
+
+        // and the next tack is measured from the new course
+        tack(&mut manoeuvres, 60_000, 85.0, -15.0);
+        assert_eq!(manoeuvres.history.len(), 1);
+        assert!((-100.0..=-85.0).contains(&manoeuvres.history.front().unwrap().turn));
+    }
+
+    #[test]
+    fn test_no_rebuild() {
+        let mut manoeuvres = Manoeuvres::default();
+        sail(&mut manoeuvres, 0, 20_000, 45.0);
+        for (i, heading) in [30.0, 15.0, 0.0, 345.0, 330.0, 315.0].iter().enumerate() {
+            manoeuvres.update(21_000 + i as u64 * 1000, 3.0, *heading);
+        }
+        // wallowing at half speed
+        for t in 27..90 {
+            manoeuvres.update(t * 1000, 3.0, 315.0);
+        }
+
+        assert_eq!(manoeuvres.history.len(), 1);
+        let tack = *manoeuvres.history.front().unwrap();
+        assert!(tack.rebuild.is_none());
+        assert_eq!(tack.min, 3.0);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut manoeuvres = Manoeuvres::default();
+        let mut start = 0;
+        let mut heading = 45.0;
+        for _ in 0..MAX_MANOEUVRES + 2 {
+            let step = if heading == 45.0 { -15.0 } else { 15.0 };
+            start = tack(&mut manoeuvres, start, heading, step);
+            heading = if heading == 45.0 { 315.0 } else { 45.0 };
+        }
+
+        // the oldest have gone
+        assert_eq!(manoeuvres.history.len(), MAX_MANOEUVRES);
+        assert_eq!(
+            manoeuvres.history.front().unwrap().time,
+            2 * 36_000 + 22_000
+        );
+        assert!(manoeuvres.history.iter().all(|m| m.rebuild.is_some()));
+
+        assert_eq!(manoeuvres.clear(), Some(()));
+        assert_eq!(manoeuvres.clear(), None);
+    }
+
+    #[test]
+    fn test_events() {
+        let mut tune = TuneSpeed::<32>::default();
+        assert_eq!(
+            tune.external_event(0, &event(EventType::WindPing)),
+            (None, None)
+        );
+        assert_eq!(
+            tune.external_event(0, &event(EventType::Wind { direction: 400.0 })),
+            (None, None)
+        );
+        assert_eq!(
+            tune.external_event(0, &event(EventType::Wind { direction: 10.0 })),
+            (Some(()), None)
+        );
+        assert_eq!(tune.manoeuvres.wind, Some(10.0));
+
+        // head to wind
+        tune.location_event(0, &fix(6.0, 350.0));
+        assert_eq!(
+            tune.external_event(0, &event(EventType::WindPing)),
+            (Some(()), None)
+        );
+        assert_eq!(tune.manoeuvres.wind, Some(350.0));
+
+        assert_eq!(
+            tune.external_event(0, &event(EventType::ClearManoeuvres)),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn test_wind_ping() {
+        // luffing head to wind, COG wanders about at half a knot
+        let mut tune = TuneSpeed::<32>::default();
+        for (i, heading) in [350.0, 20.0, 300.0, 90.0, 330.0, 40.0].iter().enumerate() {
+            tune.location_event(i as u64 * 1000, &fix(0.5, *heading));
+        }
+        assert_eq!(
+            tune.external_event(6_000, &event(EventType::WindPing)),
+            (None, None)
+        );
+        assert_eq!(tune.manoeuvres.wind, None);
+
+        // still moving, the noise averages out and one stray fix doesn't count for much
+        for (i, heading) in [345.0, 355.0, 340.0, 0.0, 350.0, 20.0].iter().enumerate() {
+            tune.location_event(10_000 + i as u64 * 1000, &fix(2.0, *heading));
+        }
+        assert_eq!(
+            tune.external_event(16_000, &event(EventType::WindPing)),
+            (Some(()), None)
+        );
+        let wind = tune.manoeuvres.wind.unwrap();
+        assert!((wind - 350.0).abs() < 5.0, "{}", wind);
+    }
+
+    #[test]
+    fn test_tune_speed() {
+        let mut tune = TuneSpeed::<32>::default();
+        tune.manoeuvres.wind = Some(0.0);
+        let mut timestamp = 0;
+        let mut heading = 45.0;
+        for _ in 0..MAX_MANOEUVRES {
+            let step = if heading == 45.0 { -15.0 } else { 15.0 };
+            timestamp = tack_tune(&mut tune, timestamp, heading, step);
+            heading = if heading == 45.0 { 315.0 } else { 45.0 };
+        }
+        assert_eq!(tune.manoeuvres.history.len(), MAX_MANOEUVRES);
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&extreme_traits::RawEngine::to_vec(&tune).unwrap()).unwrap();
+        let manoeuvres = json["manoeuvres"].as_array().unwrap();
+        assert_eq!(manoeuvres.len(), MAX_MANOEUVRES);
+        assert_eq!(manoeuvres[0]["kind"], "Tack");
+        assert_eq!(json["wind"], 0.0);
+
+        // a snapshot halfway through a tack carries on the same
+        tune.location_event(timestamp, &fix(6.0, 45.0));
+        tune.location_event(timestamp + 1000, &fix(5.0, 15.0));
+        tune.location_event(timestamp + 2000, &fix(4.0, 0.0));
+        let mut buf = [0u8; 2048];
+        let len = extreme_traits::RawEngine::snapshot(&tune, &mut buf).unwrap();
+        let mut restored = TuneSpeed::<32>::default();
+        assert!(extreme_traits::RawEngine::restore(&mut restored, &buf[..len]).is_ok());
+        tack_tune(&mut restored, timestamp + 3000, 345.0, -15.0);
+        tack_tune(&mut tune, timestamp + 3000, 345.0, -15.0);
+        assert!(restored
+            .manoeuvres
+            .history
+            .iter()
+            .eq(tune.manoeuvres.history.iter()));
+        assert!(extreme_traits::RawEngine::restore(&mut restored, &buf[..len - 1]).is_err());
+    }
+
+    #[test]
+    fn test_message_rounding() {
+        let mut tune = TuneSpeed::<32>::default();
+        tune.speed = -12.345678901234567;
+        tune.speed_dev = -12.345678901234567;
+        tune.heading_dev = -123.45678901234567;
+        tune.manoeuvres.wind = Some(123.45678901234567);
+        for i in 0..MAX_MANOEUVRES {
+            tune.manoeuvres
+                .history
+                .push_back(crate::manoeuvre::Manoeuvre {
+                    kind: Kind::Turn,
+                    time: 1_710_383_700_000 + i as u64,
+                    turn: -180.0,
+                    entry: 12.34,
+                    min: 10.01,
+                    rebuild: Some(59.9),
+                    loss: -123.4,
+                })
+                .ok();
+        }
+        let json: serde_json::Value =
+            serde_json::from_slice(&extreme_traits::RawEngine::to_vec(&tune).unwrap()).unwrap();
+        assert_eq!(json["speed"], -12.35);
+        assert_eq!(json["speed_dev"], -12.35);
+        assert_eq!(json["heading_dev"], -123.5);
+        assert_eq!(json["wind"], 123.5);
+        assert_eq!(json["manoeuvres"].as_array().unwrap().len(), MAX_MANOEUVRES);
+    }
+
+    // 20 seconds steady on `from` at 6 knots, turn 90 degrees in `step`s slowing to
+    // 3 knots, and build back up. Returns the time of the last fix.
+    fn tack(manoeuvres: &mut Manoeuvres, start: u64, from: f64, step: f64) -> u64 {
+        let mut timestamp = start;
+        let mut fixes = std::vec::Vec::new();
+        sail_fixes(&mut fixes, &mut timestamp, from, step);
+        for (timestamp, speed, heading) in fixes {
+            manoeuvres.update(timestamp, speed, heading);
+        }
+        timestamp
+    }
+
+    fn tack_tune(tune: &mut TuneSpeed<32>, start: u64, from: f64, step: f64) -> u64 {
+        let mut timestamp = start;
+        let mut fixes = std::vec::Vec::new();
+        sail_fixes(&mut fixes, &mut timestamp, from, step);
+        for (timestamp, speed, heading) in fixes {
+            tune.location_event(timestamp, &fix(speed, heading));
+        }
+        timestamp
+    }
+
+    fn sail_fixes(
+        fixes: &mut std::vec::Vec<(u64, f64, f64)>,
+        timestamp: &mut u64,
+        from: f64,
+        step: f64,
+    ) {
+        let start = *timestamp;
+        for t in 0..=20 {
+            fixes.push((start + t * 1000, 6.0, from));
+        }
+        let mut heading = from;
+        for i in 1..=6 {
+            heading = (heading + step + 360.0) % 360.0;
+            fixes.push((start + (20 + i) * 1000, 6.0 - 0.5 * i as f64, heading));
+        }
+        for i in 1..=10 {
+            fixes.push((
+                start + (26 + i) * 1000,
+                (3.0 + 0.5 * i as f64).min(6.0),
+                heading,
+            ));
+        }
+        *timestamp = start + 36_000;
+    }
+
+    fn sail(manoeuvres: &mut Manoeuvres, from: u64, to: u64, heading: f64) {
+        for timestamp in (from..=to).step_by(1000) {
+            manoeuvres.update(timestamp, 6.0, heading);
+        }
+    }
+}
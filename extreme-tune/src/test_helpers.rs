@@ -0,0 +1,17 @@
+use extreme_traits::{Fix, FixMode, FixStatus};
+
+use crate::tune::{Event, EventType};
+
+pub fn event(event: EventType) -> Event {
+    Event { event }
+}
+
+pub fn fix(speed: f64, heading: f64) -> Fix {
+    Fix {
+        sog: Some(speed),
+        cog: Some(heading),
+        status: FixStatus::Active,
+        mode: FixMode::Autonomous,
+        ..Default::default()
+    }
+}
@@ -1,17 +1,36 @@
 use core::f64::consts::PI;
-use extreme_traits::Engine;
+use extreme_traits::{ping_course, Engine, Fix, SnapshotReader, SnapshotWriter};
 use heapless::Deque;
 use libm::{atan2, cos, fmod, sin};
-use serde::{ser::SerializeStruct, Serialize, Serializer};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
+use crate::manoeuvre::{round_to, Manoeuvres};
 
 include!(concat!(env!("OUT_DIR"), "/static_files.rs"));
 
+#[derive(Deserialize)]
+pub enum EventType {
+    // direction the wind is from, degrees
+    Wind { direction: f64 },
+    // the boat is head to wind, take the wind direction from its last few seconds of course
+    WindPing,
+    ClearManoeuvres,
+}
+
+// Note: we use a struct to deserialize because serde
+// can't use tag= (to flatten) with no_std
+#[derive(Deserialize)]
+pub struct Event {
+    pub event: EventType,
+}
+
 #[derive(Default)]
 pub struct TuneSpeed<const HISTORY_SIZE: usize> {
     // Public state variables
     pub speed: f64,
     pub speed_dev: f64,
     pub heading_dev: f64,
+    pub manoeuvres: Manoeuvres,
 
     // Internal state variables (not serialized)
     speed_history: Deque<(f64, u64), HISTORY_SIZE>, // (speed, timestamp)
@@ -20,8 +39,7 @@ pub struct TuneSpeed<const HISTORY_SIZE: usize> {
 }
 
 impl<const HISTORY_SIZE: usize> Engine for TuneSpeed<HISTORY_SIZE> {
-    // we don't need events right now
-    type Event<'a> = ();
+    type Event<'a> = Event;
 
     fn get_static(&self, path: &'_ str) -> Option<&'static [u8]> {
         for &(k, v) in STATIC_FILES.iter() {
@@ -32,13 +50,12 @@ impl<const HISTORY_SIZE: usize> Engine for TuneSpeed<HISTORY_SIZE> {
         return None;
     }
 
-    fn location_event(
-        &mut self,
-        timestamp: u64,
-        _location: Option<(f64, f64)>,
-        speed_heading: Option<(f64, f64)>,
-    ) -> (Option<()>, Option<u64>) {
-        if let Some((current_speed, current_heading)) = speed_heading {
+    fn location_event(&mut self, timestamp: u64, fix: &Fix) -> (Option<()>, Option<u64>) {
+        if !fix.is_valid() {
+            return (None, None);
+        }
+
+        if let Some((current_speed, current_heading)) = fix.velocity() {
             // Update speed history
             if let Some(last_ts) = self.last_timestamp {
                 let delta_time = timestamp - last_ts;
@@ -119,6 +136,8 @@ impl<const HISTORY_SIZE: usize> Engine for TuneSpeed<HISTORY_SIZE> {
                     heading_deviation = fmod(heading_deviation + 180.0, 360.0) - 180.0;
                     self.heading_dev = heading_deviation;
 
+                    self.manoeuvres
+                        .update(timestamp, current_speed, current_heading);
                     self.last_timestamp = Some(timestamp);
 
                     return (Some(()), None);
@@ -134,6 +153,8 @@ impl<const HISTORY_SIZE: usize> Engine for TuneSpeed<HISTORY_SIZE> {
                 self.speed = current_speed;
                 self.speed_dev = 0.0;
                 self.heading_dev = 0.0;
+                self.manoeuvres
+                    .update(timestamp, current_speed, current_heading);
                 self.last_timestamp = Some(timestamp);
                 return (Some(()), None);
             }
@@ -145,16 +166,83 @@ impl<const HISTORY_SIZE: usize> Engine for TuneSpeed<HISTORY_SIZE> {
     fn external_event<'a>(
         &mut self,
         _timestamp: u64,
-        _event: &Self::Event<'a>,
+        event: &Self::Event<'a>,
     ) -> (Option<()>, Option<u64>) {
-        // No external events to handle
-        (None, None)
+        match event.event {
+            EventType::Wind { direction } => {
+                if !(0.0..=360.0).contains(&direction) {
+                    return (None, None);
+                }
+                self.manoeuvres.wind = Some(direction);
+                (Some(()), None)
+            }
+            EventType::WindPing => {
+                let history = self.heading_history.iter().zip(self.speed_history.iter());
+                let fixes = history
+                    .rev()
+                    .map(|(&(heading, ts), &(speed, _))| (heading, speed, ts));
+                match ping_course(fixes) {
+                    Some(heading) => {
+                        self.manoeuvres.wind = Some(heading);
+                        (Some(()), None)
+                    }
+                    None => (None, None),
+                }
+            }
+            EventType::ClearManoeuvres => (self.manoeuvres.clear(), None),
+        }
     }
 
     fn timer_event(&mut self, _timestamp: u64) -> (Option<()>, Option<u64>) {
         // No timer events needed
         (None, None)
     }
+
+    const SNAPSHOT_VERSION: u8 = 1;
+
+    // histories are written oldest first, as a count and (value, timestamp) pairs
+    fn snapshot(&self, out: &mut SnapshotWriter) {
+        out.f64(self.speed);
+        out.f64(self.speed_dev);
+        out.f64(self.heading_dev);
+        for history in [&self.speed_history, &self.heading_history] {
+            out.u16(history.len() as u16);
+            for &(value, timestamp) in history.iter() {
+                out.f64(value);
+                out.u64(timestamp);
+            }
+        }
+        out.bool(self.last_timestamp.is_some());
+        out.u64(self.last_timestamp.unwrap_or_default());
+        self.manoeuvres.snapshot(out);
+    }
+
+    fn restore(&mut self, mut input: SnapshotReader) -> Result<Option<u64>, ()> {
+        let mut tune = Self {
+            speed: input.f64()?,
+            speed_dev: input.f64()?,
+            heading_dev: input.f64()?,
+            ..Default::default()
+        };
+        for history in [&mut tune.speed_history, &mut tune.heading_history] {
+            for _ in 0..input.u16()? {
+                // from a device with a longer history, keep the newest
+                if history.is_full() {
+                    history.pop_front();
+                }
+                history.push_back((input.f64()?, input.u64()?)).ok();
+            }
+        }
+        tune.last_timestamp = match (input.bool()?, input.u64()?) {
+            (true, timestamp) => Some(timestamp),
+            (false, _) => None,
+        };
+        tune.manoeuvres = Manoeuvres::restore(&mut input)?;
+        input.finish()?;
+
+        *self = tune;
+        Ok(Some(0))
+    }
 }
 
 impl<const HISTORY_SIZE: usize> Serialize for TuneSpeed<HISTORY_SIZE> {
@@ -162,10 +250,16 @@ impl<const HISTORY_SIZE: usize> Serialize for TuneSpeed<HISTORY_SIZE> {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("TuneSpeed", 3)?;
-        state.serialize_field("speed", &self.speed)?;
-        state.serialize_field("speed_dev", &self.speed_dev)?;
-        state.serialize_field("heading_dev", &self.heading_dev)?;
+        let mut state = serializer.serialize_struct("TuneSpeed", 5)?;
+        state.serialize_field("speed", &round_to(self.speed, 100.0))?;
+        state.serialize_field("speed_dev", &round_to(self.speed_dev, 100.0))?;
+        state.serialize_field("heading_dev", &round_to(self.heading_dev, 10.0))?;
+        if let Some(wind) = self.manoeuvres.wind {
+            state.serialize_field("wind", &round_to(wind, 10.0))?;
+        }
+        if !self.manoeuvres.history.is_empty() {
+            state.serialize_field("manoeuvres", &self.manoeuvres)?;
+        }
         state.end()
     }
 }
@@ -0,0 +1,45 @@
+use crate::nmea::{Mode, Status};
+
+/// Where a fix came from
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum FixSource {
+    #[default]
+    Unknown,
+    Nmea,
+    Ubx,
+}
+
+/// A single position report from the receiver, independent of protocol
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Fix {
+    /// UTC time of the fix, ms since the unix epoch
+    pub time: Option<u64>,
+    /// (latitude, longitude) in degrees
+    pub location: Option<(f64, f64)>,
+    /// Speed over ground in knots
+    pub sog: Option<f64>,
+    /// Course over ground in degrees true
+    pub cog: Option<f64>,
+    pub status: Status,
+    pub mode: Mode,
+    /// Horizontal dilution of precision, when the receiver reports it
+    pub hdop: Option<f64>,
+    /// Estimated horizontal accuracy in metres, when the receiver reports it
+    pub accuracy: Option<f64>,
+    pub source: FixSource,
+}
+
+impl Fix {
+    /// (speed, course) when both are known
+    pub fn velocity(&self) -> Option<(f64, f64)> {
+        match (self.sog, self.cog) {
+            (Some(sog), Some(cog)) => Some((sog, cog)),
+            _ => None,
+        }
+    }
+
+    /// False if the receiver has flagged the fix as void or not valid
+    pub fn is_valid(&self) -> bool {
+        !matches!(self.status, Status::Void) && !matches!(self.mode, Mode::NotValid)
+    }
+}
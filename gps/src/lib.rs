@@ -0,0 +1,31 @@
+#![no_std]
+
+mod fix;
+mod nmea;
+mod pmtk;
+mod reader;
+mod receiver;
+mod time;
+mod ubx;
+pub use fix::{Fix, FixSource};
+pub use nmea::{
+    next_message, next_update, FixQuality, FixType, Mode, NMEAMessage, NMEAState, NMEAStats,
+    Satellite, Status, GNGGA, GNGSA, GNGSV, GNRMC, GNVTG,
+};
+pub use pmtk::PMTKCommand;
+pub use reader::{AsyncReader, RingBuffer, Tokeniser};
+pub use receiver::{ReceiverConfig, MAX_RATE_MS, MIN_RATE_MS};
+pub use time::{days_from_civil, days_in_month, epoch_ms, is_leap_year};
+pub use ubx::{
+    next_ubx_message, next_ubx_update, PowerMode, UBXCommand, UBXMessage, UBXReader, UBXStats,
+    NAVPVT,
+};
+
+#[cfg(test)]
+mod nmea_tests;
+#[cfg(test)]
+mod receiver_tests;
+#[cfg(test)]
+mod time_tests;
+#[cfg(test)]
+mod ubx_tests;
@@ -0,0 +1,575 @@
+use core::ops::RangeInclusive;
+use core::str::FromStr;
+
+use crate::fix::{Fix, FixSource};
+use crate::reader::Tokeniser;
+use crate::time::days_from_civil;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Active,
+    Void,
+    #[default]
+    Unknown,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Autonomous,
+    Differential,
+    Estimated,
+    NotValid,
+    #[default]
+    Unknown,
+}
+
+#[derive(Default, Debug)]
+pub struct GNRMC {
+    pub utc_time: Option<u32>,
+    pub status: Option<Status>,
+    pub latitude: Option<f64>,
+    pub ns_indicator: Option<char>,
+    pub longitude: Option<f64>,
+    pub ew_indicator: Option<char>,
+    pub speed_over_ground: Option<f64>,
+    pub course_over_ground: Option<f64>,
+    pub date: Option<u32>,
+    pub magnetic_variation: Option<f64>,
+    pub ew_indicator_mag: Option<char>,
+    pub mode: Option<Mode>,
+}
+
+/// GGA fix quality indicator
+#[derive(Debug, PartialEq)]
+pub enum FixQuality {
+    Invalid,
+    Gps,
+    Differential,
+    Pps,
+    RealTimeKinematic,
+    FloatRtk,
+    DeadReckoning,
+    Manual,
+    Simulation,
+    Unknown,
+}
+
+/// GSA fix type
+#[derive(Debug, PartialEq)]
+pub enum FixType {
+    NoFix,
+    Fix2D,
+    Fix3D,
+    Unknown,
+}
+
+#[derive(Default, Debug)]
+pub struct GNGGA {
+    pub utc_time: Option<u32>,
+    pub latitude: Option<f64>,
+    pub ns_indicator: Option<char>,
+    pub longitude: Option<f64>,
+    pub ew_indicator: Option<char>,
+    pub fix_quality: Option<FixQuality>,
+    pub satellites: Option<u8>,
+    pub hdop: Option<f64>,
+    pub altitude: Option<f64>,
+    pub geoid_separation: Option<f64>,
+    pub differential_age: Option<f64>,
+    pub differential_station: Option<u16>,
+}
+
+#[derive(Default, Debug)]
+pub struct GNVTG {
+    pub course_true: Option<f64>,
+    pub course_magnetic: Option<f64>,
+    pub speed_knots: Option<f64>,
+    pub speed_kph: Option<f64>,
+    pub mode: Option<Mode>,
+}
+
+#[derive(Default, Debug)]
+pub struct GNGSA {
+    pub selection_mode: Option<char>,
+    pub fix_type: Option<FixType>,
+    /// Number of satellites used in the solution
+    pub satellites: u8,
+    pub pdop: Option<f64>,
+    pub hdop: Option<f64>,
+    pub vdop: Option<f64>,
+    pub system_id: Option<u8>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Satellite {
+    pub prn: Option<u16>,
+    pub elevation: Option<u8>,
+    pub azimuth: Option<u16>,
+    pub snr: Option<u8>,
+}
+
+#[derive(Default, Debug)]
+pub struct GNGSV {
+    pub total_messages: Option<u8>,
+    pub message_number: Option<u8>,
+    pub satellites_in_view: Option<u8>,
+    /// Up to four satellites per sentence
+    pub satellites: [Option<Satellite>; 4],
+    pub signal_id: Option<u8>,
+}
+
+pub enum NMEAMessage {
+    GNRMC(GNRMC),
+    GNGGA(GNGGA),
+    GNVTG(GNVTG),
+    GNGSA(GNGSA),
+    GNGSV(GNGSV),
+    Unknown,
+}
+
+// ddmmyy to whole days since 1970-01-01, two digit years are taken as 20yy
+fn parse_date(token: &str) -> Result<Option<u32>, ()> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+    if token.len() != 6 || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(());
+    }
+
+    let day = token[0..2].parse::<u32>().map_err(|_| ())?;
+    let month = token[2..4].parse::<u32>().map_err(|_| ())?;
+    let year = token[4..6].parse::<i32>().map_err(|_| ())?;
+    let days = days_from_civil(2000 + year, month, day)?;
+    u32::try_from(days).map(Some).map_err(|_| ())
+}
+
+// RMC has 11 fields prior to NMEA 2.3, 12 with the mode indicator and 13 with the 4.1 nav status
+const RMC_FIELDS: RangeInclusive<usize> = 11..=13;
+const GGA_FIELDS: RangeInclusive<usize> = 14..=14;
+// VTG gains the mode indicator in 2.3
+const VTG_FIELDS: RangeInclusive<usize> = 8..=9;
+// GSA gains the system ID in 4.1
+const GSA_FIELDS: RangeInclusive<usize> = 17..=18;
+// GSV has a 3 field header, up to 4 satellites of 4 fields, and the 4.1 signal ID
+const GSV_FIELDS: RangeInclusive<usize> = 3..=20;
+
+/// Carried between calls to `next_update`
+#[derive(Default, Debug)]
+pub struct NMEAState {
+    pub stats: NMEAStats,
+    // from the latest GGA since the last RMC, taken by the next RMC. Receivers send
+    // GGA before or after RMC, so a fix's HDOP may be from the one before, but never
+    // older, and a fix gets none once GGA stops coming.
+    hdop: Option<f64>,
+}
+
+/// Sentence counters, e.g. to report link quality to the UI
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct NMEAStats {
+    /// Sentences that passed validation
+    pub sentences: u32,
+    /// Sentences rejected for a bad checksum, field count or field value
+    pub dropped: u32,
+}
+
+fn checksum(token: &str) -> u8 {
+    token.bytes().fold(0, |acc, b| acc ^ b)
+}
+
+// empty fields are valid and parse to None, anything else must parse
+fn parse_field<F: FromStr>(token: &str) -> Result<Option<F>, ()> {
+    if token.is_empty() {
+        Ok(None)
+    } else {
+        token.parse::<F>().map(Some).map_err(|_| ())
+    }
+}
+
+fn parse_time(token: &str) -> Result<Option<u32>, ()> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+
+    let hours = token.get(0..2).ok_or(())?.parse::<u32>().map_err(|_| ())?;
+    let minutes = token.get(2..4).ok_or(())?.parse::<u32>().map_err(|_| ())?;
+    let seconds = token.get(4..6).ok_or(())?.parse::<u32>().map_err(|_| ())?;
+    // 60 for a leap second
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return Err(());
+    }
+    // fractional seconds come with 1-3 (or more) digits, ".1" and ".100" are both 100ms
+    let milliseconds = match token.get(7..) {
+        Some(fraction) if !fraction.is_empty() => {
+            // all ASCII, so any byte is a char boundary
+            if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(());
+            }
+            let digits = &fraction[..fraction.len().min(3)];
+            let scale = 10u32.pow(3 - digits.len() as u32);
+            digits.parse::<u32>().map_err(|_| ())? * scale
+        }
+        _ => 0,
+    };
+
+    Ok(Some(
+        hours * 60 * 60_000 + minutes * 60_000 + seconds * 1_000 + milliseconds,
+    ))
+}
+
+// (d)ddmm.mmmm to decimal degrees
+fn parse_coordinate(token: &str, degree_digits: usize) -> Result<Option<f64>, ()> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+
+    let degrees = token.get(..degree_digits).ok_or(())?;
+    let minutes = token.get(degree_digits..).ok_or(())?;
+    let degrees = degrees.parse::<f64>().map_err(|_| ())?;
+    let minutes = minutes.parse::<f64>().map_err(|_| ())?;
+    Ok(Some(degrees + minutes / 60.0))
+}
+
+fn parse_mode(token: &str) -> Option<Mode> {
+    Some(match token {
+        "A" => Mode::Autonomous,
+        "D" => Mode::Differential,
+        "E" => Mode::Estimated,
+        "N" => Mode::NotValid,
+        _ => Mode::Unknown,
+    })
+}
+
+impl GNRMC {
+    fn parse_field(&mut self, field: usize, token: &str) -> Result<(), ()> {
+        match field {
+            0 => self.utc_time = parse_time(token)?,
+            1 => {
+                self.status = Some(match token {
+                    "A" => Status::Active,
+                    "V" => Status::Void,
+                    _ => Status::Unknown,
+                })
+            }
+            2 => self.latitude = parse_coordinate(token, 2)?,
+            3 => self.ns_indicator = token.chars().next(),
+            4 => self.longitude = parse_coordinate(token, 3)?,
+            5 => self.ew_indicator = token.chars().next(),
+            6 => self.speed_over_ground = parse_field(token)?,
+            7 => self.course_over_ground = parse_field(token)?,
+            8 => self.date = parse_date(token)?,
+            9 => self.magnetic_variation = parse_field(token)?,
+            10 => self.ew_indicator_mag = token.chars().next(),
+            11 => self.mode = parse_mode(token),
+            _ => {
+                // navigational status, unused
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GNGGA {
+    fn parse_field(&mut self, field: usize, token: &str) -> Result<(), ()> {
+        match field {
+            0 => self.utc_time = parse_time(token)?,
+            1 => self.latitude = parse_coordinate(token, 2)?,
+            2 => self.ns_indicator = token.chars().next(),
+            3 => self.longitude = parse_coordinate(token, 3)?,
+            4 => self.ew_indicator = token.chars().next(),
+            5 => {
+                self.fix_quality = parse_field::<u8>(token)?.map(|quality| match quality {
+                    0 => FixQuality::Invalid,
+                    1 => FixQuality::Gps,
+                    2 => FixQuality::Differential,
+                    3 => FixQuality::Pps,
+                    4 => FixQuality::RealTimeKinematic,
+                    5 => FixQuality::FloatRtk,
+                    6 => FixQuality::DeadReckoning,
+                    7 => FixQuality::Manual,
+                    8 => FixQuality::Simulation,
+                    _ => FixQuality::Unknown,
+                })
+            }
+            6 => self.satellites = parse_field(token)?,
+            7 => self.hdop = parse_field(token)?,
+            8 => self.altitude = parse_field(token)?,
+            10 => self.geoid_separation = parse_field(token)?,
+            12 => self.differential_age = parse_field(token)?,
+            13 => self.differential_station = parse_field(token)?,
+            _ => {
+                // units
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GNVTG {
+    fn parse_field(&mut self, field: usize, token: &str) -> Result<(), ()> {
+        match field {
+            0 => self.course_true = parse_field(token)?,
+            2 => self.course_magnetic = parse_field(token)?,
+            4 => self.speed_knots = parse_field(token)?,
+            6 => self.speed_kph = parse_field(token)?,
+            8 => self.mode = parse_mode(token),
+            _ => {
+                // units
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GNGSA {
+    fn parse_field(&mut self, field: usize, token: &str) -> Result<(), ()> {
+        match field {
+            0 => self.selection_mode = token.chars().next(),
+            1 => {
+                self.fix_type = parse_field::<u8>(token)?.map(|fix_type| match fix_type {
+                    1 => FixType::NoFix,
+                    2 => FixType::Fix2D,
+                    3 => FixType::Fix3D,
+                    _ => FixType::Unknown,
+                })
+            }
+            2..=13 => {
+                if parse_field::<u16>(token)?.is_some() {
+                    self.satellites += 1;
+                }
+            }
+            14 => self.pdop = parse_field(token)?,
+            15 => self.hdop = parse_field(token)?,
+            16 => self.vdop = parse_field(token)?,
+            _ => self.system_id = parse_field(token)?,
+        }
+        Ok(())
+    }
+}
+
+impl GNGSV {
+    fn parse_field(&mut self, field: usize, token: &str) -> Result<(), ()> {
+        match field {
+            0 => self.total_messages = parse_field(token)?,
+            1 => self.message_number = parse_field(token)?,
+            2 => self.satellites_in_view = parse_field(token)?,
+            _ => {
+                let index = (field - 3) / 4;
+                if index >= self.satellites.len() {
+                    self.signal_id = parse_field(token)?;
+                    return Ok(());
+                }
+
+                let satellite = self.satellites[index].get_or_insert_with(Satellite::default);
+                match (field - 3) % 4 {
+                    0 => satellite.prn = parse_field(token)?,
+                    1 => satellite.elevation = parse_field(token)?,
+                    2 => satellite.azimuth = parse_field(token)?,
+                    _ => satellite.snr = parse_field(token)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, fields: usize) -> Result<(), ()> {
+        match (fields - 3) % 4 {
+            0 => Ok(()),
+            1 => {
+                // with fewer than 4 satellites the signal ID lands in a new satellite's PRN
+                let index = (fields - 3) / 4;
+                if let Some(satellite) = self.satellites.get_mut(index).and_then(Option::take) {
+                    self.signal_id = satellite.prn.map(|prn| prn as u8);
+                }
+                Ok(())
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl NMEAMessage {
+    fn from_address(address: &str) -> Self {
+        // any talker, e.g. GP, GL or GN
+        match address.get(2..) {
+            Some("RMC") => NMEAMessage::GNRMC(GNRMC::default()),
+            Some("GGA") => NMEAMessage::GNGGA(GNGGA::default()),
+            Some("VTG") => NMEAMessage::GNVTG(GNVTG::default()),
+            Some("GSA") => NMEAMessage::GNGSA(GNGSA::default()),
+            Some("GSV") => NMEAMessage::GNGSV(GNGSV::default()),
+            _ => NMEAMessage::Unknown,
+        }
+    }
+
+    fn parse_field(&mut self, field: usize, token: &str) -> Result<(), ()> {
+        match self {
+            NMEAMessage::GNRMC(gnrmc) => gnrmc.parse_field(field, token),
+            NMEAMessage::GNGGA(gngga) => gngga.parse_field(field, token),
+            NMEAMessage::GNVTG(gnvtg) => gnvtg.parse_field(field, token),
+            NMEAMessage::GNGSA(gngsa) => gngsa.parse_field(field, token),
+            NMEAMessage::GNGSV(gngsv) => gngsv.parse_field(field, token),
+            NMEAMessage::Unknown => Ok(()),
+        }
+    }
+
+    // check the field count once the sentence is complete
+    fn finish(&mut self, fields: usize) -> Result<(), ()> {
+        let expected_fields = match self {
+            NMEAMessage::GNRMC(_) => RMC_FIELDS,
+            NMEAMessage::GNGGA(_) => GGA_FIELDS,
+            NMEAMessage::GNVTG(_) => VTG_FIELDS,
+            NMEAMessage::GNGSA(_) => GSA_FIELDS,
+            NMEAMessage::GNGSV(_) => GSV_FIELDS,
+            NMEAMessage::Unknown => return Ok(()),
+        };
+
+        if !expected_fields.contains(&fields) {
+            return Err(());
+        }
+
+        match self {
+            NMEAMessage::GNGSV(gngsv) => gngsv.finish(fields),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Read the next sentence, returning None if the underlying reader fails.
+/// Sentences that fail validation are counted in `stats` and skipped.
+pub async fn next_message<T>(tokeniser: &mut T, stats: &mut NMEAStats) -> Option<NMEAMessage>
+where
+    T: Tokeniser,
+{
+    loop {
+        match next_sentence(tokeniser).await? {
+            Ok(message) => {
+                stats.sentences += 1;
+                return Some(message);
+            }
+            Err(()) => {
+                stats.dropped += 1;
+            }
+        }
+    }
+}
+
+async fn next_sentence<T>(tokeniser: &mut T) -> Option<Result<NMEAMessage, ()>>
+where
+    T: Tokeniser,
+{
+    // skip to the start of the next sentence
+    let (mut message, mut sum, mut delimiter) = loop {
+        let (token, delimiter) = tokeniser.next_token().await?;
+        if let Some(start) = token.find('$') {
+            let address = &token[start + 1..];
+            break (
+                NMEAMessage::from_address(address),
+                checksum(address),
+                delimiter,
+            );
+        }
+    };
+
+    let mut fields = 0;
+    let mut is_valid = true;
+    while delimiter == b',' {
+        sum ^= b',';
+
+        let (token, next_delimiter) = tokeniser.next_token().await?;
+        if token.contains('$') {
+            // truncated sentence
+            return Some(Err(()));
+        }
+
+        sum ^= checksum(token);
+        is_valid &= message.parse_field(fields, token).is_ok();
+
+        fields += 1;
+        delimiter = next_delimiter;
+    }
+
+    if delimiter != b'*' {
+        // no checksum
+        return Some(Err(()));
+    }
+
+    let (token, _) = tokeniser.next_token().await?;
+    let token = token.trim_end();
+    if token.len() != 2 || u8::from_str_radix(token, 16) != Ok(sum) {
+        return Some(Err(()));
+    }
+
+    if is_valid && message.finish(fields).is_ok() {
+        Some(Ok(message))
+    } else {
+        Some(Err(()))
+    }
+}
+
+/// Read until the next position update, returning None if the underlying reader fails.
+/// Backing off on failure is left to the caller.
+pub async fn next_update<T>(tokeniser: &mut T, state: &mut NMEAState) -> Option<Fix>
+where
+    T: Tokeniser,
+{
+    //     log::info!("Reading...");
+
+    loop {
+        let token = next_message(tokeniser, &mut state.stats).await;
+        match token {
+            Some(NMEAMessage::GNRMC(gnrmc)) => {
+                let time = if let (Some(time), Some(date)) = (&gnrmc.utc_time, &gnrmc.date) {
+                    Some(*time as u64 + *date as u64 * 24 * 60 * 60_000)
+                } else {
+                    None
+                };
+
+                let location = if let (Some(latitude), Some(ew), Some(longitude), Some(ns)) = (
+                    &gnrmc.latitude,
+                    &gnrmc.ew_indicator,
+                    &gnrmc.longitude,
+                    &gnrmc.ns_indicator,
+                ) {
+                    let latitude_final = if *ns == 'S' {
+                        -1.0 * latitude
+                    } else {
+                        *latitude
+                    };
+
+                    let longitude_final = if *ew == 'W' {
+                        -1.0 * longitude
+                    } else {
+                        *longitude
+                    };
+
+                    Some((latitude_final, longitude_final))
+                } else {
+                    None
+                };
+
+                return Some(Fix {
+                    time,
+                    location,
+                    sog: gnrmc.speed_over_ground,
+                    cog: gnrmc.course_over_ground,
+                    status: gnrmc.status.unwrap_or_default(),
+                    mode: gnrmc.mode.unwrap_or_default(),
+                    hdop: state.hdop.take(),
+                    accuracy: None,
+                    source: FixSource::Nmea,
+                });
+            }
+            Some(NMEAMessage::GNGGA(gngga)) => {
+                state.hdop = match gngga.fix_quality {
+                    Some(FixQuality::Invalid) | None => None,
+                    _ => gngga.hdop,
+                };
+            }
+            Some(_) => {
+                // valid, but not a sentence we use
+            }
+            None => {
+                return None;
+            }
+        }
+    }
+}
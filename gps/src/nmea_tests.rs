@@ -0,0 +1,560 @@
+mod tests {
+    extern crate std;
+
+    use crate::*;
+    use embassy_futures::block_on;
+    use std::format;
+    use std::string::String;
+    use std::vec::Vec;
+
+    // 1Hz u-blox M8 capture, heading west off Outer Harbor
+    const OUTER_HARBOR: &[u8] = include_bytes!("../test_data/outer_harbor.nmea");
+
+    // 2024-03-14T02:30:40Z
+    const FIRST_FIX_MS: u64 = 1_710_383_440_000;
+
+    struct SliceReader<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl<'a> SliceReader<'a> {
+        fn new(data: &'a [u8], chunk: usize) -> Self {
+            Self { data, chunk }
+        }
+    }
+
+    impl AsyncReader for SliceReader<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+            let len = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    fn updates<const N: usize>(data: &[u8], chunk: usize) -> Vec<Fix> {
+        parse::<N>(data, chunk).0
+    }
+
+    fn parse<const N: usize>(data: &[u8], chunk: usize) -> (Vec<Fix>, NMEAStats) {
+        let mut ring_buffer = RingBuffer::<_, N>::new(SliceReader::new(data, chunk));
+        let mut state = NMEAState::default();
+        let mut result = Vec::new();
+        while let Some(fix) = block_on(next_update(&mut ring_buffer, &mut state)) {
+            result.push(fix);
+        }
+        (result, state.stats)
+    }
+
+    // wrap a sentence body with a valid checksum
+    fn sentence(body: &str) -> String {
+        let sum = body.bytes().fold(0, |acc, b| acc ^ b);
+        format!("${}*{:02X}\r\n", body, sum)
+    }
+
+    const RMC: &str = "GNRMC,023043.00,A,3457.38421,S,13830.20381,E,5.302,270.95,140324,,,A";
+
+    fn approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
+        (a - b).abs() < epsilon
+    }
+
+    #[test]
+    fn test_tokeniser() {
+        let data = b"$GNVTG,271.33,T,,M*29\r\n";
+        let mut ring_buffer = RingBuffer::<_, 8>::new(SliceReader::new(data, 3));
+
+        let mut tokens = Vec::new();
+        while let Some((token, delimiter)) = block_on(ring_buffer.next_token()) {
+            tokens.push((String::from(token), delimiter));
+        }
+
+        let expected = [
+            ("$GNVTG", b','),
+            ("271.33", b','),
+            ("T", b','),
+            ("", b','),
+            ("M", b'*'),
+            ("29\r", b'\n'),
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for ((token, delimiter), (expected_token, expected_delimiter)) in
+            tokens.iter().zip(expected.iter())
+        {
+            assert_eq!(token, expected_token);
+            assert_eq!(delimiter, expected_delimiter);
+        }
+    }
+
+    #[test]
+    fn test_tokeniser_overflow() {
+        // tokens longer than the buffer are dropped, parsing resumes on the next delimiter
+        let data = b"$GNRMC,0123456789ABCDEF,A\n";
+        let mut ring_buffer = RingBuffer::<_, 8>::new(SliceReader::new(data, 8));
+
+        assert_eq!(block_on(ring_buffer.next_token()), Some(("$GNRMC", b',')));
+        assert_eq!(block_on(ring_buffer.next_token()), Some(("", b',')));
+        assert_eq!(block_on(ring_buffer.next_token()), Some(("A", b'\n')));
+        assert_eq!(block_on(ring_buffer.next_token()), None);
+    }
+
+    #[test]
+    fn test_recorded_log() {
+        let (updates, stats) = parse::<32>(OUTER_HARBOR, 32);
+        assert_eq!(updates.len(), 12);
+        assert_eq!(
+            stats,
+            NMEAStats {
+                sentences: 87,
+                dropped: 0
+            }
+        );
+
+        let fix = updates[0];
+        assert_eq!(fix.time, Some(FIRST_FIX_MS));
+
+        let (lat, lon) = fix.location.unwrap();
+        assert!(approx_eq(lat, -34.956404, 1e-6));
+        assert!(approx_eq(lon, 138.504157, 1e-6));
+
+        assert_eq!(fix.velocity(), Some((5.2, 272.5)));
+        assert_eq!(fix.status, Status::Active);
+        assert_eq!(fix.mode, Mode::Autonomous);
+        assert_eq!(fix.source, FixSource::Nmea);
+        assert!(fix.is_valid());
+        // GGA follows RMC, so HDOP lags by one fix
+        assert_eq!(fix.hdop, None);
+        assert_eq!(updates[1].hdop, Some(1.02));
+        assert_eq!(updates[11].hdop, Some(0.92));
+
+        // one fix per second, heading west
+        for (i, fix) in updates.iter().enumerate() {
+            assert_eq!(fix.time, Some(FIRST_FIX_MS + i as u64 * 1000));
+            assert!(fix.location.unwrap().1 <= lon);
+        }
+    }
+
+    #[test]
+    fn test_read_sizes() {
+        // the result must not depend on how the UART delivers the bytes
+        let expected = updates::<32>(OUTER_HARBOR, 32);
+        for chunk in [1, 5, 13, 31, 64] {
+            assert_eq!(updates::<32>(OUTER_HARBOR, chunk), expected);
+            assert_eq!(updates::<128>(OUTER_HARBOR, chunk), expected);
+        }
+    }
+
+    #[test]
+    fn test_stale_hdop() {
+        let gga = "GNGGA,023043.00,3457.38421,S,13830.20381,E,1,08,1.02,3.1,M,-1.0,M,,";
+        let data = [RMC, gga, RMC, RMC]
+            .iter()
+            .map(|body| sentence(body))
+            .collect::<String>();
+        let updates = updates::<32>(data.as_bytes(), 32);
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[1].hdop, Some(1.02));
+        // GGA stopped, the HDOP went with it
+        assert_eq!(updates[2].hdop, None);
+    }
+
+    #[test]
+    fn test_void_fix() {
+        let data = b"$GPRMC,235959.00,V,,,,,,,311299,,,N*7D\r\n";
+        let updates = updates::<32>(data, 32);
+        assert_eq!(updates.len(), 1);
+
+        let fix = updates[0];
+        assert_eq!(fix.time, Some(4_102_444_799_000));
+        assert_eq!(fix.location, None);
+        assert_eq!(fix.velocity(), None);
+        assert_eq!(fix.status, Status::Void);
+        assert_eq!(fix.mode, Mode::NotValid);
+        assert!(!fix.is_valid());
+    }
+
+    #[test]
+    fn test_sub_second_time() {
+        // receivers pad the fraction to different widths, 10Hz output must stay in order
+        let start = FIRST_FIX_MS;
+        for (time, expected) in [
+            ("023040", start),
+            ("023040.1", start + 100),
+            ("023040.10", start + 100),
+            ("023040.100", start + 100),
+            ("023040.25", start + 250),
+            ("023040.05", start + 50),
+            ("023040.999", start + 999),
+            ("023040.1234", start + 123),
+        ] {
+            let body = format!("GNRMC,{},A,3457.38421,S,13830.20381,E,5.3,270.9,140324,,,A", time);
+            let updates = updates::<32>(sentence(&body).as_bytes(), 32);
+            assert_eq!(updates[0].time, Some(expected), "{}", time);
+        }
+
+        // a multi-byte character straddling the third digit can still checksum
+        for fraction in ["1x", "12é", "é"] {
+            let body = format!("GNRMC,023040.{},A,3457.38421,S,13830.20381,E,5.3,270.9,140324,,,A", fraction);
+            let (updates, stats) = parse::<32>(sentence(&body).as_bytes(), 32);
+            assert!(updates.is_empty(), "{}", fraction);
+            assert_eq!(stats.dropped, 1, "{}", fraction);
+        }
+    }
+
+    #[test]
+    fn test_time_range() {
+        for time in ["240000", "236000", "235961", "99999900"] {
+            let body = format!("GNRMC,{},A,3457.38421,S,13830.20381,E,5.3,270.9,140324,,,A", time);
+            let (updates, stats) = parse::<32>(sentence(&body).as_bytes(), 32);
+            assert!(updates.is_empty(), "{}", time);
+            assert_eq!(stats.dropped, 1, "{}", time);
+        }
+
+        // a leap second
+        let body = "GNRMC,235960,A,3457.38421,S,13830.20381,E,5.3,270.9,140324,,,A";
+        assert_eq!(updates::<32>(sentence(body).as_bytes(), 32).len(), 1);
+    }
+
+    #[test]
+    fn test_message() {
+        let data = b"$GNRMC,023043.00,A,3457.38421,S,13830.20381,E,5.302,270.95,140324,,,A*6E\r\n";
+        let mut ring_buffer = RingBuffer::<_, 32>::new(SliceReader::new(data, 32));
+        let mut stats = NMEAStats::default();
+
+        match block_on(next_message(&mut ring_buffer, &mut stats)) {
+            Some(NMEAMessage::GNRMC(rmc)) => {
+                assert_eq!(rmc.utc_time, Some(((2 * 60 + 30) * 60 + 43) * 1000));
+                assert_eq!(rmc.status, Some(Status::Active));
+                assert_eq!(rmc.ns_indicator, Some('S'));
+                assert_eq!(rmc.ew_indicator, Some('E'));
+                assert_eq!(rmc.magnetic_variation, None);
+                assert_eq!(rmc.mode, Some(Mode::Autonomous));
+            }
+            _ => panic!("Expected GNRMC"),
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut data = sentence(RMC);
+        // corrupt a digit of the longitude
+        data.replace_range(35..36, "9");
+        data.push_str(&sentence(RMC));
+
+        let (updates, stats) = parse::<32>(data.as_bytes(), 32);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            stats,
+            NMEAStats {
+                sentences: 1,
+                dropped: 1
+            }
+        );
+
+        // checksum must be two hex digits
+        let data = format!("${}*6\r\n", RMC);
+        let (updates, stats) = parse::<32>(data.as_bytes(), 32);
+        assert_eq!(updates.len(), 0);
+        assert_eq!(stats.dropped, 1);
+    }
+
+    #[test]
+    fn test_corrupted_log() {
+        // flip a bit in the speed of every third fix
+        let mut data = Vec::from(OUTER_HARBOR);
+        let mut corrupted = 0;
+        for (i, start) in (0..data.len())
+            .filter(|&i| data[i..].starts_with(b"$GNRMC"))
+            .enumerate()
+            .collect::<Vec<_>>()
+        {
+            if i % 3 == 0 {
+                data[start + 47] ^= 0x01;
+                corrupted += 1;
+            }
+        }
+
+        let (updates, stats) = parse::<32>(&data, 7);
+        assert_eq!(updates.len(), 12 - corrupted);
+        assert_eq!(stats.dropped, corrupted as u32);
+        assert_eq!(stats.sentences, 87 - corrupted as u32);
+    }
+
+    #[test]
+    fn test_field_count() {
+        // one field short of NMEA 2.2
+        let short = "GNRMC,023043.00,A,3457.38421,S,13830.20381,E,5.302,270.95,140324,";
+        // 4.1 nav status plus an extra field
+        let long = "GNRMC,023043.00,A,3457.38421,S,13830.20381,E,5.302,270.95,140324,,,A,V,X";
+        // NMEA 2.2 and 4.1 are both fine
+        let v22 = "GNRMC,023043.00,A,3457.38421,S,13830.20381,E,5.302,270.95,140324,,";
+        let v41 = "GNRMC,023043.00,A,3457.38421,S,13830.20381,E,5.302,270.95,140324,,,A,V";
+
+        let data = [short, long, v22, v41].map(sentence).concat();
+        let (updates, stats) = parse::<32>(data.as_bytes(), 32);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(
+            stats,
+            NMEAStats {
+                sentences: 2,
+                dropped: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed_fields() {
+        let bad_latitude = RMC.replace("3457.38421", "34S7.38421");
+        let bad_speed = RMC.replace("5.302", "5.3.2");
+        let bad_date = RMC.replace("140324", "1403");
+        let bad_time = RMC.replace("023043.00", "02:30:43");
+
+        let data = [bad_latitude, bad_speed, bad_date, bad_time]
+            .map(|body| sentence(&body))
+            .concat();
+        let (updates, stats) = parse::<32>(data.as_bytes(), 32);
+        assert_eq!(updates.len(), 0);
+        assert_eq!(stats.dropped, 4);
+    }
+
+    #[test]
+    fn test_dates() {
+        for (date, expected) in [
+            ("140324", Some(FIRST_FIX_MS)),
+            // leap days
+            ("290224", Some(1_709_173_840_000)),
+            ("290200", Some(951_791_440_000)),
+            ("010300", Some(951_877_840_000)),
+            ("311224", Some(1_735_612_240_000)),
+        ] {
+            let updates = updates::<32>(sentence(&RMC.replace("140324", date)).as_bytes(), 32);
+            assert_eq!(updates.len(), 1, "{}", date);
+            assert_eq!(updates[0].time.map(|time| time - 3000), expected, "{}", date);
+        }
+
+        // these used to underflow or land on the wrong day
+        for date in ["000324", "140024", "141324", "320124", "290223", "310424", "14-324"] {
+            let (updates, stats) =
+                parse::<32>(sentence(&RMC.replace("140324", date)).as_bytes(), 32);
+            assert!(updates.is_empty(), "{}", date);
+            assert_eq!(stats.dropped, 1, "{}", date);
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        // missing checksum, missing line, and a sentence cut short by the next one
+        let mut data = String::from("$GNRMC,023043.00,A*\r\n");
+        data.push_str("$GNRMC,023043.00,A\r\n");
+        data.push_str("$GNRMC,023043.00,A,$GNVTG,271.33,T,,M,5.214,N,9.656,K,A*29\r\n");
+        data.push_str(&sentence(RMC));
+
+        let (updates, stats) = parse::<32>(data.as_bytes(), 32);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(stats.dropped, 3);
+    }
+
+    #[test]
+    fn test_line_noise() {
+        let mut data = Vec::from(sentence(RMC).as_bytes());
+        data[20] = 0xff;
+        data.extend_from_slice(sentence(RMC).as_bytes());
+
+        let (updates, stats) = parse::<32>(&data, 32);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(stats.dropped, 1);
+    }
+
+    fn messages(data: &[u8]) -> (Vec<NMEAMessage>, NMEAStats) {
+        let mut ring_buffer = RingBuffer::<_, 32>::new(SliceReader::new(data, 32));
+        let mut stats = NMEAStats::default();
+        let mut result = Vec::new();
+        while let Some(message) = block_on(next_message(&mut ring_buffer, &mut stats)) {
+            result.push(message);
+        }
+        (result, stats)
+    }
+
+    fn message(body: &str) -> NMEAMessage {
+        let (mut messages, stats) = messages(sentence(body).as_bytes());
+        assert_eq!(stats.dropped, 0, "{} was dropped", body);
+        messages.pop().unwrap()
+    }
+
+    #[test]
+    fn test_recorded_sentence_types() {
+        let (messages, _) = messages(OUTER_HARBOR);
+
+        let count = |f: fn(&NMEAMessage) -> bool| messages.iter().filter(|m| f(m)).count();
+        assert_eq!(count(|m| matches!(m, NMEAMessage::GNRMC(_))), 12);
+        assert_eq!(count(|m| matches!(m, NMEAMessage::GNGGA(_))), 12);
+        assert_eq!(count(|m| matches!(m, NMEAMessage::GNVTG(_))), 12);
+        assert_eq!(count(|m| matches!(m, NMEAMessage::GNGSA(_))), 24);
+        assert_eq!(count(|m| matches!(m, NMEAMessage::GNGSV(_))), 15);
+        // GLL
+        assert_eq!(count(|m| matches!(m, NMEAMessage::Unknown)), 12);
+    }
+
+    #[test]
+    fn test_gga() {
+        let body = "GNGGA,023042.00,3457.38424,S,13830.20562,E,1,09,1.02,4.3,M,-5.6,M,,";
+        match message(body) {
+            NMEAMessage::GNGGA(gga) => {
+                assert_eq!(gga.utc_time, Some(((2 * 60 + 30) * 60 + 42) * 1000));
+                assert!(approx_eq(gga.latitude.unwrap(), 34.956404, 1e-6));
+                assert_eq!(gga.ns_indicator, Some('S'));
+                assert!(approx_eq(gga.longitude.unwrap(), 138.503427, 1e-6));
+                assert_eq!(gga.ew_indicator, Some('E'));
+                assert_eq!(gga.fix_quality, Some(FixQuality::Gps));
+                assert_eq!(gga.satellites, Some(9));
+                assert_eq!(gga.hdop, Some(1.02));
+                assert_eq!(gga.altitude, Some(4.3));
+                assert_eq!(gga.geoid_separation, Some(-5.6));
+                assert_eq!(gga.differential_age, None);
+                assert_eq!(gga.differential_station, None);
+            }
+            _ => panic!("Expected GNGGA"),
+        }
+
+        // dead reckoning, no position
+        match message("GPGGA,023042.00,,,,,6,00,99.99,,,,,,") {
+            NMEAMessage::GNGGA(gga) => {
+                assert_eq!(gga.fix_quality, Some(FixQuality::DeadReckoning));
+                assert_eq!(gga.satellites, Some(0));
+                assert_eq!(gga.hdop, Some(99.99));
+                assert_eq!(gga.latitude, None);
+            }
+            _ => panic!("Expected GNGGA"),
+        }
+    }
+
+    #[test]
+    fn test_vtg() {
+        match message("GNVTG,271.33,T,262.10,M,5.214,N,9.656,K,D") {
+            NMEAMessage::GNVTG(vtg) => {
+                assert_eq!(vtg.course_true, Some(271.33));
+                assert_eq!(vtg.course_magnetic, Some(262.10));
+                assert_eq!(vtg.speed_knots, Some(5.214));
+                assert_eq!(vtg.speed_kph, Some(9.656));
+                assert_eq!(vtg.mode, Some(Mode::Differential));
+            }
+            _ => panic!("Expected GNVTG"),
+        }
+
+        // NMEA 2.2, no mode indicator
+        match message("GPVTG,271.33,T,,M,5.214,N,9.656,K") {
+            NMEAMessage::GNVTG(vtg) => {
+                assert_eq!(vtg.course_magnetic, None);
+                assert!(vtg.mode.is_none());
+            }
+            _ => panic!("Expected GNVTG"),
+        }
+    }
+
+    #[test]
+    fn test_gsa() {
+        match message("GNGSA,A,3,02,05,12,13,15,18,25,29,,,,,1.85,1.02,1.48") {
+            NMEAMessage::GNGSA(gsa) => {
+                assert_eq!(gsa.selection_mode, Some('A'));
+                assert_eq!(gsa.fix_type, Some(FixType::Fix3D));
+                assert_eq!(gsa.satellites, 8);
+                assert_eq!(gsa.pdop, Some(1.85));
+                assert_eq!(gsa.hdop, Some(1.02));
+                assert_eq!(gsa.vdop, Some(1.48));
+                assert_eq!(gsa.system_id, None);
+            }
+            _ => panic!("Expected GNGSA"),
+        }
+
+        // NMEA 4.1 with system ID
+        match message("GNGSA,A,2,65,72,,,,,,,,,,,2.50,2.30,0.98,2") {
+            NMEAMessage::GNGSA(gsa) => {
+                assert_eq!(gsa.fix_type, Some(FixType::Fix2D));
+                assert_eq!(gsa.satellites, 2);
+                assert_eq!(gsa.system_id, Some(2));
+            }
+            _ => panic!("Expected GNGSA"),
+        }
+    }
+
+    #[test]
+    fn test_gsv() {
+        match message("GPGSV,3,3,11,26,02,319,,29,11,101,27,31,01,260,") {
+            NMEAMessage::GNGSV(gsv) => {
+                assert_eq!(gsv.total_messages, Some(3));
+                assert_eq!(gsv.message_number, Some(3));
+                assert_eq!(gsv.satellites_in_view, Some(11));
+                assert_eq!(
+                    gsv.satellites[0],
+                    Some(Satellite {
+                        prn: Some(26),
+                        elevation: Some(2),
+                        azimuth: Some(319),
+                        snr: None,
+                    })
+                );
+                assert_eq!(gsv.satellites[1].unwrap().snr, Some(27));
+                assert_eq!(gsv.satellites[2].unwrap().prn, Some(31));
+                assert_eq!(gsv.satellites[3], None);
+                assert_eq!(gsv.signal_id, None);
+            }
+            _ => panic!("Expected GNGSV"),
+        }
+
+        // NMEA 4.1 signal ID after a partial group
+        match message("GLGSV,2,2,05,88,61,358,40,1") {
+            NMEAMessage::GNGSV(gsv) => {
+                assert!(gsv.satellites[0].is_some());
+                assert_eq!(gsv.satellites[1], None);
+                assert_eq!(gsv.signal_id, Some(1));
+            }
+            _ => panic!("Expected GNGSV"),
+        }
+
+        // and after a full one
+        let body = "GPGSV,3,1,11,02,24,289,32,05,58,243,41,12,41,108,38,13,17,213,29,1";
+        match message(body) {
+            NMEAMessage::GNGSV(gsv) => {
+                assert!(gsv.satellites.iter().all(Option::is_some));
+                assert_eq!(gsv.signal_id, Some(1));
+            }
+            _ => panic!("Expected GNGSV"),
+        }
+    }
+
+    #[test]
+    fn test_sentence_field_counts() {
+        let bad = [
+            // GGA missing the station ID
+            "GNGGA,023042.00,3457.38424,S,13830.20562,E,1,09,1.02,4.3,M,-5.6,M,",
+            // VTG with an extra field
+            "GNVTG,271.33,T,,M,5.214,N,9.656,K,A,A",
+            // GSA one PRN short
+            "GNGSA,A,3,02,05,12,13,15,18,25,29,,,,1.85,1.02,1.48",
+            // GSV with a partial satellite
+            "GPGSV,3,3,11,26,02,319,,29,11",
+            // GSV with a fifth satellite
+            "GPGSV,3,1,11,02,24,289,32,05,58,243,41,12,41,108,38,13,17,213,29,15,62,144,44",
+        ];
+
+        let data = bad.map(sentence).concat();
+        let (messages, stats) = messages(data.as_bytes());
+        assert_eq!(messages.len(), 0);
+        assert_eq!(stats.dropped, bad.len() as u32);
+    }
+
+    #[test]
+    fn test_malformed_sentences() {
+        let bad = [
+            "GNGGA,023042.00,3457.38424,S,13830.20562,E,X,09,1.02,4.3,M,-5.6,M,,",
+            "GNVTG,271.33,T,,M,fast,N,9.656,K,A",
+            "GNGSA,A,3,02,05,12,13,15,18,25,G29,,,,,1.85,1.02,1.48",
+            "GPGSV,3,3,11,26,02,319,,29,-11,101,27,31,01,260,",
+        ];
+
+        let data = bad.map(sentence).concat();
+        let (messages, stats) = messages(data.as_bytes());
+        assert_eq!(messages.len(), 0);
+        assert_eq!(stats.dropped, bad.len() as u32);
+    }
+}
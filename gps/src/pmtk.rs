@@ -0,0 +1,58 @@
+use core::fmt::Write;
+
+/// Configuration sentences for MediaTek (MTK) receivers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PMTKCommand {
+    /// PMTK251, takes effect immediately, the ack is lost
+    SetBaud(u32),
+    /// PMTK220, fix and NMEA output interval
+    SetFixInterval(u16),
+    /// PMTK314, output RMC and GGA every `n` fixes, everything else off
+    SetOutput { rmc: u8, gga: u8 },
+    /// PMTK313
+    EnableSbas(bool),
+    /// PMTK319, SBAS integrity mode
+    SbasIntegrity(bool),
+}
+
+// formats into a fixed buffer, failing if it doesn't fit
+struct Cursor<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Write for Cursor<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.pos + s.len();
+        if end > self.buf.len() {
+            return Err(core::fmt::Error);
+        }
+        self.buf[self.pos..end].copy_from_slice(s.as_bytes());
+        self.pos = end;
+        Ok(())
+    }
+}
+
+impl PMTKCommand {
+    /// Write the sentence, checksum and line ending into `buf`, returning its length
+    #[allow(clippy::result_unit_err)]
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, ()> {
+        let mut cursor = Cursor { buf, pos: 0 };
+        match *self {
+            PMTKCommand::SetBaud(baud) => write!(cursor, "$PMTK251,{}", baud),
+            PMTKCommand::SetFixInterval(ms) => write!(cursor, "$PMTK220,{}", ms),
+            PMTKCommand::SetOutput { rmc, gga } => write!(
+                cursor,
+                "$PMTK314,0,{},0,{},0,0,0,0,0,0,0,0,0,0,0,0,0,0,0",
+                rmc, gga
+            ),
+            PMTKCommand::EnableSbas(enable) => write!(cursor, "$PMTK313,{}", enable as u8),
+            PMTKCommand::SbasIntegrity(enable) => write!(cursor, "$PMTK319,{}", enable as u8),
+        }
+        .map_err(|_| ())?;
+
+        let sum = cursor.buf[1..cursor.pos].iter().fold(0, |acc, b| acc ^ b);
+        write!(cursor, "*{:02X}\r\n", sum).map_err(|_| ())?;
+        Ok(cursor.pos)
+    }
+}
@@ -0,0 +1,92 @@
+#[allow(async_fn_in_trait)]
+pub trait AsyncReader {
+    /// Read into `buf`, returning the number of bytes read. Ok(0) signals end of stream.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()>;
+}
+
+#[allow(async_fn_in_trait)]
+pub trait Tokeniser {
+    /// Returns the next token along with the delimiter that terminated it.
+    /// A delimiter of 0 marks a corrupt token, which is returned empty.
+    async fn next_token(&mut self) -> Option<(&str, u8)>;
+}
+
+pub struct RingBuffer<Reader, const N: usize>
+where
+    Reader: AsyncReader,
+{
+    reader: Reader,
+    buf: [u8; N],
+    // start of the unconsumed data
+    read_ptr: usize,
+    // end of the valid data
+    write_ptr: usize,
+}
+
+impl<Reader, const N: usize> RingBuffer<Reader, N>
+where
+    Reader: AsyncReader,
+{
+    pub fn new(reader: Reader) -> Self {
+        Self {
+            reader,
+            buf: [0; N],
+            read_ptr: 0,
+            write_ptr: 0,
+        }
+    }
+}
+
+impl<Reader, const N: usize> Tokeniser for RingBuffer<Reader, N>
+where
+    Reader: AsyncReader,
+{
+    async fn next_token(&mut self) -> Option<(&str, u8)> {
+        let mut cursor = self.read_ptr;
+
+        loop {
+            if cursor == self.write_ptr {
+                // shuffle the partial token to the front of the buffer
+                if self.read_ptr > 0 {
+                    self.buf.copy_within(self.read_ptr..self.write_ptr, 0);
+                    self.write_ptr -= self.read_ptr;
+                    cursor -= self.read_ptr;
+                    self.read_ptr = 0;
+                }
+
+                if self.write_ptr == N {
+                    // token doesn't fit in the buffer, drop it and resync on the next delimiter
+                    log::warn!("Token overflow");
+                    self.write_ptr = 0;
+                    cursor = 0;
+                }
+
+                match self.reader.read(&mut self.buf[self.write_ptr..]).await {
+                    Ok(0) | Err(_) => {
+                        // log::info!("RX error");
+                        return None;
+                    }
+                    Ok(len) => self.write_ptr += len,
+                }
+            }
+
+            if matches!(self.buf[cursor], b',' | b'\n' | b'*') {
+                break;
+            }
+            cursor += 1;
+        }
+
+        let token_start = self.read_ptr;
+        let delimiter = self.buf[cursor];
+        self.read_ptr = cursor + 1;
+
+        match core::str::from_utf8(&self.buf[token_start..cursor]) {
+            Ok(s) => Some((s, delimiter)),
+            Err(e) => {
+                // line noise, let the parser drop the sentence
+                log::info!("UTF8 error: {:?}", e);
+                Some(("", 0))
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::pmtk::PMTKCommand;
+use crate::ubx::{PowerMode, UBXCommand};
+
+/// Fix interval limits, anything slower than 5Hz is too coarse to judge a start
+pub const MIN_RATE_MS: u16 = 100;
+pub const MAX_RATE_MS: u16 = 200;
+
+/// Receiver setup sent at boot. The baud rate command goes first at `boot_baud`,
+/// the host UART then follows the receiver to `baud` before the rest is sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReceiverConfig {
+    /// Baud rate the receiver powers up at
+    pub boot_baud: u32,
+    pub baud: u32,
+    /// Time between fixes, clamped to MIN_RATE_MS..=MAX_RATE_MS
+    pub rate_ms: u16,
+}
+
+impl Default for ReceiverConfig {
+    fn default() -> Self {
+        Self {
+            boot_baud: 9600,
+            baud: 115_200,
+            rate_ms: MIN_RATE_MS,
+        }
+    }
+}
+
+impl ReceiverConfig {
+    fn rate_ms(&self) -> u16 {
+        self.rate_ms.clamp(MIN_RATE_MS, MAX_RATE_MS)
+    }
+
+    pub fn pmtk_baud(&self) -> PMTKCommand {
+        PMTKCommand::SetBaud(self.baud)
+    }
+
+    /// RMC and GGA only, at the configured rate, with SBAS
+    pub fn pmtk_setup(&self) -> [PMTKCommand; 4] {
+        [
+            PMTKCommand::SetOutput { rmc: 1, gga: 1 },
+            PMTKCommand::SetFixInterval(self.rate_ms()),
+            PMTKCommand::EnableSbas(true),
+            PMTKCommand::SbasIntegrity(true),
+        ]
+    }
+
+    /// UART1 at the configured baud rate, UBX output only
+    pub fn ubx_baud(&self) -> UBXCommand {
+        UBXCommand::CfgPrt {
+            port: 1,
+            baud: self.baud,
+            in_proto: UBXCommand::PROTO_UBX | UBXCommand::PROTO_NMEA,
+            out_proto: UBXCommand::PROTO_UBX,
+        }
+    }
+
+    /// NAV-PVT every fix at the configured rate, full power
+    pub fn ubx_setup(&self) -> [UBXCommand; 3] {
+        let (class, id) = UBXCommand::NAV_PVT;
+        [
+            UBXCommand::CfgMsg { class, id, rate: 1 },
+            UBXCommand::CfgRate {
+                measurement_ms: self.rate_ms(),
+            },
+            UBXCommand::CfgRxm {
+                power_mode: PowerMode::Continuous,
+            },
+        ]
+    }
+}
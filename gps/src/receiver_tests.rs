@@ -0,0 +1,99 @@
+mod tests {
+    extern crate std;
+
+    use crate::*;
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn pmtk(command: PMTKCommand) -> String {
+        let mut buf = [0; 64];
+        let len = command.encode(&mut buf).unwrap();
+        String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
+
+    fn ubx(command: UBXCommand) -> Vec<u8> {
+        let mut buf = [0; 64];
+        let len = command.encode(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn test_pmtk() {
+        assert_eq!(pmtk(PMTKCommand::SetBaud(115_200)), "$PMTK251,115200*1F\r\n");
+        assert_eq!(pmtk(PMTKCommand::SetFixInterval(100)), "$PMTK220,100*2F\r\n");
+        assert_eq!(pmtk(PMTKCommand::SetFixInterval(200)), "$PMTK220,200*2C\r\n");
+        assert_eq!(
+            pmtk(PMTKCommand::SetOutput { rmc: 1, gga: 1 }),
+            "$PMTK314,0,1,0,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0*28\r\n"
+        );
+        assert_eq!(pmtk(PMTKCommand::EnableSbas(true)), "$PMTK313,1*2E\r\n");
+        assert_eq!(pmtk(PMTKCommand::SbasIntegrity(true)), "$PMTK319,1*24\r\n");
+
+        // buffer too small
+        let mut buf = [0; 16];
+        assert_eq!(PMTKCommand::SetBaud(115_200).encode(&mut buf), Err(()));
+    }
+
+    #[test]
+    fn test_pmtk_setup() {
+        let config = ReceiverConfig::default();
+        assert_eq!(pmtk(config.pmtk_baud()), "$PMTK251,115200*1F\r\n");
+
+        let setup: Vec<String> = config.pmtk_setup().into_iter().map(pmtk).collect();
+        assert_eq!(
+            setup,
+            [
+                "$PMTK314,0,1,0,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0*28\r\n",
+                "$PMTK220,100*2F\r\n",
+                "$PMTK313,1*2E\r\n",
+                "$PMTK319,1*24\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ubx_setup() {
+        let config = ReceiverConfig {
+            rate_ms: 200,
+            ..Default::default()
+        };
+
+        let prt = ubx(config.ubx_baud());
+        assert_eq!(prt[2..4], [0x06, 0x00]);
+        assert_eq!(prt[14..18], 115_200u32.to_le_bytes());
+        // UBX out only
+        assert_eq!(prt[20..22], [0x01, 0x00]);
+
+        let setup: Vec<Vec<u8>> = config.ubx_setup().into_iter().map(ubx).collect();
+        assert_eq!(
+            setup[0],
+            [0xB5, 0x62, 0x06, 0x01, 0x03, 0x00, 0x01, 0x07, 0x01, 0x13, 0x51]
+        );
+        // CFG-RATE at 5Hz
+        assert_eq!(setup[1][2..8], [0x06, 0x08, 0x06, 0x00, 0xC8, 0x00]);
+        assert_eq!(
+            setup[2],
+            [0xB5, 0x62, 0x06, 0x11, 0x02, 0x00, 0x08, 0x00, 0x21, 0x91]
+        );
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        for (rate_ms, expected) in [(0, 100), (50, 100), (100, 100), (150, 150), (1000, 200)] {
+            let config = ReceiverConfig {
+                rate_ms,
+                ..Default::default()
+            };
+            assert_eq!(
+                config.pmtk_setup()[1],
+                PMTKCommand::SetFixInterval(expected)
+            );
+            assert_eq!(
+                config.ubx_setup()[1],
+                UBXCommand::CfgRate {
+                    measurement_ms: expected
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,48 @@
+pub fn is_leap_year(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Days in `month` (1-12) of `year`, 0 for an invalid month
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date, failing on dates that don't exist.
+/// Eras of 400 years repeat exactly, so the year is counted from March within its era
+/// which puts the leap day at the end.
+#[allow(clippy::result_unit_err)]
+pub fn days_from_civil(year: i32, month: u32, day: u32) -> Result<i64, ()> {
+    if day == 0 || day > days_in_month(year, month) {
+        return Err(());
+    }
+
+    let year = if month <= 2 { year - 1 } else { year } as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Ok(era * 146_097 + day_of_era - 719_468)
+}
+
+/// UTC date and time to ms since the unix epoch, failing on invalid fields.
+/// `second` may be 60 during a leap second.
+#[allow(clippy::result_unit_err)]
+pub fn epoch_ms(
+    (year, month, day): (i32, u32, u32),
+    (hour, minute, second): (u32, u32, u32),
+    millisecond: u32,
+) -> Result<u64, ()> {
+    if hour > 23 || minute > 59 || second > 60 || millisecond > 999 {
+        return Err(());
+    }
+
+    let days = days_from_civil(year, month, day)?;
+    let seconds = days * 86_400 + (hour * 3_600 + minute * 60 + second) as i64;
+    u64::try_from(seconds * 1_000 + millisecond as i64).map_err(|_| ())
+}
@@ -0,0 +1,101 @@
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_known_dates() {
+        for ((year, month, day), expected) in [
+            ((1970, 1, 1), 0),
+            ((1969, 12, 31), -1),
+            ((1900, 3, 1), -25_508),
+            ((1980, 1, 6), 3_657),
+            ((1999, 12, 31), 10_956),
+            ((2000, 1, 1), 10_957),
+            ((2000, 2, 29), 11_016),
+            ((2000, 3, 1), 11_017),
+            ((2024, 2, 29), 19_782),
+            ((2024, 3, 1), 19_783),
+            ((2024, 3, 14), 19_796),
+            ((2038, 1, 19), 24_855),
+            ((2079, 12, 31), 40_176),
+            ((2100, 2, 28), 47_540),
+            ((2100, 3, 1), 47_541),
+        ] {
+            assert_eq!(
+                days_from_civil(year, month, day),
+                Ok(expected),
+                "{}-{}-{}",
+                year,
+                month,
+                day
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_dates() {
+        for (year, month, day) in [
+            (2024, 0, 1),
+            (2024, 13, 1),
+            (2024, 1, 0),
+            (2024, 1, 32),
+            (2024, 4, 31),
+            (2023, 2, 29),
+            (2100, 2, 29),
+            (1900, 2, 29),
+        ] {
+            assert_eq!(
+                days_from_civil(year, month, day),
+                Err(()),
+                "{}-{}-{}",
+                year,
+                month,
+                day
+            );
+        }
+        assert_eq!(days_from_civil(2000, 2, 29), Ok(11_016));
+    }
+
+    #[test]
+    fn test_every_day() {
+        // walk the calendar a day at a time across three century boundaries
+        let mut expected = days_from_civil(1800, 1, 1).unwrap();
+        for year in 1800..2300 {
+            let mut days_in_year = 0;
+            for month in 1..=12 {
+                for day in 1..=days_in_month(year, month) {
+                    assert_eq!(days_from_civil(year, month, day), Ok(expected));
+                    expected += 1;
+                    days_in_year += 1;
+                }
+            }
+            assert_eq!(days_in_year == 366, is_leap_year(year), "{}", year);
+        }
+        assert_eq!(expected, days_from_civil(2300, 1, 1).unwrap());
+    }
+
+    #[test]
+    fn test_epoch_ms() {
+        assert_eq!(epoch_ms((1970, 1, 1), (0, 0, 0), 0), Ok(0));
+        assert_eq!(
+            epoch_ms((2024, 3, 14), (2, 30, 40), 250),
+            Ok(1_710_383_440_250)
+        );
+        assert_eq!(
+            epoch_ms((2099, 12, 31), (23, 59, 59), 0),
+            Ok(4_102_444_799_000)
+        );
+        // leap second
+        assert_eq!(
+            epoch_ms((2016, 12, 31), (23, 59, 60), 0),
+            epoch_ms((2017, 1, 1), (0, 0, 0), 0)
+        );
+
+        assert_eq!(epoch_ms((2024, 3, 14), (24, 0, 0), 0), Err(()));
+        assert_eq!(epoch_ms((2024, 3, 14), (0, 60, 0), 0), Err(()));
+        assert_eq!(epoch_ms((2024, 3, 14), (0, 0, 61), 0), Err(()));
+        assert_eq!(epoch_ms((2024, 3, 14), (0, 0, 0), 1000), Err(()));
+        assert_eq!(epoch_ms((2024, 2, 30), (0, 0, 0), 0), Err(()));
+        // before the epoch
+        assert_eq!(epoch_ms((1969, 12, 31), (23, 59, 59), 0), Err(()));
+    }
+}
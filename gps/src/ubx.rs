@@ -0,0 +1,456 @@
+use crate::fix::{Fix, FixSource};
+use crate::nmea::{Mode, Status};
+use crate::reader::AsyncReader;
+use crate::time::epoch_ms;
+
+const SYNC1: u8 = 0xB5;
+const SYNC2: u8 = 0x62;
+
+const CLASS_NAV: u8 = 0x01;
+const CLASS_ACK: u8 = 0x05;
+const CLASS_CFG: u8 = 0x06;
+
+const NAV_PVT: u8 = 0x07;
+const ACK_NAK: u8 = 0x00;
+const ACK_ACK: u8 = 0x01;
+const CFG_PRT: u8 = 0x00;
+const CFG_MSG: u8 = 0x01;
+const CFG_RATE: u8 = 0x08;
+const CFG_RXM: u8 = 0x11;
+
+const NAV_PVT_LEN: usize = 92;
+// NAV-PVT is the largest message we decode, anything bigger is checksummed and skipped
+const MAX_PAYLOAD: usize = NAV_PVT_LEN;
+// a corrupt length field shouldn't swallow seconds of input, nothing we enable is this big
+const MAX_FRAME: usize = 1024;
+
+// header (sync, class, id, length) and checksum
+const FRAME_OVERHEAD: usize = 8;
+
+const MM_S_TO_KNOTS: f64 = 3600.0 / 1_852_000.0;
+
+/// Receiver power mode for CFG-RXM
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerMode {
+    Continuous,
+    PowerSave,
+    Eco,
+}
+
+/// Configuration messages sent to the receiver at boot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UBXCommand {
+    /// One navigation solution every `measurement_ms`, timestamps aligned to GPS time
+    CfgRate { measurement_ms: u16 },
+    /// UART `port` set to 8N1 at `baud`, with protocol masks for input and output
+    CfgPrt {
+        port: u8,
+        baud: u32,
+        in_proto: u16,
+        out_proto: u16,
+    },
+    CfgRxm { power_mode: PowerMode },
+    /// Output `class`/`id` once every `rate` navigation solutions on the current port, 0 disables
+    CfgMsg { class: u8, id: u8, rate: u8 },
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct NAVPVT {
+    pub itow: u32,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub min: u8,
+    pub sec: u8,
+    pub valid: u8,
+    pub time_accuracy: u32,
+    pub nano: i32,
+    pub fix_type: u8,
+    pub flags: u8,
+    pub satellites: u8,
+    /// 1e-7 degrees
+    pub lon: i32,
+    pub lat: i32,
+    /// mm
+    pub height: i32,
+    pub height_msl: i32,
+    pub horizontal_accuracy: u32,
+    pub vertical_accuracy: u32,
+    /// mm/s
+    pub vel_n: i32,
+    pub vel_e: i32,
+    pub vel_d: i32,
+    pub ground_speed: i32,
+    /// 1e-5 degrees
+    pub heading_motion: i32,
+    pub speed_accuracy: u32,
+    pub heading_accuracy: u32,
+    /// 0.01
+    pub pdop: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UBXMessage {
+    NAVPVT(NAVPVT),
+    Ack { class: u8, id: u8 },
+    Nak { class: u8, id: u8 },
+    Unknown { class: u8, id: u8 },
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct UBXStats {
+    /// Frames that passed validation
+    pub frames: u32,
+    /// Frames rejected for a bad checksum, length or field value
+    pub dropped: u32,
+}
+
+/// Fletcher checksum over class, id, length and payload
+fn checksum(data: &[u8]) -> (u8, u8) {
+    data.iter().fold((0, 0), |ck, &b| checksum_add(ck, b))
+}
+
+fn checksum_add((ck_a, ck_b): (u8, u8), byte: u8) -> (u8, u8) {
+    let ck_a = ck_a.wrapping_add(byte);
+    (ck_a, ck_b.wrapping_add(ck_a))
+}
+
+/// Frame a message into `buf`, returning the frame length
+pub(crate) fn encode(class: u8, id: u8, payload: &[u8], buf: &mut [u8]) -> Result<usize, ()> {
+    let len = payload.len() + FRAME_OVERHEAD;
+    if len > buf.len() || payload.len() > u16::MAX as usize {
+        return Err(());
+    }
+
+    buf[0] = SYNC1;
+    buf[1] = SYNC2;
+    buf[2] = class;
+    buf[3] = id;
+    buf[4..6].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+    buf[6..len - 2].copy_from_slice(payload);
+
+    let (ck_a, ck_b) = checksum(&buf[2..len - 2]);
+    buf[len - 2] = ck_a;
+    buf[len - 1] = ck_b;
+    Ok(len)
+}
+
+impl UBXCommand {
+    /// NAV-PVT, for `CfgMsg`
+    pub const NAV_PVT: (u8, u8) = (CLASS_NAV, NAV_PVT);
+
+    pub const PROTO_UBX: u16 = 0x0001;
+    pub const PROTO_NMEA: u16 = 0x0002;
+
+    /// Frame the command into `buf`, returning the frame length
+    #[allow(clippy::result_unit_err)]
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, ()> {
+        let mut payload = [0; 20];
+        let (id, len) = match *self {
+            UBXCommand::CfgRate { measurement_ms } => {
+                payload[0..2].copy_from_slice(&measurement_ms.to_le_bytes());
+                // one solution per measurement
+                payload[2..4].copy_from_slice(&1u16.to_le_bytes());
+                // align to GPS time
+                payload[4..6].copy_from_slice(&1u16.to_le_bytes());
+                (CFG_RATE, 6)
+            }
+            UBXCommand::CfgPrt {
+                port,
+                baud,
+                in_proto,
+                out_proto,
+            } => {
+                payload[0] = port;
+                // 8 data bits, no parity, 1 stop bit
+                payload[4..8].copy_from_slice(&0x0000_08D0u32.to_le_bytes());
+                payload[8..12].copy_from_slice(&baud.to_le_bytes());
+                payload[12..14].copy_from_slice(&in_proto.to_le_bytes());
+                payload[14..16].copy_from_slice(&out_proto.to_le_bytes());
+                (CFG_PRT, 20)
+            }
+            UBXCommand::CfgRxm { power_mode } => {
+                // reserved, must be 8
+                payload[0] = 0x08;
+                payload[1] = match power_mode {
+                    PowerMode::Continuous => 0,
+                    PowerMode::PowerSave => 1,
+                    PowerMode::Eco => 4,
+                };
+                (CFG_RXM, 2)
+            }
+            UBXCommand::CfgMsg { class, id, rate } => {
+                payload[0] = class;
+                payload[1] = id;
+                payload[2] = rate;
+                (CFG_MSG, 3)
+            }
+        };
+        encode(CLASS_CFG, id, &payload[..len], buf)
+    }
+}
+
+fn u16_at(payload: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([payload[offset], payload[offset + 1]])
+}
+
+fn u32_at(payload: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        payload[offset],
+        payload[offset + 1],
+        payload[offset + 2],
+        payload[offset + 3],
+    ])
+}
+
+fn i32_at(payload: &[u8], offset: usize) -> i32 {
+    u32_at(payload, offset) as i32
+}
+
+impl NAVPVT {
+    // validity flags
+    const VALID_DATE: u8 = 0x01;
+    const VALID_TIME: u8 = 0x02;
+    // fix flags
+    const GNSS_FIX_OK: u8 = 0x01;
+    const DIFF_SOLN: u8 = 0x02;
+
+    fn parse(payload: &[u8]) -> Result<Self, ()> {
+        if payload.len() != NAV_PVT_LEN {
+            return Err(());
+        }
+
+        Ok(Self {
+            itow: u32_at(payload, 0),
+            year: u16_at(payload, 4),
+            month: payload[6],
+            day: payload[7],
+            hour: payload[8],
+            min: payload[9],
+            sec: payload[10],
+            valid: payload[11],
+            time_accuracy: u32_at(payload, 12),
+            nano: i32_at(payload, 16),
+            fix_type: payload[20],
+            flags: payload[21],
+            satellites: payload[23],
+            lon: i32_at(payload, 24),
+            lat: i32_at(payload, 28),
+            height: i32_at(payload, 32),
+            height_msl: i32_at(payload, 36),
+            horizontal_accuracy: u32_at(payload, 40),
+            vertical_accuracy: u32_at(payload, 44),
+            vel_n: i32_at(payload, 48),
+            vel_e: i32_at(payload, 52),
+            vel_d: i32_at(payload, 56),
+            ground_speed: i32_at(payload, 60),
+            heading_motion: i32_at(payload, 64),
+            speed_accuracy: u32_at(payload, 68),
+            heading_accuracy: u32_at(payload, 72),
+            pdop: u16_at(payload, 76),
+        })
+    }
+
+    /// UTC time of the solution, ms since the unix epoch
+    pub fn time(&self) -> Option<u64> {
+        let mask = Self::VALID_DATE | Self::VALID_TIME;
+        if self.valid & mask != mask {
+            return None;
+        }
+
+        let seconds = epoch_ms(
+            (self.year as i32, self.month as u32, self.day as u32),
+            (self.hour as u32, self.min as u32, self.sec as u32),
+            0,
+        )
+        .ok()?;
+        // nano is signed, the solution can fall just before the second
+        seconds.checked_add_signed((self.nano as i64).div_euclid(1_000_000))
+    }
+
+    pub fn fix(&self) -> Fix {
+        let has_position = matches!(self.fix_type, 1..=4);
+        let mode = match self.fix_type {
+            0 | 5 => Mode::NotValid,
+            1 => Mode::Estimated,
+            2..=4 if self.flags & Self::DIFF_SOLN != 0 => Mode::Differential,
+            2..=4 => Mode::Autonomous,
+            _ => Mode::Unknown,
+        };
+        let status = if self.flags & Self::GNSS_FIX_OK != 0 {
+            Status::Active
+        } else {
+            Status::Void
+        };
+
+        Fix {
+            time: self.time(),
+            location: has_position.then_some((self.lat as f64 * 1e-7, self.lon as f64 * 1e-7)),
+            sog: has_position.then_some(self.ground_speed as f64 * MM_S_TO_KNOTS),
+            cog: has_position.then_some(self.heading_motion as f64 * 1e-5),
+            status,
+            mode,
+            hdop: None,
+            accuracy: has_position.then_some(self.horizontal_accuracy as f64 / 1000.0),
+            source: FixSource::Ubx,
+        }
+    }
+}
+
+impl UBXMessage {
+    fn parse(class: u8, id: u8, payload: &[u8]) -> Result<Self, ()> {
+        match (class, id) {
+            (CLASS_NAV, NAV_PVT) => Ok(UBXMessage::NAVPVT(NAVPVT::parse(payload)?)),
+            (CLASS_ACK, ACK_ACK | ACK_NAK) => {
+                // payload is the class and id of the acknowledged message
+                let &[acked_class, acked_id] = payload else {
+                    return Err(());
+                };
+                Ok(if id == ACK_ACK {
+                    UBXMessage::Ack {
+                        class: acked_class,
+                        id: acked_id,
+                    }
+                } else {
+                    UBXMessage::Nak {
+                        class: acked_class,
+                        id: acked_id,
+                    }
+                })
+            }
+            _ => Ok(UBXMessage::Unknown { class, id }),
+        }
+    }
+}
+
+/// Frames UBX messages out of a byte stream, skipping anything between frames
+/// (NMEA output, line noise).
+pub struct UBXReader<Reader, const N: usize>
+where
+    Reader: AsyncReader,
+{
+    reader: Reader,
+    buf: [u8; N],
+    read_ptr: usize,
+    write_ptr: usize,
+    payload: [u8; MAX_PAYLOAD],
+}
+
+impl<Reader, const N: usize> UBXReader<Reader, N>
+where
+    Reader: AsyncReader,
+{
+    pub fn new(reader: Reader) -> Self {
+        Self {
+            reader,
+            buf: [0; N],
+            read_ptr: 0,
+            write_ptr: 0,
+            payload: [0; MAX_PAYLOAD],
+        }
+    }
+
+    async fn next_byte(&mut self) -> Option<u8> {
+        if self.read_ptr == self.write_ptr {
+            match self.reader.read(&mut self.buf).await {
+                Ok(0) | Err(_) => return None,
+                Ok(len) => {
+                    self.read_ptr = 0;
+                    self.write_ptr = len;
+                }
+            }
+        }
+
+        let byte = self.buf[self.read_ptr];
+        self.read_ptr += 1;
+        Some(byte)
+    }
+
+    async fn next_frame(&mut self) -> Option<Result<UBXMessage, ()>> {
+        // skip to the start of the next frame
+        let mut previous = 0;
+        loop {
+            let byte = self.next_byte().await?;
+            if previous == SYNC1 && byte == SYNC2 {
+                break;
+            }
+            previous = byte;
+        }
+
+        let mut header = [0; 4];
+        for b in header.iter_mut() {
+            *b = self.next_byte().await?;
+        }
+        let mut ck = checksum(&header);
+        let [class, id, ..] = header;
+        let len = u16_at(&header, 2) as usize;
+        if len > MAX_FRAME {
+            return Some(Err(()));
+        }
+
+        for i in 0..len {
+            let byte = self.next_byte().await?;
+            ck = checksum_add(ck, byte);
+            if i < MAX_PAYLOAD {
+                self.payload[i] = byte;
+            }
+        }
+
+        let ck_a = self.next_byte().await?;
+        let ck_b = self.next_byte().await?;
+        if (ck_a, ck_b) != ck {
+            return Some(Err(()));
+        }
+
+        if len > MAX_PAYLOAD {
+            // valid, but too big for anything we decode
+            return Some(Ok(UBXMessage::Unknown { class, id }));
+        }
+        Some(UBXMessage::parse(class, id, &self.payload[..len]))
+    }
+}
+
+/// Read the next frame, returning None if the underlying reader fails.
+/// Frames that fail validation are counted in `stats` and skipped.
+pub async fn next_ubx_message<Reader, const N: usize>(
+    reader: &mut UBXReader<Reader, N>,
+    stats: &mut UBXStats,
+) -> Option<UBXMessage>
+where
+    Reader: AsyncReader,
+{
+    loop {
+        match reader.next_frame().await? {
+            Ok(message) => {
+                stats.frames += 1;
+                return Some(message);
+            }
+            Err(()) => {
+                stats.dropped += 1;
+            }
+        }
+    }
+}
+
+/// Read until the next NAV-PVT solution, returning None if the underlying reader fails.
+/// Backing off on failure is left to the caller.
+pub async fn next_ubx_update<Reader, const N: usize>(
+    reader: &mut UBXReader<Reader, N>,
+    stats: &mut UBXStats,
+) -> Option<Fix>
+where
+    Reader: AsyncReader,
+{
+    loop {
+        match next_ubx_message(reader, stats).await? {
+            UBXMessage::NAVPVT(pvt) => return Some(pvt.fix()),
+            UBXMessage::Nak { class, id } => {
+                log::warn!("UBX message {:02x}:{:02x} rejected", class, id);
+            }
+            _ => {
+                // valid, but not a message we use
+            }
+        }
+    }
+}
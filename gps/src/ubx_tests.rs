@@ -0,0 +1,274 @@
+mod tests {
+    extern crate std;
+
+    use crate::ubx::encode;
+    use crate::*;
+    use embassy_futures::block_on;
+    use std::vec;
+    use std::vec::Vec;
+
+    // 2024-03-14T02:30:40Z
+    const FIX_MS: u64 = 1_710_383_440_000;
+
+    struct SliceReader<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl AsyncReader for SliceReader<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+            let len = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    fn parse(data: &[u8], chunk: usize) -> (Vec<Fix>, UBXStats) {
+        let mut reader = UBXReader::<_, 16>::new(SliceReader { data, chunk });
+        let mut stats = UBXStats::default();
+        let mut result = Vec::new();
+        while let Some(fix) = block_on(next_ubx_update(&mut reader, &mut stats)) {
+            result.push(fix);
+        }
+        (result, stats)
+    }
+
+    fn messages(data: &[u8]) -> Vec<UBXMessage> {
+        let mut reader = UBXReader::<_, 16>::new(SliceReader { data, chunk: 5 });
+        let mut stats = UBXStats::default();
+        let mut result = Vec::new();
+        while let Some(message) = block_on(next_ubx_message(&mut reader, &mut stats)) {
+            result.push(message);
+        }
+        result
+    }
+
+    fn frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0; payload.len() + 8];
+        let len = encode(class, id, payload, &mut buf).unwrap();
+        assert_eq!(len, buf.len());
+        buf
+    }
+
+    fn command(command: UBXCommand) -> Vec<u8> {
+        let mut buf = [0; 64];
+        let len = command.encode(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    // NAV-PVT for a 3D fix heading west off Outer Harbor
+    fn pvt_payload() -> [u8; 92] {
+        let mut payload = [0; 92];
+        payload[4..6].copy_from_slice(&2024u16.to_le_bytes());
+        payload[6..11].copy_from_slice(&[3, 14, 2, 30, 40]);
+        // valid date, time, fully resolved
+        payload[11] = 0x07;
+        payload[16..20].copy_from_slice(&250_000_000i32.to_le_bytes());
+        // 3D fix, gnssFixOK
+        payload[20] = 3;
+        payload[21] = 0x01;
+        payload[23] = 12;
+        payload[24..28].copy_from_slice(&1_385_041_570i32.to_le_bytes());
+        payload[28..32].copy_from_slice(&(-349_564_040i32).to_le_bytes());
+        // 1.5m horizontal accuracy
+        payload[40..44].copy_from_slice(&1_500u32.to_le_bytes());
+        // 2675 mm/s is 5.2 knots
+        payload[60..64].copy_from_slice(&2_675i32.to_le_bytes());
+        payload[64..68].copy_from_slice(&27_250_000i32.to_le_bytes());
+        payload[76..78].copy_from_slice(&135u16.to_le_bytes());
+        payload
+    }
+
+    fn approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
+        (a - b).abs() < epsilon
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(
+            command(UBXCommand::CfgRate {
+                measurement_ms: 100
+            }),
+            [0xB5, 0x62, 0x06, 0x08, 0x06, 0x00, 0x64, 0x00, 0x01, 0x00, 0x01, 0x00, 0x7A, 0x12],
+        );
+        assert_eq!(
+            command(UBXCommand::CfgRxm {
+                power_mode: PowerMode::Continuous
+            }),
+            [0xB5, 0x62, 0x06, 0x11, 0x02, 0x00, 0x08, 0x00, 0x21, 0x91],
+        );
+        let (class, id) = UBXCommand::NAV_PVT;
+        assert_eq!(
+            command(UBXCommand::CfgMsg { class, id, rate: 1 }),
+            [0xB5, 0x62, 0x06, 0x01, 0x03, 0x00, 0x01, 0x07, 0x01, 0x13, 0x51],
+        );
+
+        let prt = command(UBXCommand::CfgPrt {
+            port: 1,
+            baud: 115_200,
+            in_proto: UBXCommand::PROTO_UBX | UBXCommand::PROTO_NMEA,
+            out_proto: UBXCommand::PROTO_UBX,
+        });
+        assert_eq!(prt.len(), 28);
+        assert_eq!(prt[..6], [0xB5, 0x62, 0x06, 0x00, 0x14, 0x00]);
+        assert_eq!(prt[6], 1);
+        assert_eq!(prt[10..14], 0x08D0u32.to_le_bytes());
+        assert_eq!(prt[14..18], 115_200u32.to_le_bytes());
+        assert_eq!(prt[18..22], [0x03, 0x00, 0x01, 0x00]);
+        // our own frames must pass our own checksum
+        assert_eq!(
+            messages(&prt),
+            [UBXMessage::Unknown {
+                class: 0x06,
+                id: 0x00
+            }]
+        );
+
+        // buffer too small
+        let mut buf = [0; 12];
+        assert_eq!(
+            UBXCommand::CfgPrt {
+                port: 1,
+                baud: 9600,
+                in_proto: 0,
+                out_proto: 0
+            }
+            .encode(&mut buf),
+            Err(())
+        );
+    }
+
+    #[test]
+    fn test_nav_pvt() {
+        let (fixes, stats) = parse(&frame(0x01, 0x07, &pvt_payload()), 7);
+        assert_eq!(stats, UBXStats { frames: 1, dropped: 0 });
+        assert_eq!(fixes.len(), 1);
+
+        let fix = fixes[0];
+        assert_eq!(fix.time, Some(FIX_MS + 250));
+        let (lat, lon) = fix.location.unwrap();
+        assert!(approx_eq(lat, -34.956404, 1e-7));
+        assert!(approx_eq(lon, 138.504157, 1e-7));
+        assert!(approx_eq(fix.sog.unwrap(), 5.2, 0.001));
+        assert!(approx_eq(fix.cog.unwrap(), 272.5, 1e-9));
+        assert_eq!(fix.accuracy, Some(1.5));
+        assert_eq!(fix.hdop, None);
+        assert_eq!(fix.status, Status::Active);
+        assert_eq!(fix.mode, Mode::Autonomous);
+        assert_eq!(fix.source, FixSource::Ubx);
+        assert!(fix.is_valid());
+    }
+
+    #[test]
+    fn test_nav_pvt_time() {
+        // solutions can land just before the second, nano is negative
+        let mut payload = pvt_payload();
+        payload[16..20].copy_from_slice(&(-100_000_000i32).to_le_bytes());
+        let (fixes, _) = parse(&frame(0x01, 0x07, &payload), 92);
+        assert_eq!(fixes[0].time, Some(FIX_MS - 100));
+
+        // no valid time until the receiver has the almanac
+        let mut payload = pvt_payload();
+        payload[11] = 0x01;
+        let (fixes, _) = parse(&frame(0x01, 0x07, &payload), 92);
+        assert_eq!(fixes[0].time, None);
+
+        // garbage date flagged valid
+        let mut payload = pvt_payload();
+        payload[6] = 13;
+        let (fixes, _) = parse(&frame(0x01, 0x07, &payload), 92);
+        assert_eq!(fixes[0].time, None);
+
+        // leap years
+        let mut payload = pvt_payload();
+        payload[6..11].copy_from_slice(&[2, 29, 0, 0, 0]);
+        payload[16..20].copy_from_slice(&0i32.to_le_bytes());
+        let (fixes, _) = parse(&frame(0x01, 0x07, &payload), 92);
+        assert_eq!(fixes[0].time, Some(1_709_164_800_000));
+    }
+
+    #[test]
+    fn test_nav_pvt_no_fix() {
+        let mut payload = pvt_payload();
+        payload[20] = 0;
+        payload[21] = 0;
+        let (fixes, _) = parse(&frame(0x01, 0x07, &payload), 92);
+        let fix = fixes[0];
+        assert_eq!(fix.location, None);
+        assert_eq!(fix.velocity(), None);
+        assert_eq!(fix.status, Status::Void);
+        assert_eq!(fix.mode, Mode::NotValid);
+        assert!(!fix.is_valid());
+        // the receiver clock is still good
+        assert_eq!(fix.time, Some(FIX_MS + 250));
+
+        // differential
+        let mut payload = pvt_payload();
+        payload[21] = 0x03;
+        let (fixes, _) = parse(&frame(0x01, 0x07, &payload), 92);
+        assert_eq!(fixes[0].mode, Mode::Differential);
+
+        // fix without gnssFixOK is outside the receiver's accuracy masks
+        let mut payload = pvt_payload();
+        payload[21] = 0x00;
+        let (fixes, _) = parse(&frame(0x01, 0x07, &payload), 92);
+        assert_eq!(fixes[0].status, Status::Void);
+        assert!(fixes[0].location.is_some());
+    }
+
+    #[test]
+    fn test_ack() {
+        let mut data = frame(0x05, 0x01, &[0x06, 0x08]);
+        data.extend(frame(0x05, 0x00, &[0x06, 0x00]));
+        // ack with the wrong length
+        data.extend(frame(0x05, 0x01, &[0x06]));
+
+        assert_eq!(
+            messages(&data),
+            [
+                UBXMessage::Ack {
+                    class: 0x06,
+                    id: 0x08
+                },
+                UBXMessage::Nak {
+                    class: 0x06,
+                    id: 0x00
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mixed_stream() {
+        let pvt = frame(0x01, 0x07, &pvt_payload());
+
+        let mut data = Vec::new();
+        // NMEA output left enabled on the port
+        data.extend(b"$GNRMC,023040.00,A,3457.38424,S,13830.24942,E,5.2,272.5,140324,,,A*6B\r\n");
+        data.extend(&pvt);
+        // NAV-SAT, too big to keep but still valid
+        data.extend(frame(0x01, 0x35, &[0x5A; 200]));
+        // line noise, including a stray sync byte
+        data.extend([0x00, 0xB5, 0xFF, 0x62, 0xB5]);
+        data.extend(&pvt);
+        // bad checksum
+        let mut corrupt = pvt.clone();
+        corrupt[40] ^= 0x10;
+        data.extend(corrupt);
+        // NAV-PVT with the wrong length
+        data.extend(frame(0x01, 0x07, &[0; 84]));
+        // corrupt length field, must resync on the following frame
+        data.extend([0xB5, 0x62, 0x01, 0x07, 0xFF, 0xFF]);
+        data.extend(&pvt);
+        // truncated
+        data.extend(&pvt[..50]);
+
+        for chunk in [1, 7, 16, 64] {
+            let (fixes, stats) = parse(&data, chunk);
+            assert_eq!(fixes.len(), 3, "chunk {}", chunk);
+            assert!(fixes.iter().all(|fix| fix.time == Some(FIX_MS + 250)));
+            assert_eq!(stats, UBXStats { frames: 4, dropped: 3 }, "chunk {}", chunk);
+        }
+    }
+}
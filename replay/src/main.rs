@@ -0,0 +1,201 @@
+// Replay an NMEA log, and optionally a script of UI events, through the engines on
+// GPS time, writing every state update the UI would have seen as a JSON line.
+//
+//   replay <log.nmea> [--events <script>] [--engine <name>] [--out <file>]
+//          [--resume <snapshot>] [--checkpoint <snapshot>]
+//
+// --resume starts the engine from a snapshot, --checkpoint writes one at the end of
+// the run, so a long log can be replayed in pieces or a state handed between devices.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::process::ExitCode;
+
+use embassy_futures::block_on;
+use gps::{next_update, AsyncReader, NMEAState, RingBuffer};
+
+use extreme_traits::{define_engines, MAX_MESSAGE_SIZE};
+
+mod replay;
+mod script;
+
+use replay::Replay;
+use script::{parse_script, EventTime, ScriptEvent};
+
+#[cfg(test)]
+mod replay_tests;
+
+define_engines! {
+    EngineType {
+        Race(extreme_race::Race),
+        TuneSpeed(extreme_tune::TuneSpeed<32>)
+    }
+}
+
+const USAGE: &str = "usage: replay <log.nmea> [--events <script>] [--engine <name>] [--out <file>]
+              [--resume <snapshot>] [--checkpoint <snapshot>]";
+
+struct Args {
+    log: String,
+    events: Option<String>,
+    engine: Option<String>,
+    out: Option<String>,
+    resume: Option<String>,
+    checkpoint: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, ()> {
+    let mut log = None;
+    let mut events = None;
+    let mut engine = None;
+    let mut out = None;
+    let mut resume = None;
+    let mut checkpoint = None;
+
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--events" => &mut events,
+            "--engine" => &mut engine,
+            "--out" => &mut out,
+            "--resume" => &mut resume,
+            "--checkpoint" => &mut checkpoint,
+            _ if arg.starts_with("--") || log.is_some() => return Err(()),
+            _ => {
+                log = Some(arg);
+                continue;
+            }
+        };
+        *value = Some(args.next().ok_or(())?);
+    }
+
+    Ok(Args {
+        log: log.ok_or(())?,
+        events,
+        engine,
+        out,
+        resume,
+        checkpoint,
+    })
+}
+
+struct StdReader<R: Read>(R);
+
+impl<R: Read> AsyncReader for StdReader<R> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        self.0.read(buf).map_err(|_| ())
+    }
+}
+
+/// Run the whole log through `replay`, returning the NMEA parser state
+fn run<R: Read, E: extreme_traits::RawEngine, W: Write>(
+    log: R,
+    replay: &mut Replay<E, W>,
+) -> io::Result<NMEAState> {
+    let mut state = NMEAState::default();
+    let mut ring_buffer = RingBuffer::<_, 128>::new(StdReader(log));
+    while let Some(fix) = block_on(next_update(&mut ring_buffer, &mut state)) {
+        replay.fix(&fix)?;
+    }
+    replay.finish()?;
+    Ok(state)
+}
+
+fn main() -> ExitCode {
+    let Ok(args) = parse_args(std::env::args().skip(1)) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    let log = match File::open(&args.log) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("{}: {}", args.log, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut events = Vec::new();
+    // selecting the engine is just the first UI event
+    if let Some(engine) = &args.engine {
+        if <EngineTypeLabels as extreme_traits::StringList>::index_of(engine).is_none() {
+            eprintln!(
+                "unknown engine {}, expected one of {:?}",
+                engine, EngineTypeVARIANTS
+            );
+            return ExitCode::FAILURE;
+        }
+        events.push(ScriptEvent {
+            time: EventTime::Relative(0),
+            payload: format!("{{\"index\":\"{}\"}}", engine).into_bytes(),
+            line: 0,
+        });
+    }
+    if let Some(path) = &args.events {
+        let script = match std::fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        };
+        match parse_script(&script) {
+            Ok(script) => events.extend(script),
+            Err(line) => {
+                eprintln!("{}:{}: expected <time> <json event>", path, line);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let out: Box<dyn Write> = match &args.out {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+
+    let mut replay = Replay::new(EngineType::default(), events, BufWriter::new(out));
+    if let Some(path) = &args.resume {
+        let snapshot = match std::fs::read(path) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        };
+        if replay.resume(&snapshot).is_err() {
+            eprintln!("{}: not a snapshot of these engines", path);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let result = run(log, &mut replay);
+    let state = match result {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("write failed: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(path) = &args.checkpoint {
+        let written = replay
+            .checkpoint()
+            .map_err(|_| io::Error::other("engine state too large"))
+            .and_then(|snapshot| std::fs::write(path, snapshot));
+        if let Err(e) = written {
+            eprintln!("{}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    eprintln!(
+        "{} sentences, {} dropped, {} untimed fixes, {} updates",
+        state.stats.sentences, state.stats.dropped, replay.untimed, replay.updates
+    );
+    ExitCode::SUCCESS
+}
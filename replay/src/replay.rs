@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use extreme_traits::{Fix, RawEngine, MAX_SNAPSHOT_SIZE};
+
+use crate::script::ScriptEvent;
+
+/// Drives an engine from logged fixes and scripted UI events, on GPS time. Timers
+/// requested by the engine fire when GPS time passes them, in order with the events,
+/// as the sleeper task would on the boat.
+pub struct Replay<E: RawEngine, W: Write> {
+    engine: E,
+    events: VecDeque<ScriptEvent>,
+    out: W,
+    // time of the first fix, relative script times count from here
+    start: Option<u64>,
+    // time of the last fix, events and timers have been delivered up to here
+    now: Option<u64>,
+    timer: Option<u64>,
+    /// State updates written so far
+    pub updates: usize,
+    /// Fixes dropped because nothing had told us the time yet
+    pub untimed: usize,
+}
+
+impl<E: RawEngine, W: Write> Replay<E, W> {
+    pub fn new(engine: E, events: Vec<ScriptEvent>, out: W) -> Self {
+        Self {
+            engine,
+            events: events.into(),
+            out,
+            start: None,
+            now: None,
+            timer: None,
+            updates: 0,
+            untimed: 0,
+        }
+    }
+
+    /// Start from an engine snapshot, with the timer it was waiting on
+    pub fn resume(&mut self, snapshot: &[u8]) -> Result<(), ()> {
+        let timer = self.engine.restore(snapshot)?;
+        self.set_timer(timer);
+        Ok(())
+    }
+
+    /// Snapshot the engine as it stands, to resume from later
+    pub fn checkpoint(&self) -> Result<Vec<u8>, ()> {
+        let mut buf = vec![0; MAX_SNAPSHOT_SIZE];
+        let len = self.engine.snapshot(&mut buf)?;
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    /// Deliver a fix, and any events or timers due before it. Fixes without a time
+    /// are given the time of the previous fix, as the handler would use its clock.
+    pub fn fix(&mut self, fix: &Fix) -> io::Result<()> {
+        let Some(timestamp) = fix.time.or(self.now) else {
+            self.untimed += 1;
+            return Ok(());
+        };
+        self.start.get_or_insert(timestamp);
+
+        self.advance(timestamp)?;
+        self.now = Some(timestamp);
+        let (update, timer) = self.engine.location_event(timestamp, fix);
+        self.set_timer(timer);
+        if update.is_some() {
+            self.write_state(timestamp)?;
+        }
+        Ok(())
+    }
+
+    /// Deliver the rest of the script once the log has run out. Timers are only run
+    /// up to the last event, an engine can keep asking for them forever.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let Some(start) = self.start else {
+            return self.out.flush();
+        };
+        let last = self
+            .events
+            .iter()
+            .map(|event| event.resolve(start))
+            .fold(self.now.unwrap_or(start), u64::max);
+        self.advance(last)?;
+        self.out.flush()
+    }
+
+    // deliver everything due at or before `until`, oldest first
+    fn advance(&mut self, until: u64) -> io::Result<()> {
+        let Some(start) = self.start else {
+            return Ok(());
+        };
+
+        loop {
+            // script order wins, an event timed before the last one goes straight after it
+            let event = self.events.front().map(|event| {
+                let time = event.resolve(start).max(self.now.unwrap_or(start));
+                (time, event.line)
+            });
+
+            match (event, self.timer) {
+                (Some((time, _)), Some(timer)) if timer < time && timer <= until => {
+                    self.fire_timer(timer)?
+                }
+                (Some((time, line)), _) if time <= until => self.fire_event(time, line)?,
+                (_, Some(timer)) if timer <= until => self.fire_timer(timer)?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn fire_timer(&mut self, timer: u64) -> io::Result<()> {
+        self.timer = None;
+        self.now = Some(self.now.map_or(timer, |now| now.max(timer)));
+        let (update, timer_next) = self.engine.timer_event(timer);
+        self.set_timer(timer_next);
+        if update.is_some() {
+            self.write_state(timer)?;
+        }
+        Ok(())
+    }
+
+    fn fire_event(&mut self, time: u64, line: usize) -> io::Result<()> {
+        let Some(event) = self.events.pop_front() else {
+            return Ok(());
+        };
+        self.now = Some(time);
+        match self.engine.external_event(time, &event.payload) {
+            Ok((update, timer)) => {
+                self.set_timer(timer);
+                if let Some(message) = update {
+                    self.write_message(time, &message)?;
+                }
+            }
+            Err(_) => eprintln!("line {}: event rejected by the engine", line),
+        }
+        Ok(())
+    }
+
+    // Some(0) cancels the timer, None leaves it alone
+    fn set_timer(&mut self, timer: Option<u64>) {
+        match timer {
+            Some(0) => self.timer = None,
+            Some(timer) => self.timer = Some(timer),
+            None => {}
+        }
+    }
+
+    fn write_state(&mut self, timestamp: u64) -> io::Result<()> {
+        match self.engine.to_vec() {
+            Ok(message) => self.write_message(timestamp, &message),
+            Err(_) => {
+                eprintln!("{}: failed to serialize engine state", timestamp);
+                Ok(())
+            }
+        }
+    }
+
+    // same shape as the websocket updates, less the clock fields
+    fn write_message(&mut self, timestamp: u64, message: &[u8]) -> io::Result<()> {
+        write!(self.out, "{{\"timestamp\":{},\"engine\":", timestamp)?;
+        self.out.write_all(message)?;
+        self.out.write_all(b"}\n")?;
+        self.updates += 1;
+        Ok(())
+    }
+}
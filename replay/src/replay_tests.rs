@@ -0,0 +1,250 @@
+mod tests {
+    use crate::replay::Replay;
+    use crate::script::{parse_script, EventTime};
+    use crate::{run, EngineType};
+    use extreme_traits::RawEngine;
+
+    const OUTER_HARBOR: &[u8] = include_bytes!("../../gps/test_data/outer_harbor.nmea");
+    // 2024-03-14T02:30:40Z
+    const FIRST_FIX_MS: u64 = 1_710_383_440_000;
+
+    // (timestamp, engine state) for each update written
+    fn replay(log: &[u8], script: &str) -> Vec<(u64, String)> {
+        let mut out = Vec::new();
+        let mut replay = Replay::new(
+            EngineType::default(),
+            parse_script(script).unwrap(),
+            &mut out,
+        );
+        run(log, &mut replay).unwrap();
+        let updates = replay.updates;
+
+        let lines: Vec<(u64, String)> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let rest = line.strip_prefix("{\"timestamp\":").unwrap();
+                let (timestamp, engine) = rest.split_once(",\"engine\":").unwrap();
+                (
+                    timestamp.parse().unwrap(),
+                    engine.strip_suffix('}').unwrap().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(lines.len(), updates);
+        lines
+    }
+
+    #[test]
+    fn test_script() {
+        let script = "\
+            # comment\n\
+            \n\
+            +2.5 {\"index\":\"Race\"}\n\
+            1710383460000   {\"event\":\"LinePort\"}\n";
+        let events = parse_script(script).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].time, EventTime::Relative(2_500));
+        assert_eq!(events[0].payload, b"{\"index\":\"Race\"}");
+        assert_eq!(events[0].line, 3);
+        assert_eq!(events[1].time, EventTime::Absolute(1_710_383_460_000));
+        assert_eq!(events[1].resolve(0), 1_710_383_460_000);
+        assert_eq!(events[0].resolve(FIRST_FIX_MS), FIRST_FIX_MS + 2_500);
+
+        // no payload, bad times
+        assert_eq!(parse_script("+1\n"), Err(1));
+        assert_eq!(parse_script("# ok\n+1 {}\nsoon {}\n"), Err(3));
+        assert_eq!(parse_script("+-1 {}\n"), Err(1));
+        assert_eq!(parse_script("-1 {}\n"), Err(1));
+    }
+
+    #[test]
+    fn test_selector_only() {
+        // the selector doesn't care where the boat is
+        assert!(replay(OUTER_HARBOR, "").is_empty());
+    }
+
+    #[test]
+    fn test_race() {
+        let updates = replay(OUTER_HARBOR, "+0 {\"index\":\"Race\"}\n");
+
+        // engine selected, then every fix with a velocity
+        assert_eq!(updates.len(), 13);
+        assert_eq!(updates[0].0, FIRST_FIX_MS);
+        assert!(updates[0].1.contains("\"fuck_yeah\":\"Race\""));
+        assert!(updates[1..]
+            .iter()
+            .all(|(_, state)| state.contains("\"state\":\"Active\"")));
+        assert!(updates.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(updates[12].0, FIRST_FIX_MS + 11_000);
+    }
+
+    #[test]
+    fn test_timer() {
+        // a 5s sequence started 2s in runs out between fixes on GPS time
+        let script = "\
+            +0 {\"index\":\"Race\"}\n\
+            +2 {\"event\":{\"BumpSeq\":{\"timestamp\":1710383442000,\"seconds\":5}}}\n\
+            +2.5 {\"event\":\"LineStbd\"}\n";
+        let updates = replay(OUTER_HARBOR, script);
+
+        let sequence = updates
+            .iter()
+            .position(|(_, state)| state.contains("InSequence"))
+            .unwrap();
+        assert_eq!(updates[sequence].0, FIRST_FIX_MS + 2_000);
+        assert!(updates[sequence].1.contains("\"start_time\":1710383447000"));
+
+        // the pin lands on the fix before it
+        let line = updates
+            .iter()
+            .position(|(_, state)| state.contains("\"line\":\"Stbd\""))
+            .unwrap();
+        assert_eq!(updates[line].0, FIRST_FIX_MS + 2_500);
+
+        let racing = updates
+            .iter()
+            .position(|(_, state)| state.contains("Racing"))
+            .unwrap();
+        assert_eq!(updates[racing].0, FIRST_FIX_MS + 7_000);
+        // the start fires before the fix at the same time
+        assert_eq!(updates[racing + 1].0, FIRST_FIX_MS + 7_000);
+        assert!(updates[racing + 1].1.contains("Racing"));
+        assert!(!updates[racing - 1].1.contains("Racing"));
+    }
+
+    #[test]
+    fn test_events_after_log() {
+        let script = "\
+            +0 {\"index\":\"Race\"}\n\
+            +60 {\"event\":\"LineStbd\"}\n\
+            +61 {\"event\":\"Bogus\"}\n";
+        let updates = replay(OUTER_HARBOR, script);
+        let (timestamp, state) = updates.last().unwrap();
+        assert_eq!(*timestamp, FIRST_FIX_MS + 60_000);
+        assert!(state.contains("\"line\":\"Stbd\""));
+    }
+
+    #[test]
+    fn test_untimed() {
+        // fixes before the receiver knows the time can't be placed
+        let mut log = b"$GNRMC,,V,,,,,,,,,,N*4D\r\n".to_vec();
+        log.extend_from_slice(OUTER_HARBOR);
+
+        let mut out = Vec::new();
+        let mut replay = Replay::new(EngineType::default(), Vec::new(), &mut out);
+        run(log.as_slice(), &mut replay).unwrap();
+        assert_eq!(replay.untimed, 1);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut buf = [0u8; 128];
+
+        // no engine selected
+        let mut engine = EngineType::default();
+        let len = engine.snapshot(&mut buf).unwrap();
+        assert_eq!(buf[0], 0);
+        assert_eq!(engine.restore(&buf[..len]), Ok(Some(0)));
+        assert!(matches!(engine, EngineType::Selector(_)));
+
+        // the engine goes by name
+        engine
+            .external_event(0, b"{\"index\":\"TuneSpeed\"}")
+            .unwrap();
+        let len = engine.snapshot(&mut buf).unwrap();
+        assert_eq!(&buf[..10], b"\x09TuneSpeed");
+        let mut restored = EngineType::default();
+        assert_eq!(restored.restore(&buf[..len]), Ok(Some(0)));
+        assert!(matches!(restored, EngineType::TuneSpeed(_)));
+
+        // an engine this build doesn't have
+        buf[1..10].copy_from_slice(b"TuneSpeeD");
+        assert!(restored.restore(&buf[..len]).is_err());
+        assert!(matches!(restored, EngineType::TuneSpeed(_)));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut buf = [0u8; 128];
+
+        // nothing selected, nothing saved
+        let mut engine = EngineType::default();
+        assert_eq!(engine.save(&mut buf), Ok(0));
+        assert_eq!(engine.load(&[]), Ok(()));
+        assert!(matches!(engine, EngineType::Selector(_)));
+
+        // the engine goes by name, not by where it is in the list
+        engine.external_event(0, b"{\"index\":\"Race\"}").unwrap();
+        engine
+            .external_event(
+                0,
+                b"{\"event\":{\"LineStbdAt\":{\"lat\":-34.95,\"lon\":138.5}}}",
+            )
+            .unwrap();
+        let len = engine.save(&mut buf).unwrap();
+        assert_eq!(&buf[..5], b"\x04Race");
+        let mut restored = EngineType::default();
+        assert_eq!(restored.load(&buf[..len]), Ok(()));
+        assert!(matches!(restored, EngineType::Race(_)));
+        assert_eq!(restored.save(&mut [0u8; 128]), Ok(len));
+
+        // an engine this build doesn't have
+        buf[1..5].copy_from_slice(b"RacE");
+        assert!(restored.load(&buf[..len]).is_err());
+        assert!(matches!(restored, EngineType::Race(_)));
+    }
+
+    #[test]
+    fn test_resume() {
+        // start a 5s sequence 2s in, and stop the first run at 4s
+        let script = "\
+            +0 {\"index\":\"Race\"}\n\
+            +1 {\"event\":\"LineStbd\"}\n\
+            +2 {\"event\":{\"BumpSeq\":{\"timestamp\":1710383442000,\"seconds\":5}}}\n";
+        let split = OUTER_HARBOR
+            .windows(6)
+            .enumerate()
+            .filter(|(_, window)| window == b"$GNRMC")
+            .nth(5)
+            .unwrap()
+            .0;
+        let (first, second) = OUTER_HARBOR.split_at(split);
+
+        let mut out = Vec::new();
+        let mut replay = Replay::new(
+            EngineType::default(),
+            parse_script(script).unwrap(),
+            &mut out,
+        );
+        run(first, &mut replay).unwrap();
+        let checkpoint = replay.checkpoint().unwrap();
+
+        // the second half picks up the line and the pending start
+        let mut out = Vec::new();
+        let mut replay = Replay::new(EngineType::default(), Vec::new(), &mut out);
+        replay.resume(&checkpoint).unwrap();
+        run(second, &mut replay).unwrap();
+        assert!(replay.resume(b"\x04Race\xff").is_err());
+        let resumed = String::from_utf8(out).unwrap();
+        let whole = replay_log(OUTER_HARBOR, script);
+
+        let racing = resumed
+            .lines()
+            .find(|line| line.contains("Racing"))
+            .unwrap();
+        assert!(racing.starts_with(&format!("{{\"timestamp\":{}", FIRST_FIX_MS + 7_000)));
+        assert_eq!(resumed.lines().last(), whole.lines().last());
+    }
+
+    fn replay_log(log: &[u8], script: &str) -> String {
+        let mut out = Vec::new();
+        let mut replay = Replay::new(
+            EngineType::default(),
+            parse_script(script).unwrap(),
+            &mut out,
+        );
+        run(log, &mut replay).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+}
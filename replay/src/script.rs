@@ -0,0 +1,80 @@
+/// When a scripted UI event is delivered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventTime {
+    /// GPS time, ms since the unix epoch, as logged by the handler
+    Absolute(u64),
+    /// ms after the first fix in the log
+    Relative(u64),
+}
+
+/// A UI event, as the web client would send it over the websocket
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptEvent {
+    pub time: EventTime,
+    pub payload: Vec<u8>,
+    /// Line in the script, for error reporting
+    pub line: usize,
+}
+
+impl ScriptEvent {
+    pub fn resolve(&self, start: u64) -> u64 {
+        match self.time {
+            EventTime::Absolute(time) => time,
+            EventTime::Relative(offset) => start + offset,
+        }
+    }
+}
+
+fn parse_time(token: &str) -> Result<EventTime, ()> {
+    match token.strip_prefix('+') {
+        // seconds after the first fix, fractions allowed
+        Some(seconds) => {
+            let seconds = seconds.parse::<f64>().map_err(|_| ())?;
+            if !seconds.is_finite() || seconds < 0.0 {
+                return Err(());
+            }
+            Ok(EventTime::Relative((seconds * 1000.0).round() as u64))
+        }
+        None => token
+            .parse::<u64>()
+            .map(EventTime::Absolute)
+            .map_err(|_| ()),
+    }
+}
+
+/// Parse an event script. Each line is a time followed by the JSON event:
+///
+/// ```text
+/// # select the race engine 5s in, then set the pin end
+/// +5 {"index":"Race"}
+/// 1710383460000 {"event":"LinePort"}
+/// ```
+///
+/// Times are either GPS ms since the epoch, or `+seconds` after the first fix.
+/// Blank lines and lines starting with `#` are ignored. Events are delivered in
+/// script order, an event timed before its predecessor goes straight after it.
+/// Returns the line number of the first bad line on failure.
+pub fn parse_script(script: &str) -> Result<Vec<ScriptEvent>, usize> {
+    let mut events = Vec::new();
+    for (index, line) in script.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (time, payload) = line.split_once(char::is_whitespace).ok_or(line_number)?;
+        let time = parse_time(time).map_err(|_| line_number)?;
+        let payload = payload.trim();
+        if payload.is_empty() {
+            return Err(line_number);
+        }
+
+        events.push(ScriptEvent {
+            time,
+            payload: payload.as_bytes().to_vec(),
+            line: line_number,
+        });
+    }
+    Ok(events)
+}
@@ -0,0 +1,40 @@
+use common::storage::{Storage, MAX_RECORD_SIZE};
+use embassy_rp::{
+    flash::{Blocking, Flash, ERASE_SIZE},
+    peripherals::FLASH,
+};
+
+// The Pico W's 2MB flash, the last sector is in the STORAGE region of memory.x and
+// clear of where the cyw43 firmware can be flashed
+const FLASH_SIZE: usize = 2 * 1024 * 1024;
+const STORAGE_OFFSET: u32 = (FLASH_SIZE - ERASE_SIZE) as u32;
+
+/// Keeps the saved state in the last sector of flash
+pub struct FlashStorage {
+    flash: Flash<'static, FLASH, Blocking, FLASH_SIZE>,
+}
+
+impl FlashStorage {
+    pub fn new(flash: FLASH) -> Self {
+        Self {
+            flash: Flash::new_blocking(flash),
+        }
+    }
+}
+
+impl Storage for FlashStorage {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        let len = buf.len().min(MAX_RECORD_SIZE);
+        self.flash
+            .blocking_read(STORAGE_OFFSET, &mut buf[..len])
+            .map_err(|e| log::error!("Failed to read flash: {:?}", e))?;
+        Ok(len)
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), ()> {
+        self.flash
+            .blocking_erase(STORAGE_OFFSET, STORAGE_OFFSET + ERASE_SIZE as u32)
+            .and_then(|_| self.flash.blocking_write(STORAGE_OFFSET, data))
+            .map_err(|e| log::error!("Failed to write flash: {:?}", e))
+    }
+}
@@ -17,7 +17,7 @@ use embassy_rp::{
     pio::{InterruptHandler, Pio},
     uart::{
         Async as UartAsync, Config as UartConfig, InterruptHandler as UartInterruptHandler, Uart,
-        UartRx, UartTx,
+        UartRx,
     },
     usb::{Driver, InterruptHandler as UsbInterruptHandler},
 };
@@ -37,17 +37,25 @@ use panic_probe as _;
 use static_cell::StaticCell;
 
 // Local modules
+mod flash_storage;
 mod http;
 mod network_tasks;
-mod nmea_parser;
 
 use crate::{
+    flash_storage::FlashStorage,
     http::HttpHandler,
     network_tasks::{dhcp_server_task, net_task, wifi_task},
-    nmea_parser::{next_update, AsyncReader, RingBuffer},
 };
+use gps::{next_update, AsyncReader, NMEAState, PMTKCommand, ReceiverConfig, RingBuffer};
 
-use extreme_traits::define_engines;
+// MTK receiver, RMC and GGA at 10Hz need more than the 9600 baud it boots with
+const GPS_CONFIG: ReceiverConfig = ReceiverConfig {
+    boot_baud: 9600,
+    baud: 115_200,
+    rate_ms: 100,
+};
+
+use extreme_traits::{define_engines, MAX_MESSAGE_SIZE};
 
 // type EngineType = extreme_race::Race;
 
@@ -60,8 +68,10 @@ define_engines! {
 
 // Constants
 const MAX_WEB_SOCKETS: usize = 4;
-const MAX_MESSAGE_SIZE: usize = 512;
-const SOCKET_BUFFER_SIZE: usize = MAX_MESSAGE_SIZE * 4;
+// the TCP buffers hold a couple of wrapped messages
+const SOCKET_BUFFER_SIZE: usize = 2048;
+const _: () =
+    assert!(SOCKET_BUFFER_SIZE >= 2 * (MAX_MESSAGE_SIZE + edge_net::ws::FrameHeader::MAX_LEN));
 
 bind_interrupts!(struct Irqs {
     PIO0_IRQ_0 => InterruptHandler<PIO0>;
@@ -165,6 +175,11 @@ async fn main(spawner: Spawner) {
     static HTTPD_HANDLER: StaticCell<HttpHandler<EngineType>> = StaticCell::new();
     let httpd_handler = HTTPD_HANDLER.init(HttpHandler::new(EngineType::default()));
 
+    let result = spawner.spawn(storage_task(FlashStorage::new(p.FLASH), httpd_handler));
+    if result.is_err() {
+        log::warn!("failed to spawn storage task");
+    }
+
     let result = spawner.spawn(httpd_task(stack, httpd_handler));
     if result.is_err() {
         log::warn!("failed to spawn httpd task");
@@ -176,26 +191,12 @@ async fn main(spawner: Spawner) {
     }
 
     let mut config = UartConfig::default();
-    config.baudrate = 9600;
-    let uart = Uart::new(
+    config.baudrate = GPS_CONFIG.boot_baud;
+    let mut uart = Uart::new(
         p.UART1, p.PIN_8, p.PIN_9, Irqs, p.DMA_CH2, p.DMA_CH1, config,
     );
-    let (mut uart_tx, uart_rx) = uart.split();
-
-    // Configure GPS
-    // Only generate GPRMC message twice per second
-    send_pmtk_command(&mut uart_tx, "PMTK314,0,1,0,0,0,0,0,0").await;
-    // Enable SBAS
-    send_pmtk_command(&mut uart_tx, "PMTK313,1").await;
-    // SBAS integrity mode
-    send_pmtk_command(&mut uart_tx, "PMTK319,1").await;
-
-    // Set new baud rate
-    // send_pmtk_command(&mut uart_tx, "PMTK251,115200").await;
-    // Need to wait a moment for the change to take effect
-    // Timer::after(Duration::from_millis(100)).await;
-    // config.baudrate = 115200;
-    // uart.set_config(config);
+    init_pmtk(&mut uart).await;
+    let (_uart_tx, uart_rx) = uart.split();
 
     let result = spawner.spawn(gps_task(uart_rx, httpd_handler));
     if result.is_err() {
@@ -218,6 +219,11 @@ pub async fn sleeper_task(handler: &'static HttpHandler<EngineType>) {
     handler.run_sleeper().await
 }
 
+#[embassy_executor::task]
+pub async fn storage_task(mut storage: FlashStorage, handler: &'static HttpHandler<EngineType>) {
+    handler.run_storage(&mut storage).await
+}
+
 struct UartReader(UartRx<'static, UART1, UartAsync>);
 impl AsyncReader for UartReader {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
@@ -234,9 +240,15 @@ pub async fn gps_task(
     handler: &'static HttpHandler<EngineType>,
 ) {
     let mut ring_buffer = RingBuffer::<UartReader, 32>::new(UartReader(rx));
+    let mut state = NMEAState::default();
     loop {
-        let (time, location, speed) = next_update(&mut ring_buffer).await;
-        handler.location_event(time, location, speed).await;
+        match next_update(&mut ring_buffer, &mut state).await {
+            Some(fix) => {
+                handler.set_gps_dropped(state.stats.dropped);
+                handler.location_event(fix).await;
+            }
+            None => Timer::after(Duration::from_millis(100)).await,
+        }
     }
 }
 
@@ -273,42 +285,30 @@ pub async fn httpd_task(
     }
 }
 
-async fn send_pmtk_command(tx: &mut UartTx<'static, UART1, UartAsync>, command: &str) {
-    // Calculate checksum
-    let checksum = command.bytes().fold(0u8, |acc, b| acc ^ b);
+/// Switch the receiver to the configured baud and fix rate, RMC and GGA only
+async fn init_pmtk(uart: &mut Uart<'static, UART1, UartAsync>) {
+    send_pmtk_command(uart, GPS_CONFIG.pmtk_baud()).await;
 
-    // We'll use a static buffer since we're in no_std
-    let mut buffer: [u8; 64] = [0; 64];
-    let mut pos = 0;
-
-    // Build command manually
-    buffer[pos] = b'$';
-    pos += 1;
-    for &byte in command.as_bytes() {
-        buffer[pos] = byte;
-        pos += 1;
+    // Need to wait a moment for the change to take effect
+    Timer::after(Duration::from_millis(100)).await;
+    uart.set_baudrate(GPS_CONFIG.baud);
+
+    for command in GPS_CONFIG.pmtk_setup() {
+        send_pmtk_command(uart, command).await;
     }
-    buffer[pos] = b'*';
-    pos += 1;
-
-    // Convert checksum to hex (manual implementation)
-    let hex_chars = [
-        b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9', b'A', b'B', b'C', b'D', b'E',
-        b'F',
-    ];
-    buffer[pos] = hex_chars[(checksum >> 4) as usize];
-    pos += 1;
-    buffer[pos] = hex_chars[(checksum & 0xF) as usize];
-    pos += 1;
-
-    // Add CR+LF
-    buffer[pos] = b'\r';
-    pos += 1;
-    buffer[pos] = b'\n';
-    pos += 1;
-
-    // Send command
-    if let Err(e) = tx.write(&buffer[..pos]).await {
+}
+
+async fn send_pmtk_command(uart: &mut Uart<'static, UART1, UartAsync>, command: PMTKCommand) {
+    let mut buffer: [u8; 64] = [0; 64];
+    let len = match command.encode(&mut buffer) {
+        Ok(len) => len,
+        Err(_) => {
+            log::error!("Failed to encode GPS command: {:?}", command);
+            return;
+        }
+    };
+
+    if let Err(e) = uart.write(&buffer[..len]).await {
         log::error!("Failed to send GPS command: {:?}", e);
     }
 }
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use crate::sim::{parse_course, SimConfig};
+use crate::source::GpsSource;
+
+// NMEA 0183 over IP, as used by OpenCPN and most chartplotter gateways
+pub const DEFAULT_NMEA_PORT: u16 = 10110;
+const DEFAULT_STATE_FILE: &str = "extreme-state.bin";
+
+pub const USAGE: &str = "\
+usage: tgt-std [--state <path>] [GPS source]
+  --state <path>      where the race setup is kept, default extreme-state.bin
+  --tcp <host:port>   NMEA stream from a TCP server, e.g. a chartplotter gateway
+  --udp [port]        NMEA broadcast, port defaults to 10110
+  --serial <path>     NMEA from a serial device, set the baud with stty first
+  --file <path>       NMEA log, replayed at the speed it was recorded
+  --sim [course]      simulated boat sailing a course file, or around a start line
+    --speed <knots>   boat speed, default 6
+    --noise <metres>  position noise, default 1";
+
+fn parse_number(value: Option<String>) -> Result<f64, ()> {
+    let value = value.ok_or(())?.parse::<f64>().map_err(|_| ())?;
+    if value.is_finite() && value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(())
+    }
+}
+
+pub struct Args {
+    pub source: Option<GpsSource>,
+    pub state: PathBuf,
+}
+
+impl Args {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, ()> {
+        let mut args = args.peekable();
+        let mut source = None;
+        let mut state = PathBuf::from(DEFAULT_STATE_FILE);
+
+        while let Some(flag) = args.next() {
+            let next = match flag.as_str() {
+                "--tcp" => {
+                    let addr = args.next().ok_or(())?;
+                    // resolve once, a feed on the LAN shouldn't move
+                    let addr = std::net::ToSocketAddrs::to_socket_addrs(&addr)
+                        .map_err(|_| ())?
+                        .next()
+                        .ok_or(())?;
+                    GpsSource::Tcp(addr)
+                }
+                "--udp" => match args.next_if(|arg| !arg.starts_with("--")) {
+                    Some(port) => GpsSource::Udp(port.parse().map_err(|_| ())?),
+                    None => GpsSource::Udp(DEFAULT_NMEA_PORT),
+                },
+                "--serial" => GpsSource::Serial(args.next().ok_or(())?.into()),
+                "--file" => GpsSource::File(args.next().ok_or(())?.into()),
+                "--state" => {
+                    state = args.next().ok_or(())?.into();
+                    continue;
+                }
+                "--sim" => {
+                    let mut config = SimConfig::default();
+                    if let Some(path) = args.next_if(|arg| !arg.starts_with("--")) {
+                        let course = std::fs::read_to_string(&path).map_err(|e| {
+                            eprintln!("{}: {}", path, e);
+                        })?;
+                        config.course = parse_course(&course).map_err(|line| {
+                            eprintln!("{}:{}: expected <heading> <seconds> [speed]", path, line);
+                        })?;
+                    }
+                    GpsSource::Sim(config)
+                }
+                // simulator settings, must follow --sim
+                "--speed" | "--noise" => {
+                    let Some(GpsSource::Sim(config)) = &mut source else {
+                        return Err(());
+                    };
+                    let value = parse_number(args.next())?;
+                    if flag == "--speed" {
+                        config.speed = value;
+                    } else {
+                        config.noise = value;
+                    }
+                    continue;
+                }
+                _ => return Err(()),
+            };
+
+            // one source at a time
+            if source.replace(next).is_some() {
+                return Err(());
+            }
+        }
+        Ok(Args { source, state })
+    }
+}
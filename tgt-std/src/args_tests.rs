@@ -0,0 +1,52 @@
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::args::Args;
+    use crate::sim::START_COURSE;
+    use crate::source::GpsSource;
+
+    fn parse(args: &[&str]) -> Result<Args, ()> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn args(args: &[&str]) -> Result<Option<GpsSource>, ()> {
+        parse(args).map(|args| args.source)
+    }
+
+    #[test]
+    fn test_args() {
+        assert_eq!(args(&[]), Ok(None));
+        assert_eq!(args(&["--udp"]), Ok(Some(GpsSource::Udp(10110))));
+        assert_eq!(args(&["--udp", "2000"]), Ok(Some(GpsSource::Udp(2000))));
+        assert_eq!(
+            args(&["--tcp", "127.0.0.1:10110"]),
+            Ok(Some(GpsSource::Tcp("127.0.0.1:10110".parse().unwrap())))
+        );
+
+        let Ok(Some(GpsSource::Sim(config))) = args(&["--sim", "--speed", "4.5", "--noise", "0"])
+        else {
+            panic!("expected the simulator");
+        };
+        assert_eq!(config.course, START_COURSE);
+        assert_eq!(config.speed, 4.5);
+        assert_eq!(config.noise, 0.0);
+
+        // settings without the simulator, two sources, nonsense
+        assert_eq!(args(&["--speed", "4"]), Err(()));
+        assert_eq!(args(&["--udp", "--file", "log.nmea"]), Err(()));
+        assert_eq!(args(&["--sim", "--noise", "-1"]), Err(()));
+        assert_eq!(args(&["--tcp"]), Err(()));
+        assert_eq!(args(&["log.nmea"]), Err(()));
+    }
+
+    #[test]
+    fn test_state() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.state, PathBuf::from("extreme-state.bin"));
+
+        let args = parse(&["--state", "/tmp/race.bin", "--udp"]).unwrap();
+        assert_eq!(args.state, PathBuf::from("/tmp/race.bin"));
+        assert_eq!(args.source, Some(GpsSource::Udp(10110)));
+        assert!(parse(&["--state"]).is_err());
+    }
+}
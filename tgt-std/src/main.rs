@@ -19,6 +19,22 @@ use static_cell::StaticCell;
 
 // Local modules
 // mod http;
+mod args;
+mod sim;
+mod source;
+mod storage;
+use args::Args;
+use source::gps_task;
+use storage::FileStorage;
+
+#[cfg(test)]
+mod args_tests;
+#[cfg(test)]
+mod message_tests;
+#[cfg(test)]
+mod sim_tests;
+#[cfg(test)]
+mod test_helpers;
 
 use common::http::{HttpHandler, MAX_WEB_SOCKETS, SOCKET_BUFFER_SIZE};
 
@@ -40,6 +56,14 @@ define_engines! {
 //     .init();
 
 fn main() {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(_) => {
+            eprintln!("{}", args::USAGE);
+            std::process::exit(1);
+        }
+    };
+
     static HTTPD_HANDLER: StaticCell<HttpHandler<EngineType>> = StaticCell::new();
     let httpd_handler = HTTPD_HANDLER.init(HttpHandler::new(EngineType::default()));
 
@@ -60,6 +84,19 @@ fn main() {
         if result.is_err() {
             log::warn!("failed to spawn sleeper task");
         }
+
+        let storage = FileStorage { path: args.state };
+        let result = spawner.spawn(storage_task(storage, httpd_handler));
+        if result.is_err() {
+            log::warn!("failed to spawn storage task");
+        }
+
+        if let Some(source) = args.source {
+            let result = spawner.spawn(gps_task(source, stack, httpd_handler));
+            if result.is_err() {
+                log::warn!("failed to spawn gps task");
+            }
+        }
     });
 }
 
@@ -68,6 +105,11 @@ pub async fn sleeper_task(handler: &'static HttpHandler<EngineType>) {
     handler.run_sleeper().await
 }
 
+#[embassy_executor::task]
+pub async fn storage_task(mut storage: FileStorage, handler: &'static HttpHandler<EngineType>) {
+    handler.run_storage(&mut storage).await
+}
+
 #[embassy_executor::task]
 pub async fn httpd_task(stack: &'static Stack, handler: &'static HttpHandler<EngineType>) -> ! {
     // let buffers = TcpBuffers::<MAX_WEB_SOCKETS, SOCKET_BUFFER_SIZE, SOCKET_BUFFER_SIZE>::new();
@@ -0,0 +1,136 @@
+mod tests {
+    use common::http::wrap_message;
+    use extreme_traits::{RawEngine, MAX_MESSAGE_SIZE};
+
+    use crate::test_helpers::{event, fix};
+    use crate::EngineType;
+
+    // what the clients get with room to spare, whatever the engine is up to
+    const MAX_UPDATE: usize = MAX_MESSAGE_SIZE * 7 / 8;
+
+    const START: u64 = 1_710_383_700_000;
+
+    // the engine's message as the clients get it, a day since the clock was synced
+    // and every sentence dropped that could be
+    fn check_size(engine: &EngineType, timestamp: u64) {
+        let message = engine.to_vec().unwrap();
+        let update = wrap_message(&message, timestamp, Some((86_400_000, -500)), u32::MAX);
+        let len = update.unwrap().len();
+        assert!(len <= MAX_UPDATE, "{}", len);
+    }
+
+    #[test]
+    fn test_race_message_size() {
+        let mut engine = EngineType::default();
+        event(&mut engine, r#"{"index":"Race"}"#);
+        event(
+            &mut engine,
+            r#"{"event":{"LineStbdAt":{"lat":-34.956404,"lon":138.503427}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"LinePortAt":{"lat":-34.957152,"lon":138.503438}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"Geometry":{"bow":1.234567,"beam":-0.345678}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"Wind":{"direction":283.123456}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"Sequence":{"sequence":"ThreeTwoOne"}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"MarkAt":{"lat":-34.951234,"lon":138.501234}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"MarkAt":{"lat":-34.959876,"lon":138.509876}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"FinishStbdAt":{"lat":-34.951234,"lon":138.501234}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"FinishPortAt":{"lat":-34.951876,"lon":138.501876}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"Rating":{"rating":{"TimeOnDistance":{"rating":-123.456789}}}}}"#,
+        );
+        event(
+            &mut engine,
+            r#"{"event":{"TargetRating":{"rating":{"TimeOnDistance":{"rating":612.3456789}}}}}"#,
+        );
+
+        // over the line in the last minute
+        let bump = r#"{"event":{"BumpSeq":{"timestamp":1710383520000,"seconds":180}}}"#;
+        let (_, mut timer) = engine.external_event(0, bump.as_bytes()).unwrap();
+        engine.location_event(
+            START - 49_877,
+            &fix((-34.956100, 138.503331), 5.123456, 91.234567),
+        );
+        check_size(&engine, START - 49_877);
+
+        // racing, with a start report and the next mark
+        while let Some(signal) = timer.filter(|&signal| signal < START) {
+            timer = engine.timer_event(signal).1;
+        }
+        engine.location_event(
+            START - 877,
+            &fix((-34.956789, 138.503531), 5.123456, 271.234567),
+        );
+        engine.timer_event(START);
+        engine.location_event(
+            START + 2_345,
+            &fix((-34.956789, 138.503329), 5.123456, 271.234567),
+        );
+        let EngineType::Race(race) = &engine else {
+            panic!("Engine was not Race as expected");
+        };
+        assert!(race.start.is_some() && race.eta().is_some() && race.time_owed().is_some());
+        check_size(&engine, START + 2_345);
+    }
+
+    #[test]
+    fn test_tune_speed_message_size() {
+        let mut engine = EngineType::default();
+        event(&mut engine, r#"{"index":"TuneSpeed"}"#);
+        event(
+            &mut engine,
+            r#"{"event":{"Wind":{"direction":183.123456}}}"#,
+        );
+
+        // a full history of tacks, each one slow to build back
+        let location = (-34.956100, 138.503331);
+        let mut timestamp = START;
+        let mut heading = 228.765432;
+        for step in [-15.123456, 15.123456, -15.123456, 15.123456] {
+            for _ in 0..20 {
+                engine.location_event(timestamp, &fix(location, 6.123456, heading));
+                timestamp += 1_000;
+            }
+            for i in 1..=6 {
+                heading += step;
+                let speed = 6.123456 - 0.5 * i as f64;
+                engine.location_event(timestamp, &fix(location, speed, heading));
+                timestamp += 1_000;
+            }
+            for i in 1..=30 {
+                let speed = (3.123456 + 0.1 * i as f64).min(6.123456);
+                engine.location_event(timestamp, &fix(location, speed, heading));
+                timestamp += 1_000;
+            }
+        }
+        let EngineType::TuneSpeed(tune) = &engine else {
+            panic!("Engine was not TuneSpeed as expected");
+        };
+        assert!(tune.manoeuvres.history.is_full());
+        check_size(&engine, timestamp);
+    }
+}
@@ -0,0 +1,222 @@
+use core::f64::consts::PI;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use embassy_time::{Duration, Instant, Timer};
+use extreme_traits::{Fix, FixMode, FixStatus};
+
+use common::http::HttpHandler;
+
+use crate::EngineType;
+
+const FIX_INTERVAL_MS: u64 = 100;
+const R: f64 = 6371e3; // radius of earth in meters
+const KNOTS_TO_M_S: f64 = 1852.0 / 3600.0;
+// a dinghy tacks through 90 degrees in about 8s
+const TURN_RATE: f64 = 12.0;
+// speed lost while the boat is turning
+const TURN_SPEED: f64 = 0.6;
+// time constant of the boat speeding up or slowing down, seconds
+const ACCELERATION_S: f64 = 4.0;
+
+// Outer Harbor, the committee boat end of the line
+const START_LOCATION: (f64, f64) = (-34.956404, 138.504157);
+
+/// One leg of a course: hold `heading` for `seconds` at `speed` times the boat speed.
+/// The boat turns onto each new heading at its turn rate, losing speed as it goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leg {
+    pub heading: f64,
+    pub seconds: f64,
+    pub speed: f64,
+}
+
+const fn leg(heading: f64, seconds: f64, speed: f64) -> Leg {
+    Leg {
+        heading,
+        seconds,
+        speed,
+    }
+}
+
+/// Wind from the north, with the line running west from the committee boat. The boat
+/// runs the line so both ends can be pinged, bears away below it, then beats back up
+/// through it and runs home. Legs even out so the course repeats in the same place.
+pub const START_COURSE: &[Leg] = &[
+    // committee boat to the pin, and back, less what the gybe costs
+    leg(270.0, 60.0, 1.0),
+    leg(90.0, 70.0, 1.0),
+    // bear away below the line
+    leg(180.0, 40.0, 1.1),
+    // port tack approach, then tack onto starboard through the line
+    leg(45.0, 30.0, 0.8),
+    leg(315.0, 60.0, 0.8),
+    leg(45.0, 30.0, 0.8),
+    // run back to the committee boat
+    leg(180.0, 22.0, 1.1),
+];
+
+/// Parse a course, one leg per line: `<heading> <seconds> [speed]`, where `speed`
+/// is a fraction of the boat speed and defaults to 1. Blank lines and lines starting
+/// with `#` are ignored. Returns the line number of the first bad line on failure.
+pub fn parse_course(course: &str) -> Result<Vec<Leg>, usize> {
+    let mut legs = Vec::new();
+    for (index, line) in course.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Result<Vec<f64>, _> = line.split_whitespace().map(str::parse).collect();
+        let leg = match fields.as_deref() {
+            Ok(&[heading, seconds]) => leg(heading, seconds, 1.0),
+            Ok(&[heading, seconds, speed]) => leg(heading, seconds, speed),
+            _ => return Err(index + 1),
+        };
+        if !(0.0..=360.0).contains(&leg.heading) || leg.seconds <= 0.0 || leg.speed < 0.0 {
+            return Err(index + 1);
+        }
+        legs.push(leg);
+    }
+
+    if legs.is_empty() {
+        return Err(0);
+    }
+    Ok(legs)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimConfig {
+    pub course: Vec<Leg>,
+    /// Boat speed on a leg with a speed of 1, knots
+    pub speed: f64,
+    /// Standard deviation of the position error, metres. SOG is given a tenth of this
+    /// in knots, COG this many degrees.
+    pub noise: f64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            course: START_COURSE.to_vec(),
+            speed: 6.0,
+            noise: 1.0,
+        }
+    }
+}
+
+/// A boat sailing a course, one fix at a time
+pub struct Sim {
+    config: SimConfig,
+    leg: usize,
+    // seconds left on the current leg
+    remaining: f64,
+    // true position, degrees
+    location: (f64, f64),
+    heading: f64,
+    // knots
+    speed: f64,
+    time: u64,
+    seed: u32,
+}
+
+impl Sim {
+    pub fn new(config: SimConfig, time: u64) -> Self {
+        let first = config.course[0];
+        Self {
+            leg: 0,
+            remaining: first.seconds,
+            location: START_LOCATION,
+            heading: first.heading,
+            speed: config.speed * first.speed,
+            time,
+            seed: 1,
+            config,
+        }
+    }
+
+    // approximately normal, from the sum of uniforms
+    fn gaussian(&mut self) -> f64 {
+        let mut sum = 0.0;
+        for _ in 0..12 {
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+            sum += self.seed as f64 / u32::MAX as f64;
+        }
+        sum - 6.0
+    }
+
+    /// Move the boat on by one fix interval
+    pub fn step(&mut self) {
+        let dt = FIX_INTERVAL_MS as f64 / 1000.0;
+        let leg = self.config.course[self.leg];
+
+        // turn the short way onto the leg's heading
+        let error = (leg.heading - self.heading + 540.0) % 360.0 - 180.0;
+        let turn = error.clamp(-TURN_RATE * dt, TURN_RATE * dt);
+        self.heading = (self.heading + turn + 360.0) % 360.0;
+
+        let mut target = self.config.speed * leg.speed;
+        if error.abs() > TURN_RATE * dt {
+            target *= TURN_SPEED;
+        }
+        self.speed += (target - self.speed) * dt / ACCELERATION_S;
+
+        let distance = self.speed * KNOTS_TO_M_S * dt;
+        let (lat, lon) = self.location;
+        let heading = self.heading * PI / 180.0;
+        let lat = lat + (distance * heading.cos() / R) * 180.0 / PI;
+        let lon = lon + (distance * heading.sin() / (R * (lat * PI / 180.0).cos())) * 180.0 / PI;
+        self.location = (lat, lon);
+        self.time += FIX_INTERVAL_MS;
+
+        self.remaining -= dt;
+        if self.remaining <= 0.0 {
+            self.leg = (self.leg + 1) % self.config.course.len();
+            self.remaining += self.config.course[self.leg].seconds;
+        }
+    }
+
+    /// The fix a receiver on the boat would report now
+    pub fn fix(&mut self) -> Fix {
+        let noise = self.config.noise;
+        let north = self.gaussian() * noise;
+        let east = self.gaussian() * noise;
+        let (lat, lon) = self.location;
+        let lat_error = north / R * 180.0 / PI;
+        let lon_error = east / (R * (lat * PI / 180.0).cos()) * 180.0 / PI;
+
+        let sog = (self.speed + self.gaussian() * noise / 10.0).max(0.0);
+        let cog = (self.heading + self.gaussian() * noise + 360.0) % 360.0;
+
+        Fix {
+            time: Some(self.time),
+            location: Some((lat + lat_error, lon + lon_error)),
+            sog: Some(sog),
+            cog: Some(cog),
+            status: FixStatus::Active,
+            mode: FixMode::Autonomous,
+            hdop: Some(0.9),
+            accuracy: Some(noise),
+            ..Default::default()
+        }
+    }
+}
+
+/// Sail the course forever, in real time
+pub async fn run(config: SimConfig, handler: &'static HttpHandler<EngineType>) -> ! {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or(0);
+    // fixes land on the interval, as a receiver's do
+    let mut sim = Sim::new(config, now - now % FIX_INTERVAL_MS);
+
+    let mut next = Instant::now();
+    loop {
+        handler.location_event(sim.fix()).await;
+        sim.step();
+        next += Duration::from_millis(FIX_INTERVAL_MS);
+        Timer::at(next).await;
+    }
+}
@@ -0,0 +1,164 @@
+mod tests {
+    use crate::sim::{parse_course, Leg, Sim, SimConfig, START_COURSE};
+
+    const START_MS: u64 = 1_710_383_440_000;
+    const METRES_PER_DEGREE: f64 = 6371e3 * core::f64::consts::PI / 180.0;
+
+    fn quiet(course: &[Leg]) -> Sim {
+        let config = SimConfig {
+            course: course.to_vec(),
+            noise: 0.0,
+            ..Default::default()
+        };
+        Sim::new(config, START_MS)
+    }
+
+    // metres between two fixes
+    fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+        let north = (a.0 - b.0) * METRES_PER_DEGREE;
+        let east = (a.1 - b.1) * METRES_PER_DEGREE * (a.0.to_radians()).cos();
+        (north * north + east * east).sqrt()
+    }
+
+    #[test]
+    fn test_parse_course() {
+        let course = "\
+            # beat and run\n\
+            \n\
+            45 30 0.8\n\
+            315 30 0.8\n\
+            180 42\n";
+        assert_eq!(
+            parse_course(course),
+            Ok(vec![
+                Leg {
+                    heading: 45.0,
+                    seconds: 30.0,
+                    speed: 0.8
+                },
+                Leg {
+                    heading: 315.0,
+                    seconds: 30.0,
+                    speed: 0.8
+                },
+                Leg {
+                    heading: 180.0,
+                    seconds: 42.0,
+                    speed: 1.0
+                },
+            ])
+        );
+
+        assert_eq!(parse_course("# nothing\n"), Err(0));
+        assert_eq!(parse_course("45 30\n45\n"), Err(2));
+        assert_eq!(parse_course("45 30 1 2\n"), Err(1));
+        assert_eq!(parse_course("400 30\n"), Err(1));
+        assert_eq!(parse_course("45 0\n"), Err(1));
+        assert_eq!(parse_course("north 30\n"), Err(1));
+    }
+
+    #[test]
+    fn test_sailing() {
+        let mut sim = quiet(&[Leg {
+            heading: 90.0,
+            seconds: 60.0,
+            speed: 1.0,
+        }]);
+        let first = sim.fix();
+        assert_eq!(first.time, Some(START_MS));
+        assert!(first.is_valid());
+
+        // 6 knots for a minute is a tenth of a mile
+        for _ in 0..600 {
+            sim.step();
+        }
+        let fix = sim.fix();
+        assert_eq!(fix.time, Some(START_MS + 60_000));
+        assert_eq!(fix.sog, Some(6.0));
+        assert_eq!(fix.cog, Some(90.0));
+        let (start, end) = (first.location.unwrap(), fix.location.unwrap());
+        assert!((distance(start, end) - 185.2).abs() < 0.1);
+        assert!(end.1 > start.1);
+    }
+
+    #[test]
+    fn test_tack() {
+        let mut sim = quiet(&[
+            Leg {
+                heading: 45.0,
+                seconds: 10.0,
+                speed: 1.0,
+            },
+            Leg {
+                heading: 315.0,
+                seconds: 30.0,
+                speed: 1.0,
+            },
+        ]);
+        for _ in 0..100 {
+            sim.step();
+        }
+
+        // turns the short way, through the wind, and slows down doing it
+        let mut slowest: f64 = 6.0;
+        for _ in 0..80 {
+            sim.step();
+            let fix = sim.fix();
+            let cog = fix.cog.unwrap();
+            assert!(cog <= 45.0 || cog >= 315.0, "cog {}", cog);
+            slowest = slowest.min(fix.sog.unwrap());
+        }
+        assert_eq!(sim.fix().cog, Some(315.0));
+        assert!(slowest < 5.0, "slowest {}", slowest);
+
+        // and speeds back up
+        for _ in 0..200 {
+            sim.step();
+        }
+        assert!(sim.fix().sog.unwrap() > 5.9);
+    }
+
+    #[test]
+    fn test_start_course() {
+        // the course comes back to the committee boat, so it can run all day
+        let mut sim = quiet(START_COURSE);
+        let start = sim.fix().location.unwrap();
+        let seconds: f64 = START_COURSE.iter().map(|leg| leg.seconds).sum();
+        for _ in 0..(seconds * 10.0) as usize {
+            sim.step();
+        }
+        let end = sim.fix().location.unwrap();
+        assert!(distance(start, end) < 10.0, "{} m", distance(start, end));
+    }
+
+    #[test]
+    fn test_noise() {
+        let config = SimConfig {
+            course: vec![Leg {
+                heading: 0.0,
+                seconds: 1000.0,
+                speed: 0.0,
+            }],
+            noise: 3.0,
+            ..Default::default()
+        };
+        let mut sim = Sim::new(config, START_MS);
+        // stopped, so the truth is the first position
+        let truth = quiet(START_COURSE).fix().location.unwrap();
+
+        let count = 2000;
+        let mut sum = 0.0;
+        let mut sum_squares = 0.0;
+        for _ in 0..count {
+            let fix = sim.fix();
+            let error = (fix.location.unwrap().0 - truth.0) * METRES_PER_DEGREE;
+            sum += error;
+            sum_squares += error * error;
+            assert!(fix.sog.unwrap() >= 0.0);
+        }
+        let mean = sum / count as f64;
+        let deviation = (sum_squares / count as f64 - mean * mean).sqrt();
+        assert!(mean.abs() < 0.3, "mean {}", mean);
+        assert!((deviation - 3.0).abs() < 0.3, "deviation {}", deviation);
+    }
+}
@@ -0,0 +1,148 @@
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use async_io::Async;
+use edge_net::{
+    nal::{TcpConnect, UdpBind, UdpReceive},
+    std::{Stack, UdpSocket},
+};
+use embassy_time::{Duration, Instant, Timer};
+use gps::{next_update, AsyncReader, NMEAState, RingBuffer};
+
+use common::http::HttpHandler;
+
+use crate::sim::{self, SimConfig};
+use crate::EngineType;
+
+/// Where the desktop build gets its fixes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum GpsSource {
+    Tcp(SocketAddr),
+    Udp(u16),
+    Serial(PathBuf),
+    File(PathBuf),
+    Sim(SimConfig),
+}
+
+struct IoReader<R: embedded_io_async::Read>(R);
+impl<R: embedded_io_async::Read> AsyncReader for IoReader<R> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        self.0.read(buf).await.map_err(|_| ())
+    }
+}
+
+// A datagram has to be read in one go, so buffer it and hand it out in whatever
+// sized pieces the ring buffer has room for
+struct UdpReader {
+    socket: UdpSocket,
+    datagram: [u8; 1500],
+    start: usize,
+    end: usize,
+}
+
+impl AsyncReader for UdpReader {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        while self.start == self.end {
+            let (len, _) = self
+                .socket
+                .receive(&mut self.datagram)
+                .await
+                .map_err(|_| ())?;
+            self.start = 0;
+            self.end = len;
+        }
+        let len = buf.len().min(self.end - self.start);
+        buf[..len].copy_from_slice(&self.datagram[self.start..self.start + len]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+struct SerialReader(Async<File>);
+impl AsyncReader for SerialReader {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        self.0
+            .read_with(|mut file| file.read(buf))
+            .await
+            .map_err(|_| ())
+    }
+}
+
+// Regular files are always ready, read them directly
+struct FileReader(File);
+impl AsyncReader for FileReader {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        self.0.read(buf).map_err(|_| ())
+    }
+}
+
+/// Feed fixes to the handler until the reader fails or runs out
+async fn forward<Reader: AsyncReader>(
+    reader: Reader,
+    handler: &'static HttpHandler<EngineType>,
+    paced: bool,
+) {
+    let mut ring_buffer = RingBuffer::<Reader, 128>::new(reader);
+    let mut state = NMEAState::default();
+    // (GPS time, local time) of the first fix, to pace a log
+    let mut origin: Option<(u64, Instant)> = None;
+
+    while let Some(fix) = next_update(&mut ring_buffer, &mut state).await {
+        if let (true, Some(time)) = (paced, fix.time) {
+            let (first, started) = *origin.get_or_insert((time, Instant::now()));
+            Timer::at(started + Duration::from_millis(time.saturating_sub(first))).await;
+        }
+        handler.set_gps_dropped(state.stats.dropped);
+        handler.location_event(fix).await;
+    }
+}
+
+#[embassy_executor::task]
+pub async fn gps_task(
+    source: GpsSource,
+    stack: &'static Stack,
+    handler: &'static HttpHandler<EngineType>,
+) {
+    log::info!("GPS source: {:?}", source);
+    loop {
+        match &source {
+            GpsSource::Tcp(addr) => match stack.connect(*addr).await {
+                Ok(socket) => forward(IoReader(socket), handler, false).await,
+                Err(e) => log::error!("Failed to connect to {}: {:?}", addr, e),
+            },
+            GpsSource::Udp(port) => {
+                let local = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), *port);
+                match stack.bind(local).await {
+                    Ok(socket) => {
+                        let reader = UdpReader {
+                            socket,
+                            datagram: [0; 1500],
+                            start: 0,
+                            end: 0,
+                        };
+                        forward(reader, handler, false).await
+                    }
+                    Err(e) => log::error!("Failed to bind UDP port {}: {:?}", port, e),
+                }
+            }
+            GpsSource::Serial(path) => match File::open(path).and_then(Async::new) {
+                Ok(file) => forward(SerialReader(file), handler, false).await,
+                Err(e) => log::error!("Failed to open {}: {:?}", path.display(), e),
+            },
+            GpsSource::File(path) => {
+                match File::open(path) {
+                    Ok(file) => forward(FileReader(file), handler, true).await,
+                    Err(e) => log::error!("Failed to open {}: {:?}", path.display(), e),
+                }
+                log::info!("End of {}", path.display());
+                return;
+            }
+            GpsSource::Sim(config) => sim::run(config.clone(), handler).await,
+        }
+
+        // stream dropped, or never came up
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
@@ -0,0 +1,35 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use common::storage::Storage;
+
+/// Keeps the saved state in a file
+pub struct FileStorage {
+    pub path: PathBuf,
+}
+
+impl Storage for FileStorage {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        match std::fs::read(&self.path) {
+            Ok(data) => {
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                Ok(len)
+            }
+            // first run
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(0),
+            Err(e) => {
+                log::error!("Failed to read {}: {:?}", self.path.display(), e);
+                Err(())
+            }
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), ()> {
+        // write alongside and rename, so a crash can't leave half a file
+        let temp = self.path.with_extension("tmp");
+        std::fs::write(&temp, data)
+            .and_then(|_| std::fs::rename(&temp, &self.path))
+            .map_err(|e| log::error!("Failed to write {}: {:?}", self.path.display(), e))
+    }
+}
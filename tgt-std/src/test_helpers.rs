@@ -0,0 +1,23 @@
+use extreme_traits::{Fix, FixMode, FixStatus, RawEngine};
+
+use crate::EngineType;
+
+pub fn event(engine: &mut EngineType, event: &str) {
+    assert!(
+        engine.external_event(0, event.as_bytes()).is_ok(),
+        "{}",
+        event
+    );
+}
+
+pub fn fix(location: (f64, f64), sog: f64, cog: f64) -> Fix {
+    Fix {
+        location: Some(location),
+        sog: Some(sog),
+        cog: Some(cog),
+        hdop: Some(1.23456789),
+        status: FixStatus::Active,
+        mode: FixMode::Autonomous,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,39 @@
+use common::storage::{Storage, MAX_RECORD_SIZE};
+use embedded_storage::{ReadStorage, Storage as _};
+
+// The last sector of the XIAO's 4MB flash, well clear of the app partition
+const FLASH_SIZE: u32 = 4 * 1024 * 1024;
+const STORAGE_OFFSET: u32 = FLASH_SIZE - esp_storage::FlashStorage::SECTOR_SIZE;
+
+/// Keeps the saved state in the last sector of flash. esp-storage calls the ROM flash
+/// routines from RAM, and erases the sector before writing it.
+pub struct FlashStorage {
+    flash: esp_storage::FlashStorage,
+}
+
+impl FlashStorage {
+    pub fn new() -> Self {
+        Self {
+            flash: esp_storage::FlashStorage::new(),
+        }
+    }
+}
+
+impl Storage for FlashStorage {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        let len = buf.len().min(MAX_RECORD_SIZE);
+        self.flash
+            .read(STORAGE_OFFSET, &mut buf[..len])
+            .map_err(|e| log::error!("Failed to read flash: {:?}", e))?;
+        Ok(len)
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), ()> {
+        if data.len() > MAX_RECORD_SIZE {
+            return Err(());
+        }
+        self.flash
+            .write(STORAGE_OFFSET, data)
+            .map_err(|e| log::error!("Failed to write flash: {:?}", e))
+    }
+}
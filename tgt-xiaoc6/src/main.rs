@@ -5,6 +5,7 @@
 use core::{net::{Ipv4Addr, SocketAddr, IpAddr}, str::FromStr};
 use heapless::Vec;
 use static_cell::StaticCell;
+use embedded_io_async::Write;
 
 use embassy_executor::Spawner;
 use embassy_net::{Ipv4Cidr, Stack, StackResources, StaticConfigV4};
@@ -35,22 +36,22 @@ use edge_net::{
 
 // Local modules
 // mod http;
+mod flash_storage;
 mod network_tasks;
-mod nmea_parser;
 
 use common::http::{HttpHandler, MAX_WEB_SOCKETS, SOCKET_BUFFER_SIZE};
 use crate::{
+    flash_storage::FlashStorage,
     network_tasks::{dhcp_task, net_task, wifi_task},
-    nmea_parser::{next_update, AsyncReader, RingBuffer},
 };
+use gps::{next_ubx_update, AsyncReader, ReceiverConfig, UBXCommand, UBXReader, UBXStats};
 
-// UBX protocol constants
-// const UBX_SYNC1: u8 = 0xB5;
-// const UBX_SYNC2: u8 = 0x62;
-// const UBX_CLASS_CFG: u8 = 0x06;
-// const UBX_CFG_RXM: u8 = 0x11;
-// const UBX_CFG_RXM_POWER_MODE: u8 = 0x02; // standby
-
+// u-blox receiver, NAV-PVT at 10Hz needs more than the 9600 baud it boots with
+const GPS_CONFIG: ReceiverConfig = ReceiverConfig {
+    boot_baud: 9600,
+    baud: 115_200,
+    rate_ms: 100,
+};
 
 use extreme_traits::{define_engines, MAX_MESSAGE_SIZE};
 
@@ -125,19 +126,21 @@ async fn main(spawner: Spawner) {
     static HTTPD_HANDLER: StaticCell<HttpHandler<EngineType>> = StaticCell::new();
     let httpd_handler = HTTPD_HANDLER.init(HttpHandler::new(EngineType::default()));
 
+    spawner.spawn(storage_task(FlashStorage::new(), httpd_handler)).ok();
     spawner.spawn(httpd_task(stack, httpd_handler)).ok();
 
     let (tx_pin, rx_pin) = (peripherals.GPIO16, peripherals.GPIO17);
     let config = UartConfig::default()
-        .with_baudrate(9600)
+        .with_baudrate(GPS_CONFIG.boot_baud)
         .with_rx(RxConfig::default())
        ; // .with_fifo_full_threshold(READ_BUF_SIZE as u16));
 
-    let uart0 = Uart::new(peripherals.UART0, config)
+    let mut uart0 = Uart::new(peripherals.UART0, config)
         .unwrap()
         .with_tx(tx_pin)
         .with_rx(rx_pin)
         .into_async();
+    init_ubx(&mut uart0).await;
     let (uart_rx, mut _uart_tx) = uart0.split();
     spawner.spawn(gps_task(uart_rx, httpd_handler)).ok(); 
 
@@ -153,6 +156,11 @@ async fn main(spawner: Spawner) {
 }
 
 
+#[embassy_executor::task]
+pub async fn storage_task(mut storage: FlashStorage, handler: &'static HttpHandler<EngineType>) {
+    handler.run_storage(&mut storage).await
+}
+
 #[embassy_executor::task]
 pub async fn sleeper_task(handler: &'static HttpHandler<EngineType>) {
     handler.run_sleeper().await
@@ -197,10 +205,7 @@ struct UartReader(UartRx<'static, Async>);
 impl AsyncReader for UartReader {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
         match self.0.read_async(buf).await {
-            Ok(_) => {
-                
-                Ok(buf.len())
-            },
+            Ok(len) => Ok(len),
             Err(_) => {
                 println!("Failed to read from UART");
                 Err(())
@@ -214,52 +219,49 @@ pub async fn gps_task(
     rx: UartRx<'static, Async>,
     handler: &'static HttpHandler<EngineType>,
 ) {
-    let mut ring_buffer = RingBuffer::<UartReader, 32>::new(UartReader(rx));
+    let mut ubx_reader = UBXReader::<UartReader, 32>::new(UartReader(rx));
+    let mut stats = UBXStats::default();
     loop {
-        let (time, location, speed) = next_update(&mut ring_buffer).await;
-        // println!("Time: {:?}, Location: {:?}, Speed: {:?}", time, location, speed);
-        handler.location_event(time, location, speed).await;
+        match next_ubx_update(&mut ubx_reader, &mut stats).await {
+            Some(fix) => {
+                // println!("Fix: {:?}", fix);
+                handler.set_gps_dropped(stats.dropped);
+                handler.location_event(fix).await;
+            }
+            None => Timer::after(Duration::from_millis(100)).await,
+        }
     }
 }
 
-// async fn send_ubx_command(tx: &mut UartTx<'static, Async>, class: u8, id: u8, payload: &[u8]) {
-//     let mut buffer: [u8; 64] = [0; 64];
-//     let mut pos = 0;
-
-//     // Header
-//     buffer[pos] = UBX_SYNC1; pos += 1;
-//     buffer[pos] = UBX_SYNC2; pos += 1;
-//     buffer[pos] = class; pos += 1;
-//     buffer[pos] = id; pos += 1;
-    
-//     // Length (little endian)
-//     buffer[pos] = payload.len() as u8; pos += 1;
-//     buffer[pos] = 0; pos += 1;
-
-//     // Payload
-//     for &byte in payload {
-//         buffer[pos] = byte;
-//         pos += 1;
-//     }
-
-//     // Calculate checksum
-//     let (ck_a, ck_b) = calculate_ubx_checksum(&buffer[2..pos]);
-//     buffer[pos] = ck_a; pos += 1;
-//     buffer[pos] = ck_b; pos += 1;
-
-//     if let Err(e) = tx.write_async(&buffer[..pos]).await {
-//         log::error!("Failed to send UBX command: {:?}", e);
-//     }
-// }
-
-// fn calculate_ubx_checksum(data: &[u8]) -> (u8, u8) {
-//     let mut ck_a: u8 = 0;
-//     let mut ck_b: u8 = 0;
-
-//     for &byte in data {
-//         ck_a = ck_a.wrapping_add(byte);
-//         ck_b = ck_b.wrapping_add(ck_a);
-//     }
-
-//     (ck_a, ck_b)
-// }
\ No newline at end of file
+/// Switch the receiver to UBX output at the configured baud and fix rate
+async fn init_ubx(uart: &mut Uart<'static, Async>) {
+    send_ubx_command(uart, GPS_CONFIG.ubx_baud()).await;
+
+    // the receiver acks at the old rate before switching
+    Timer::after(Duration::from_millis(100)).await;
+    let config = UartConfig::default()
+        .with_baudrate(GPS_CONFIG.baud)
+        .with_rx(RxConfig::default());
+    if let Err(e) = uart.apply_config(&config) {
+        log::error!("Failed to set GPS baud rate: {:?}", e);
+    }
+
+    for command in GPS_CONFIG.ubx_setup() {
+        send_ubx_command(uart, command).await;
+    }
+}
+
+async fn send_ubx_command(uart: &mut Uart<'static, Async>, command: UBXCommand) {
+    let mut buffer: [u8; 64] = [0; 64];
+    let len = match command.encode(&mut buffer) {
+        Ok(len) => len,
+        Err(_) => {
+            log::error!("Failed to encode UBX command: {:?}", command);
+            return;
+        }
+    };
+
+    if let Err(e) = uart.write_all(&buffer[..len]).await {
+        log::error!("Failed to send UBX command: {:?}", e);
+    }
+}